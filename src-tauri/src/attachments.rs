@@ -0,0 +1,222 @@
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+// 反馈附件：GUI 把用户粘贴或拖入的文件保存到共享存储目录，反馈消息里只传递文件路径
+
+const DEFAULT_MAX_ATTACHMENT_BYTES: u64 = 10 * 1024 * 1024;
+const DEFAULT_MAX_ATTACHMENT_COUNT: usize = 5;
+
+/// 已保存的反馈附件
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct FeedbackAttachment {
+    pub name: String,
+    pub mime_type: String,
+    pub size: u64,
+    pub path: String,
+}
+
+/// 前端提交的附件 (base64 编码的文件内容)
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AttachmentUpload {
+    pub name: String,
+    #[serde(default)]
+    pub mime_type: String,
+    pub data: String,
+}
+
+/// 附件大小和数量限制 (MCP_ATTACHMENT_MAX_BYTES、MCP_ATTACHMENT_MAX_COUNT 环境变量)
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AttachmentLimits {
+    pub max_bytes: u64,
+    pub max_count: usize,
+}
+
+pub fn get_attachment_limits() -> AttachmentLimits {
+    let env = |name: &str| std::env::var(name).ok().and_then(|value| value.trim().parse::<u64>().ok());
+    AttachmentLimits {
+        max_bytes: env("MCP_ATTACHMENT_MAX_BYTES").unwrap_or(DEFAULT_MAX_ATTACHMENT_BYTES),
+        max_count: env("MCP_ATTACHMENT_MAX_COUNT").map(|count| count as usize).unwrap_or(DEFAULT_MAX_ATTACHMENT_COUNT),
+    }
+}
+
+pub fn get_attachments_root() -> PathBuf {
+    let mut path = crate::get_shared_storage_dir();
+    path.push("attachments");
+    path
+}
+
+// 只保留文件名中的安全字符，防止路径穿越
+fn sanitize_file_name(name: &str) -> String {
+    let base = name.rsplit(['/', '\\']).next().unwrap_or(name);
+    let sanitized: String = base
+        .chars()
+        .map(|c| if c.is_alphanumeric() || matches!(c, '.' | '-' | '_' | ' ') { c } else { '_' })
+        .collect();
+    let sanitized = sanitized.trim().trim_start_matches('.').to_string();
+    if sanitized.is_empty() { "attachment".to_string() } else { sanitized }
+}
+
+/// 校验并保存附件到 `attachments/<session_id>/`
+pub fn save_attachments(root: &Path, session_id: &str, uploads: Vec<AttachmentUpload>, limits: AttachmentLimits) -> Result<Vec<FeedbackAttachment>> {
+    use base64::Engine;
+
+    if uploads.len() > limits.max_count {
+        return Err(anyhow::anyhow!("Too many attachments: {} (limit {})", uploads.len(), limits.max_count));
+    }
+
+    // 先全部解码校验，避免只保存了一部分附件
+    let mut decoded = Vec::new();
+    for upload in uploads {
+        let bytes = base64::engine::general_purpose::STANDARD
+            .decode(upload.data.trim())
+            .map_err(|e| anyhow::anyhow!("Attachment '{}' is not valid base64: {}", upload.name, e))?;
+        if bytes.len() as u64 > limits.max_bytes {
+            return Err(anyhow::anyhow!(
+                "Attachment '{}' is {} bytes, larger than the {} byte limit",
+                upload.name, bytes.len(), limits.max_bytes
+            ));
+        }
+        decoded.push((upload, bytes));
+    }
+    if decoded.is_empty() {
+        return Ok(Vec::new());
+    }
+
+    let dir = root.join(sanitize_file_name(session_id));
+    fs::create_dir_all(&dir)?;
+    let mut attachments = Vec::new();
+    for (index, (upload, bytes)) in decoded.into_iter().enumerate() {
+        let path = dir.join(format!("{}-{}", index + 1, sanitize_file_name(&upload.name)));
+        fs::write(&path, &bytes)?;
+        let mime_type = if upload.mime_type.trim().is_empty() {
+            "application/octet-stream".to_string()
+        } else {
+            upload.mime_type
+        };
+        attachments.push(FeedbackAttachment {
+            name: upload.name,
+            mime_type,
+            size: bytes.len() as u64,
+            path: path.to_string_lossy().to_string(),
+        });
+    }
+    Ok(attachments)
+}
+
+/// 读取附件内容，只允许读取附件目录中且未超过大小限制的文件
+pub fn read_attachment(root: &Path, attachment: &FeedbackAttachment, limits: AttachmentLimits) -> Result<Vec<u8>> {
+    let path = fs::canonicalize(&attachment.path)?;
+    let root = fs::canonicalize(root)?;
+    if !path.starts_with(&root) {
+        return Err(anyhow::anyhow!("Attachment '{}' is outside the attachments directory", attachment.name));
+    }
+    let size = fs::metadata(&path)?.len();
+    if size > limits.max_bytes {
+        return Err(anyhow::anyhow!("Attachment '{}' exceeds the {} byte limit", attachment.name, limits.max_bytes));
+    }
+    Ok(fs::read(&path)?)
+}
+
+/// 删除会话的附件目录；附件内容已嵌入反馈结果，结果返回后不再需要
+pub fn remove_session_attachments(root: &Path, session_id: &str) -> Result<()> {
+    let dir = root.join(sanitize_file_name(session_id));
+    match fs::remove_dir_all(&dir) {
+        Err(e) if e.kind() != std::io::ErrorKind::NotFound => Err(e.into()),
+        _ => Ok(()),
+    }
+}
+
+/// 附件作为文本返回时使用的 MIME 类型，不是文本文件时返回 None。
+/// 前端未提供类型 (application/octet-stream) 的附件按扩展名判断
+pub fn text_mime_type(attachment: &FeedbackAttachment) -> Option<String> {
+    let mime_type = attachment.mime_type.as_str();
+    let essence = mime_type.split(';').next().unwrap_or_default().trim();
+    let is_text = essence.starts_with("text/")
+        || essence.ends_with("+json")
+        || essence.ends_with("+xml")
+        || matches!(
+            essence,
+            "application/json" | "application/xml" | "application/javascript" | "application/x-yaml" | "application/yaml"
+                | "application/toml" | "application/x-sh" | "application/sql"
+        );
+    if is_text {
+        return Some(mime_type.to_string());
+    }
+    if essence != "application/octet-stream" {
+        return None;
+    }
+
+    let extension = Path::new(&attachment.name).extension()?.to_str()?.to_ascii_lowercase();
+    let mime_type = match extension.as_str() {
+        "md" | "markdown" => "text/markdown",
+        "json" => "application/json",
+        "html" | "htm" => "text/html",
+        "csv" => "text/csv",
+        "txt" | "log" | "diff" | "patch" | "xml" | "yaml" | "yml" | "toml" | "ini" | "cfg" | "conf" | "sh" | "sql" | "rs" | "py"
+        | "js" | "ts" | "tsx" | "jsx" | "vue" | "go" | "java" | "kt" | "c" | "h" | "cpp" | "hpp" | "cs" | "rb" | "php" | "swift"
+        | "css" | "scss" => "text/plain",
+        _ => return None,
+    };
+    Some(mime_type.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use base64::Engine;
+
+    fn upload(name: &str, bytes: &[u8]) -> AttachmentUpload {
+        AttachmentUpload {
+            name: name.to_string(),
+            mime_type: String::new(),
+            data: base64::engine::general_purpose::STANDARD.encode(bytes),
+        }
+    }
+
+    #[test]
+    fn test_save_and_read_attachments() {
+        let root = std::env::temp_dir().join(format!("mcp_attachments_test_{}", uuid::Uuid::new_v4()));
+        let limits = AttachmentLimits { max_bytes: 16, max_count: 2 };
+
+        let saved = save_attachments(&root, "session", vec![upload("../../etc/passwd", b"log line")], limits).unwrap();
+        assert_eq!(saved.len(), 1);
+        assert_eq!(saved[0].mime_type, "application/octet-stream");
+        assert!(Path::new(&saved[0].path).starts_with(root.join("session")));
+        assert!(saved[0].path.ends_with("1-passwd"));
+        assert_eq!(read_attachment(&root, &saved[0], limits).unwrap(), b"log line");
+
+        assert!(save_attachments(&root, "session", vec![upload("big.bin", &[0; 17])], limits).is_err());
+        assert!(save_attachments(&root, "session", vec![upload("a", b"a"), upload("b", b"b"), upload("c", b"c")], limits).is_err());
+
+        // 附件目录以外的文件不允许读取
+        let outside = FeedbackAttachment { path: std::env::temp_dir().to_string_lossy().to_string(), ..saved[0].clone() };
+        assert!(read_attachment(&root, &outside, limits).is_err());
+
+        remove_session_attachments(&root, "session").unwrap();
+        assert!(!root.join("session").exists());
+        remove_session_attachments(&root, "session").unwrap();
+        fs::remove_dir_all(&root).ok();
+    }
+
+    #[test]
+    fn test_text_mime_type() {
+        let attachment = |name: &str, mime_type: &str| FeedbackAttachment {
+            name: name.to_string(),
+            mime_type: mime_type.to_string(),
+            size: 0,
+            path: String::new(),
+        };
+        assert_eq!(text_mime_type(&attachment("a", "text/x-log; charset=utf-8")).as_deref(), Some("text/x-log; charset=utf-8"));
+        assert_eq!(text_mime_type(&attachment("a", "application/vnd.api+json")).as_deref(), Some("application/vnd.api+json"));
+        assert_eq!(text_mime_type(&attachment("build.log", "application/octet-stream")).as_deref(), Some("text/plain"));
+        assert_eq!(text_mime_type(&attachment("notes.MD", "application/octet-stream")).as_deref(), Some("text/markdown"));
+        assert_eq!(text_mime_type(&attachment("blob.bin", "application/octet-stream")), None);
+        assert_eq!(text_mime_type(&attachment("passwd", "application/octet-stream")), None);
+        assert_eq!(text_mime_type(&attachment("a.pdf", "application/pdf")), None);
+    }
+}
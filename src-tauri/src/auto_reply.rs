@@ -0,0 +1,259 @@
+use anyhow::Result;
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use std::fs;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+use crate::templates;
+
+// 自动回复规则：GUI 收到反馈请求时按顺序匹配规则，第一条匹配的规则生效
+
+/// 一条自动回复规则，所有匹配条件均可省略 (省略的条件视为匹配)
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct AutoReplyRule {
+    pub name: String,
+    #[serde(default = "default_enabled")]
+    pub enabled: bool,
+    /// AI 来源 (MCP_SOURCE 值，不区分大小写)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub ai_source: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub context_regex: Option<String>,
+    /// 匹配 AI 回复内容
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub content_regex: Option<String>,
+    pub action: RuleAction,
+}
+
+fn default_enabled() -> bool {
+    true
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum RuleAction {
+    /// 自动回复，text 支持快捷回复模板变量；after_seconds 大于 0 时，只有用户这段时间内既没有回复也没有编辑回复才自动回复
+    Reply {
+        text: String,
+        #[serde(default)]
+        after_seconds: u64,
+    },
+    /// 延迟显示请求，期间不打扰用户
+    Delay { seconds: u64 },
+    /// 转交请求：silent 静默显示 (不播放提示音、不置顶窗口)，cancel 直接取消
+    Route {
+        to: RouteTarget,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        message: Option<String>,
+    },
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RouteTarget {
+    Silent,
+    Cancel,
+}
+
+#[derive(Debug, Default, Deserialize)]
+struct RulesFile {
+    #[serde(default)]
+    rules: Vec<AutoReplyRule>,
+}
+
+struct CompiledRule {
+    rule: AutoReplyRule,
+    context: Option<Regex>,
+    content: Option<Regex>,
+}
+
+/// 编译后的规则列表
+pub struct RuleSet {
+    rules: Vec<CompiledRule>,
+}
+
+impl RuleSet {
+    pub fn compile(rules: Vec<AutoReplyRule>) -> Result<Self> {
+        let compile = |rule: &AutoReplyRule, field: &str, pattern: &Option<String>| -> Result<Option<Regex>> {
+            pattern
+                .as_deref()
+                .map(|pattern| Regex::new(pattern).map_err(|e| anyhow::anyhow!("Rule '{}': invalid {}: {}", rule.name, field, e)))
+                .transpose()
+        };
+
+        let mut compiled = Vec::new();
+        for rule in rules {
+            let context = compile(&rule, "context_regex", &rule.context_regex)?;
+            let content = compile(&rule, "content_regex", &rule.content_regex)?;
+            compiled.push(CompiledRule { rule, context, content });
+        }
+        Ok(Self { rules: compiled })
+    }
+
+    /// 从规则文件加载规则 (扩展名为 .toml 时按 TOML 解析，否则按 JSON 解析)，文件不存在时没有规则
+    pub fn load(path: &Path) -> Result<Self> {
+        if !path.exists() {
+            return Ok(Self { rules: Vec::new() });
+        }
+        let content = fs::read_to_string(path)?;
+        let is_toml = path.extension().is_some_and(|ext| ext.eq_ignore_ascii_case("toml"));
+        let file: RulesFile = if is_toml {
+            toml::from_str(&content).map_err(|e| anyhow::anyhow!("Failed to parse {}: {}", path.display(), e))?
+        } else {
+            serde_json::from_str(&content).map_err(|e| anyhow::anyhow!("Failed to parse {}: {}", path.display(), e))?
+        };
+        Self::compile(file.rules)
+    }
+
+    pub fn load_default() -> Result<Self> {
+        Self::load(&get_rules_path())
+    }
+
+    pub fn len(&self) -> usize {
+        self.rules.len()
+    }
+
+    /// 返回第一条匹配请求的启用规则
+    pub fn find_match(&self, request: &Value) -> Option<&AutoReplyRule> {
+        let text = |key: &str| request[key].as_str().unwrap_or_default();
+        self.rules
+            .iter()
+            .find(|compiled| {
+                let rule = &compiled.rule;
+                rule.enabled
+                    && rule.ai_source.as_deref().is_none_or(|source| source.eq_ignore_ascii_case(text("aiSource")))
+                    && compiled.context.as_ref().is_none_or(|regex| regex.is_match(text("context")))
+                    && compiled.content.as_ref().is_none_or(|regex| regex.is_match(text("aiResponse")))
+            })
+            .map(|compiled| &compiled.rule)
+    }
+}
+
+/// 规则文件路径 (MCP_AUTO_REPLY_RULES 环境变量，默认为共享存储目录下的 auto_reply_rules.json)
+pub fn get_rules_path() -> PathBuf {
+    if let Some(path) = std::env::var_os("MCP_AUTO_REPLY_RULES").filter(|path| !path.is_empty()) {
+        return PathBuf::from(path);
+    }
+    let mut path = crate::get_shared_storage_dir();
+    path.push("auto_reply_rules.json");
+    path
+}
+
+fn get_log_path() -> PathBuf {
+    let mut path = crate::get_shared_storage_dir();
+    fs::create_dir_all(&path).ok();
+    path.push("auto_reply.log");
+    path
+}
+
+/// 渲染自动回复内容，与快捷回复模板使用相同的变量
+pub fn render_reply(text: &str, request: &Value) -> String {
+    templates::render_template(text, request)
+}
+
+/// 模拟匹配请求，不执行任何动作
+pub fn dry_run(rules: &RuleSet, request: &Value) -> Value {
+    match rules.find_match(request) {
+        Some(rule) => {
+            let mut result = json!({
+                "matched": true,
+                "rule": rule.name,
+                "action": rule.action
+            });
+            if let RuleAction::Reply { text, .. } = &rule.action {
+                result["reply"] = json!(render_reply(text, request));
+            }
+            result
+        }
+        None => json!({ "matched": false }),
+    }
+}
+
+/// 记录自动执行的动作 (JSON Lines)
+pub fn log_action(session_id: &str, rule: &AutoReplyRule, action: &str, detail: &str) {
+    eprintln!("🤖 Auto-reply rule '{}' {} session {}: {}", rule.name, action, session_id, detail);
+    let entry = json!({
+        "timestamp": chrono::Utc::now().to_rfc3339(),
+        "sessionId": session_id,
+        "rule": rule.name,
+        "action": action,
+        "detail": detail
+    });
+    let written = fs::OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(get_log_path())
+        .and_then(|mut file| writeln!(file, "{}", entry));
+    if let Err(e) = written {
+        eprintln!("⚠️ Failed to write auto-reply log: {}", e);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rules(value: Value) -> Result<RuleSet> {
+        RuleSet::compile(serde_json::from_value(value).unwrap())
+    }
+
+    #[test]
+    fn test_rule_matching() {
+        let rules = rules(json!([
+            { "name": "disabled", "enabled": false, "action": { "type": "delay", "seconds": 5 } },
+            {
+                "name": "augment",
+                "ai_source": "Augment",
+                "context_regex": "(?i)refactor",
+                "action": { "type": "reply", "text": "continue with {context}", "after_seconds": 600 }
+            },
+            { "name": "deploy", "content_regex": "deploy", "action": { "type": "route", "to": "cancel" } }
+        ]))
+        .unwrap();
+        assert_eq!(rules.len(), 3);
+
+        let request = json!({ "aiSource": "augment", "context": "Big Refactor", "aiResponse": "done" });
+        assert_eq!(rules.find_match(&request).unwrap().name, "augment");
+        let result = dry_run(&rules, &request);
+        assert_eq!(result["reply"], "continue with Big Refactor");
+        assert_eq!(result["action"]["after_seconds"], 600);
+
+        let request = json!({ "aiSource": "cursor", "context": "Refactor", "aiResponse": "ready to deploy?" });
+        assert_eq!(rules.find_match(&request).unwrap().name, "deploy");
+        assert_eq!(dry_run(&rules, &json!({ "aiSource": "cursor", "aiResponse": "hi" })), json!({ "matched": false }));
+    }
+
+    #[test]
+    fn test_invalid_rules_are_rejected() {
+        let error = rules(json!([{ "name": "bad", "context_regex": "(", "action": { "type": "delay", "seconds": 1 } }]));
+        assert!(error.err().unwrap().to_string().contains("Rule 'bad'"));
+        assert!(serde_json::from_value::<Vec<AutoReplyRule>>(json!([{ "name": "x", "action": { "type": "route", "to": "slack" } }])).is_err());
+    }
+    #[test]
+    fn test_load_rules_by_extension() {
+        let dir = std::env::temp_dir().join(format!("mcp_rules_{}", uuid::Uuid::new_v4()));
+        fs::create_dir_all(&dir).unwrap();
+
+        let toml_path = dir.join("rules.toml");
+        fs::write(&toml_path, r#"
+[[rules]]
+name = "augment-idle"
+ai_source = "augment"
+action = { type = "reply", text = "continue", after_seconds = 600 }
+"#).unwrap();
+        let rules = RuleSet::load(&toml_path).unwrap();
+        let rule = rules.find_match(&json!({ "aiSource": "augment" })).unwrap();
+        assert_eq!(rule.action, RuleAction::Reply { text: "continue".to_string(), after_seconds: 600 });
+
+        let json_path = dir.join("rules.json");
+        fs::write(&json_path, r#"{ "rules": [{ "name": "night", "action": { "type": "delay", "seconds": 5 } }] }"#).unwrap();
+        assert_eq!(RuleSet::load(&json_path).unwrap().len(), 1);
+
+        // TOML 内容不能按 JSON 解析
+        fs::copy(&toml_path, &json_path).unwrap();
+        assert!(RuleSet::load(&json_path).is_err());
+        fs::remove_dir_all(&dir).ok();
+    }
+}
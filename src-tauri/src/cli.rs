@@ -0,0 +1,303 @@
+use anyhow::Result;
+use serde_json::{json, Value};
+use std::io::Read;
+
+use crate::auto_reply::{self, RuleSet};
+use crate::feedback_ipc::FeedbackResponse;
+use crate::feedback_store::{self, PendingSession};
+use crate::history::{ExportFormat, FeedbackHistory, HistoryQuery};
+
+// 命令行子命令，在启动 GUI 或 MCP 服务器之前处理
+
+const HISTORY_USAGE: &str = "\
+Usage: cc-custom-mcp history export [options]
+
+Options:
+  --format <markdown|jsonl|csv|json>  Output format (default: markdown)
+  --since <date>                      Only sessions requested on or after this date (YYYY-MM-DD or RFC 3339)
+  --until <date>                      Only sessions requested on or before this date
+  --source <ai_source>                Only sessions from this AI source (MCP_SOURCE value)
+  --context <context>                 Only sessions with this context
+  --status <answered|cancelled|timed_out>
+  --query <text>                      Full-text search
+  --output <file>                     Write to a file instead of stdout";
+
+const RULES_USAGE: &str = "\
+Usage: cc-custom-mcp rules check [--rules <file>]
+       cc-custom-mcp rules dry-run [--rules <file>] [--source <ai_source>] [--context <context>] [--message <ai_response>]
+
+Validates the auto-reply rules file, or shows which rule would handle a feedback request without acting on it.";
+
+const FEEDBACK_USAGE: &str = "\
+Usage: cc-custom-mcp feedback list [--json]
+       cc-custom-mcp feedback show <session_id>
+       cc-custom-mcp feedback reply <session_id> [text|-] [--option <value>]... [--form <json>]
+       cc-custom-mcp feedback cancel <session_id>
+
+Answers pending feedback requests without the GUI, e.g. over SSH. Session ids may be abbreviated
+to any unique prefix. Without text, options or form values the reply is read from stdin.";
+
+/// 执行命令行子命令，不是子命令时返回 None 继续正常启动
+pub fn run(args: &[String]) -> Option<i32> {
+    let result = match args.first().map(String::as_str) {
+        Some("history") => run_history(&args[1..]),
+        Some("rules") => run_rules(&args[1..]),
+        Some("feedback") => run_feedback(&args[1..]),
+        Some("tui") => crate::tui::run(),
+        _ => return None,
+    };
+    Some(match result {
+        Ok(()) => 0,
+        Err(e) => {
+            eprintln!("❌ {}", e);
+            2
+        }
+    })
+}
+
+fn run_history(args: &[String]) -> Result<()> {
+    match args.first().map(String::as_str) {
+        Some("export") => {}
+        Some("--help") | Some("-h") => {
+            println!("{}", HISTORY_USAGE);
+            return Ok(());
+        }
+        _ => return Err(anyhow::anyhow!("Unknown history command\n\n{}", HISTORY_USAGE)),
+    }
+
+    let mut format = ExportFormat::Markdown;
+    let mut query = HistoryQuery::default();
+    let mut output = None;
+    let mut options = args[1..].iter();
+    while let Some(option) = options.next() {
+        if option == "--help" || option == "-h" {
+            println!("{}", HISTORY_USAGE);
+            return Ok(());
+        }
+        let value = options
+            .next()
+            .cloned()
+            .ok_or_else(|| anyhow::anyhow!("Missing value for {}\n\n{}", option, HISTORY_USAGE))?;
+        match option.as_str() {
+            "--format" => {
+                format = ExportFormat::from_string(&value)
+                    .ok_or_else(|| anyhow::anyhow!("Unsupported format '{}', expected markdown, jsonl, csv or json", value))?;
+            }
+            "--since" => query.since = Some(value),
+            "--until" => query.until = Some(value),
+            "--source" => query.ai_source = Some(value),
+            "--context" => query.context = Some(value),
+            "--status" => query.status = Some(value),
+            "--query" => query.query = Some(value),
+            "--output" => output = Some(value),
+            _ => return Err(anyhow::anyhow!("Unknown option {}\n\n{}", option, HISTORY_USAGE)),
+        }
+    }
+
+    let content = FeedbackHistory::open_default()?.export(&query, format)?;
+    match output {
+        Some(path) => {
+            std::fs::write(&path, content)?;
+            eprintln!("✅ Feedback history exported to {}", path);
+        }
+        None => print!("{}", content),
+    }
+    Ok(())
+}
+
+fn run_rules(args: &[String]) -> Result<()> {
+    let command = args.first().map(String::as_str);
+    if !matches!(command, Some("check") | Some("dry-run")) {
+        if matches!(command, Some("--help") | Some("-h")) {
+            println!("{}", RULES_USAGE);
+            return Ok(());
+        }
+        return Err(anyhow::anyhow!("Unknown rules command\n\n{}", RULES_USAGE));
+    }
+
+    let mut path = auto_reply::get_rules_path();
+    let mut request = json!({
+        "sessionId": "dry-run",
+        "aiSource": "",
+        "aiSourceDisplay": "",
+        "context": "",
+        "aiResponse": ""
+    });
+    let mut options = args[1..].iter();
+    while let Some(option) = options.next() {
+        let value = options
+            .next()
+            .cloned()
+            .ok_or_else(|| anyhow::anyhow!("Missing value for {}\n\n{}", option, RULES_USAGE))?;
+        match option.as_str() {
+            "--rules" => path = value.into(),
+            "--source" => {
+                request["aiSourceDisplay"] = json!(value);
+                request["aiSource"] = json!(value);
+            }
+            "--context" => request["context"] = json!(value),
+            "--message" => request["aiResponse"] = json!(value),
+            _ => return Err(anyhow::anyhow!("Unknown option {}\n\n{}", option, RULES_USAGE)),
+        }
+    }
+
+    let rules = RuleSet::load(&path)?;
+    if command == Some("check") {
+        println!("✅ {} auto-reply rules in {}", rules.len(), path.display());
+    } else {
+        println!("{}", serde_json::to_string_pretty(&auto_reply::dry_run(&rules, &request))?);
+    }
+    Ok(())
+}
+
+fn run_feedback(args: &[String]) -> Result<()> {
+    let command = args.first().map(String::as_str);
+    let rt = tokio::runtime::Runtime::new()?;
+    match command {
+        Some("list") => {
+            let sessions = rt.block_on(feedback_store::list_pending_sessions());
+            if args[1..].iter().any(|arg| arg == "--json") {
+                let requests: Vec<&Value> = sessions.iter().map(|session| &session.request).collect();
+                println!("{}", serde_json::to_string_pretty(&requests)?);
+            } else if sessions.is_empty() {
+                println!("No pending feedback requests");
+            } else {
+                for session in &sessions {
+                    println!("{}", summary_line(session));
+                }
+            }
+            Ok(())
+        }
+        Some("show") | Some("reply") | Some("cancel") => {
+            let id = args
+                .get(1)
+                .ok_or_else(|| anyhow::anyhow!("Missing session id\n\n{}", FEEDBACK_USAGE))?;
+            let session = rt.block_on(feedback_store::find_session(id))?;
+            match command {
+                Some("show") => {
+                    print_session(&session);
+                    Ok(())
+                }
+                Some("reply") => {
+                    let response = parse_reply(&args[2..])?;
+                    rt.block_on(feedback_store::reply(&session, response))?;
+                    eprintln!("✅ Feedback sent to {}", session.session_id());
+                    Ok(())
+                }
+                _ => {
+                    rt.block_on(feedback_store::cancel(&session))?;
+                    eprintln!("🛑 Feedback request {} cancelled", session.session_id());
+                    Ok(())
+                }
+            }
+        }
+        Some("--help") | Some("-h") => {
+            println!("{}", FEEDBACK_USAGE);
+            Ok(())
+        }
+        _ => Err(anyhow::anyhow!("Unknown feedback command\n\n{}", FEEDBACK_USAGE)),
+    }
+}
+
+fn summary_line(session: &PendingSession) -> String {
+    let request = &session.request;
+    let text = |key: &str| request[key].as_str().unwrap_or_default().to_string();
+    let source = Some(text("aiSourceDisplay")).filter(|s| !s.is_empty()).unwrap_or_else(|| text("aiSource"));
+    let preview: String = text("aiResponse").split_whitespace().collect::<Vec<_>>().join(" ").chars().take(60).collect();
+    format!("{}  {:<16}  {:<24}  {}", text("sessionId"), source, text("context"), preview)
+}
+
+fn print_session(session: &PendingSession) {
+    let request = &session.request;
+    let text = |key: &str| request[key].as_str().unwrap_or_default().to_string();
+    println!("Session:   {}", session.session_id());
+    println!("Source:    {}", text("aiSourceDisplay"));
+    println!("Context:   {}", text("context"));
+    println!("Requested: {}", text("timestamp"));
+    println!();
+    println!("{}", text("aiResponse"));
+
+    if let Some(options) = request["options"].as_array().filter(|options| !options.is_empty()) {
+        let multi = request["multiSelect"].as_bool().unwrap_or(false);
+        println!();
+        println!("Options ({}, reply with --option <value>):", if multi { "choose any" } else { "choose one" });
+        for option in options {
+            let label = option["label"].as_str().unwrap_or_default();
+            println!("  {:<16} {}", option["value"].as_str().unwrap_or_default(), label);
+        }
+    }
+    if let Some(properties) = request["fields"]["properties"].as_object() {
+        let required: Vec<&str> = request["fields"]["required"].as_array().into_iter().flatten().filter_map(Value::as_str).collect();
+        println!();
+        println!("Form fields (reply with --form '{{\"name\": ...}}'):");
+        for (name, schema) in properties {
+            let marker = if required.contains(&name.as_str()) { " (required)" } else { "" };
+            println!("  {:<16} {}{}", name, schema["type"].as_str().unwrap_or("string"), marker);
+        }
+    }
+    if !request["allowFreeText"].as_bool().unwrap_or(true) {
+        println!();
+        println!("Free text replies are not accepted for this request.");
+    }
+}
+
+fn parse_reply(args: &[String]) -> Result<FeedbackResponse> {
+    let mut response = FeedbackResponse::default();
+    let mut text = None;
+    let mut options = args.iter();
+    while let Some(arg) = options.next() {
+        match arg.as_str() {
+            "--option" | "--form" => {
+                let value = options
+                    .next()
+                    .ok_or_else(|| anyhow::anyhow!("Missing value for {}\n\n{}", arg, FEEDBACK_USAGE))?;
+                if arg == "--option" {
+                    response.selected_options.get_or_insert_with(Vec::new).push(value.clone());
+                } else {
+                    let values: Value = serde_json::from_str(value).map_err(|e| anyhow::anyhow!("Invalid --form JSON: {}", e))?;
+                    response.form_values = Some(values);
+                }
+            }
+            _ if arg.starts_with("--") => return Err(anyhow::anyhow!("Unknown option {}\n\n{}", arg, FEEDBACK_USAGE)),
+            _ if text.is_none() => text = Some(arg.clone()),
+            _ => return Err(anyhow::anyhow!("Unexpected argument '{}', quote the reply text\n\n{}", arg, FEEDBACK_USAGE)),
+        }
+    }
+
+    let read_stdin = match text.as_deref() {
+        Some("-") => true,
+        Some(_) => false,
+        None => response.selected_options.is_none() && response.form_values.is_none(),
+    };
+    response.feedback = if read_stdin {
+        let mut input = String::new();
+        std::io::stdin().read_to_string(&mut input)?;
+        input.trim_end().to_string()
+    } else {
+        text.unwrap_or_default()
+    };
+    Ok(response)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn args(values: &[&str]) -> Vec<String> {
+        values.iter().map(|value| value.to_string()).collect()
+    }
+
+    #[test]
+    fn test_parse_reply() {
+        let response = parse_reply(&args(&["ship it", "--option", "a", "--option", "b", "--form", r#"{"name": "x"}"#])).unwrap();
+        assert_eq!(response.feedback, "ship it");
+        assert_eq!(response.selected_options, Some(vec!["a".to_string(), "b".to_string()]));
+        assert_eq!(response.form_values, Some(json!({ "name": "x" })));
+
+        let response = parse_reply(&args(&["--option", "a"])).unwrap();
+        assert_eq!(response.feedback, "");
+        assert!(parse_reply(&args(&["one", "two"])).is_err());
+        assert!(parse_reply(&args(&["--form", "{"])).is_err());
+        assert!(parse_reply(&args(&["--verbose"])).is_err());
+    }
+}
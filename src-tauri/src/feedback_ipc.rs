@@ -0,0 +1,507 @@
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::Duration;
+use tauri::{AppHandle, Emitter};
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+use tokio::sync::mpsc::UnboundedSender;
+
+use crate::attachments::FeedbackAttachment;
+
+// MCP 进程 (`--mcp-mode`) 与 GUI 之间的反馈通道：GUI 持有本地 socket (Windows 上为命名管道)，
+// 每个等待中的反馈请求占用一条连接。GUI 未运行或连接失败时回退到文件目录 IPC。
+
+// 单条消息的最大长度，防止异常数据导致大量内存分配
+const MAX_FRAME_LEN: u32 = 16 * 1024 * 1024;
+
+// 命名管道名称前缀，后接当前用户的 SID，不同用户的 GUI 各自使用自己的管道
+#[cfg(windows)]
+const PIPE_NAME_PREFIX: &str = r"\\.\pipe\cc-custom-mcp-feedback-";
+
+/// 用户提交的反馈：自由文本，以及可选的选项选择、表单填写结果和附件
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct FeedbackResponse {
+    #[serde(default)]
+    pub feedback: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub selected_options: Option<Vec<String>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub form_values: Option<Value>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub attachments: Vec<FeedbackAttachment>,
+}
+
+/// 反馈通道消息，以 4 字节大端长度前缀 + JSON 正文的帧格式传输
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum IpcMessage {
+    /// MCP 进程 → GUI：新的反馈请求，内容与请求文件相同
+    Request { request: Value },
+    /// GUI → MCP 进程：用户提交的反馈
+    Response { session_id: String, response: FeedbackResponse },
+    /// 双向：会话被取消 (用户在 GUI 中取消，或 AI 客户端取消了请求)
+    Cancel { session_id: String },
+    /// MCP 进程 → GUI：查询待处理的反馈请求
+    ListPending,
+    /// GUI → MCP 进程：待处理的反馈请求
+    Pending { sessions: Vec<Value> },
+    /// GUI → 命令行客户端：回复或取消的处理结果，error 为空表示已送达
+    Ack {
+        session_id: String,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        error: Option<String>,
+    },
+}
+
+pub async fn write_frame<W: AsyncWrite + Unpin>(writer: &mut W, message: &IpcMessage) -> Result<()> {
+    let body = serde_json::to_vec(message)?;
+    writer.write_u32(body.len() as u32).await?;
+    writer.write_all(&body).await?;
+    writer.flush().await?;
+    Ok(())
+}
+
+/// 读取一条消息，对端关闭连接时返回 `None`
+pub async fn read_frame<R: AsyncRead + Unpin>(reader: &mut R) -> Result<Option<IpcMessage>> {
+    let len = match reader.read_u32().await {
+        Ok(len) => len,
+        Err(e) if e.kind() == std::io::ErrorKind::UnexpectedEof => return Ok(None),
+        Err(e) => return Err(e.into()),
+    };
+    if len > MAX_FRAME_LEN {
+        return Err(anyhow::anyhow!("IPC frame too large: {} bytes", len));
+    }
+    let mut body = vec![0; len as usize];
+    reader.read_exact(&mut body).await?;
+    Ok(Some(serde_json::from_slice(&body)?))
+}
+
+// 获取 socket 地址 (MCP_IPC_ENDPOINT 环境变量；Unix 上默认为 $XDG_RUNTIME_DIR 或临时目录下当前用户私有目录中的
+// feedback.sock，Windows 上为以当前用户 SID 命名的命名管道)
+fn get_endpoint() -> Result<String> {
+    if let Ok(endpoint) = std::env::var("MCP_IPC_ENDPOINT") {
+        if !endpoint.trim().is_empty() {
+            return Ok(endpoint);
+        }
+    }
+
+    #[cfg(windows)]
+    {
+        Ok(format!("{}{}", PIPE_NAME_PREFIX, current_user_sid()?))
+    }
+    #[cfg(not(windows))]
+    {
+        let mut path = private_socket_dir()?;
+        path.push("feedback.sock");
+        Ok(path.to_string_lossy().to_string())
+    }
+}
+
+// 存放 socket 的目录：$XDG_RUNTIME_DIR 本身只有当前用户可访问；否则使用临时目录下按 uid 区分的 0700 目录，
+// 共享的临时目录中该目录可能被其他用户抢先创建，因此要求属主为当前用户且不允许其他用户访问
+#[cfg(unix)]
+fn private_socket_dir() -> Result<std::path::PathBuf> {
+    let uid = unsafe { libc::getuid() };
+    let dir = match std::env::var_os("XDG_RUNTIME_DIR").filter(|dir| !dir.is_empty()) {
+        Some(runtime_dir) => std::path::PathBuf::from(runtime_dir).join("cc-custom-mcp"),
+        None => std::env::temp_dir().join(format!("cc-custom-mcp-{}", uid)),
+    };
+    ensure_private_dir(&dir)?;
+    Ok(dir)
+}
+
+// 创建 0700 目录，已存在时检查其属主为当前用户且其他用户无法访问
+#[cfg(unix)]
+fn ensure_private_dir(dir: &std::path::Path) -> Result<()> {
+    use std::os::unix::fs::{DirBuilderExt, MetadataExt};
+
+    match std::fs::DirBuilder::new().mode(0o700).create(dir) {
+        Ok(()) => {}
+        Err(e) if e.kind() == std::io::ErrorKind::AlreadyExists => {}
+        Err(e) => return Err(e.into()),
+    }
+    let metadata = std::fs::symlink_metadata(dir)?;
+    if !metadata.is_dir() || metadata.uid() != unsafe { libc::getuid() } || metadata.mode() & 0o077 != 0 {
+        return Err(anyhow::anyhow!(
+            "Refusing to use {} for the feedback socket: it must be a directory owned by the current user with mode 0700",
+            dir.display()
+        ));
+    }
+    Ok(())
+}
+
+// 当前用户的 SID 字符串 (如 S-1-5-21-...)
+#[cfg(windows)]
+fn current_user_sid() -> Result<String> {
+    use windows_sys::Win32::Foundation::{CloseHandle, LocalFree, HANDLE};
+    use windows_sys::Win32::Security::Authorization::ConvertSidToStringSidW;
+    use windows_sys::Win32::Security::{GetTokenInformation, TokenUser, TOKEN_QUERY, TOKEN_USER};
+    use windows_sys::Win32::System::Threading::{GetCurrentProcess, OpenProcessToken};
+
+    unsafe {
+        let mut token: HANDLE = std::ptr::null_mut();
+        if OpenProcessToken(GetCurrentProcess(), TOKEN_QUERY, &mut token) == 0 {
+            return Err(std::io::Error::last_os_error().into());
+        }
+        // 第一次调用获取所需长度；TOKEN_USER 后紧跟 SID 数据，使用 u64 缓冲区保证对齐
+        let mut len = 0u32;
+        GetTokenInformation(token, TokenUser, std::ptr::null_mut(), 0, &mut len);
+        let mut buffer = vec![0u64; (len as usize).div_ceil(8)];
+        let queried = GetTokenInformation(token, TokenUser, buffer.as_mut_ptr().cast(), len, &mut len);
+        let error = std::io::Error::last_os_error();
+        CloseHandle(token);
+        if queried == 0 {
+            return Err(error.into());
+        }
+
+        let user = &*(buffer.as_ptr() as *const TOKEN_USER);
+        let mut sid: *mut u16 = std::ptr::null_mut();
+        if ConvertSidToStringSidW(user.User.Sid, &mut sid) == 0 {
+            return Err(std::io::Error::last_os_error().into());
+        }
+        let sid_len = (0..).take_while(|&i| *sid.add(i) != 0).count();
+        let sid_string = String::from_utf16_lossy(std::slice::from_raw_parts(sid, sid_len));
+        LocalFree(sid.cast());
+        Ok(sid_string)
+    }
+}
+
+// 只允许当前用户访问的安全描述符 (受保护的 DACL，不继承默认权限)，用于创建反馈命名管道
+#[cfg(windows)]
+struct OwnerOnlySecurity {
+    descriptor: windows_sys::Win32::Security::PSECURITY_DESCRIPTOR,
+}
+
+// 安全描述符创建后只读，可以在线程间传递
+#[cfg(windows)]
+unsafe impl Send for OwnerOnlySecurity {}
+
+#[cfg(windows)]
+impl OwnerOnlySecurity {
+    fn new() -> Result<Self> {
+        use windows_sys::Win32::Security::Authorization::{ConvertStringSecurityDescriptorToSecurityDescriptorW, SDDL_REVISION_1};
+
+        let sddl: Vec<u16> = format!("D:P(A;;GA;;;{})", current_user_sid()?).encode_utf16().chain(std::iter::once(0)).collect();
+        let mut descriptor = std::ptr::null_mut();
+        let converted = unsafe {
+            ConvertStringSecurityDescriptorToSecurityDescriptorW(sddl.as_ptr(), SDDL_REVISION_1, &mut descriptor, std::ptr::null_mut())
+        };
+        if converted == 0 {
+            return Err(std::io::Error::last_os_error().into());
+        }
+        Ok(Self { descriptor })
+    }
+
+    fn create_pipe(
+        &self,
+        options: &tokio::net::windows::named_pipe::ServerOptions,
+        endpoint: &str,
+    ) -> std::io::Result<tokio::net::windows::named_pipe::NamedPipeServer> {
+        use windows_sys::Win32::Security::SECURITY_ATTRIBUTES;
+
+        let mut attributes = SECURITY_ATTRIBUTES {
+            nLength: std::mem::size_of::<SECURITY_ATTRIBUTES>() as u32,
+            lpSecurityDescriptor: self.descriptor,
+            bInheritHandle: 0,
+        };
+        unsafe { options.create_with_security_attributes_raw(endpoint, (&mut attributes as *mut SECURITY_ATTRIBUTES).cast()) }
+    }
+}
+
+#[cfg(windows)]
+impl Drop for OwnerOnlySecurity {
+    fn drop(&mut self) {
+        unsafe { windows_sys::Win32::Foundation::LocalFree(self.descriptor) };
+    }
+}
+
+// --- MCP 进程侧 ---
+
+#[cfg(unix)]
+pub async fn connect() -> Result<tokio::net::UnixStream> {
+    Ok(tokio::net::UnixStream::connect(get_endpoint()?).await?)
+}
+
+#[cfg(windows)]
+pub async fn connect() -> Result<tokio::net::windows::named_pipe::NamedPipeClient> {
+    Ok(tokio::net::windows::named_pipe::ClientOptions::new().open(get_endpoint()?)?)
+}
+
+/// 连接 GUI，GUI 刚启动时 socket 可能尚未就绪，按间隔重试
+#[cfg(any(unix, windows))]
+pub async fn connect_with_retry(attempts: u32) -> Result<impl AsyncRead + AsyncWrite + Send + Unpin + 'static> {
+    let mut attempt = 1;
+    loop {
+        match connect().await {
+            Ok(stream) => return Ok(stream),
+            Err(e) if attempt >= attempts => return Err(e),
+            Err(_) => {
+                attempt += 1;
+                tokio::time::sleep(Duration::from_millis(500)).await;
+            }
+        }
+    }
+}
+
+/// 向 GUI 查询通过 socket 提交、仍在等待回复的反馈请求
+pub async fn list_pending() -> Result<Vec<Value>> {
+    let mut stream = connect().await?;
+    write_frame(&mut stream, &IpcMessage::ListPending).await?;
+    match read_frame(&mut stream).await? {
+        Some(IpcMessage::Pending { sessions }) => Ok(sessions),
+        other => Err(anyhow::anyhow!("Unexpected IPC reply: {:?}", other)),
+    }
+}
+
+/// 命令行客户端通过 GUI 回复或取消其他 MCP 进程提交的会话
+pub async fn submit_to_gui(message: IpcMessage) -> Result<()> {
+    let mut stream = connect().await?;
+    write_frame(&mut stream, &message).await?;
+    match read_frame(&mut stream).await? {
+        Some(IpcMessage::Ack { error: None, .. }) => Ok(()),
+        Some(IpcMessage::Ack { error: Some(error), .. }) => Err(anyhow::anyhow!(error)),
+        other => Err(anyhow::anyhow!("Unexpected IPC reply: {:?}", other)),
+    }
+}
+
+// --- GUI 侧 ---
+
+// 通过 socket 等待回复的会话
+struct IpcSession {
+    request: Value,
+    tx: UnboundedSender<IpcMessage>,
+}
+
+lazy_static::lazy_static! {
+    static ref IPC_SESSIONS: Mutex<HashMap<String, IpcSession>> = Mutex::new(HashMap::new());
+}
+
+/// 通过 socket 等待回复的单个反馈请求
+pub fn pending_request(session_id: &str) -> Option<Value> {
+    IPC_SESSIONS.lock().ok()?.get(session_id).map(|session| session.request.clone())
+}
+
+/// 通过 socket 等待回复的反馈请求 (用于前端重新加载时恢复会话)
+pub fn pending_requests() -> Vec<Value> {
+    let mut requests: Vec<Value> = IPC_SESSIONS
+        .lock()
+        .map(|sessions| sessions.values().map(|session| session.request.clone()).collect())
+        .unwrap_or_default();
+    requests.sort_by(|a, b| a["timestamp"].as_str().cmp(&b["timestamp"].as_str()));
+    requests
+}
+
+// 移除已结束的会话 (回复、取消或撤回)，同时清除它的自动规则记录
+fn remove_session(session_id: &str) -> Option<IpcSession> {
+    crate::forget_feedback_session(session_id);
+    IPC_SESSIONS.lock().ok().and_then(|mut sessions| sessions.remove(session_id))
+}
+
+// 结束会话并把消息发回对应的 MCP 进程，会话不是通过 socket 提交时返回 false
+fn finish_session(session_id: &str, message: IpcMessage) -> bool {
+    match remove_session(session_id) {
+        Some(session) => session.tx.send(message).is_ok(),
+        None => false,
+    }
+}
+
+/// 把用户反馈发回等待中的 MCP 进程
+pub fn send_response(session_id: &str, response: FeedbackResponse) -> bool {
+    finish_session(session_id, IpcMessage::Response { session_id: session_id.to_string(), response })
+}
+
+/// 通知等待中的 MCP 进程会话已被用户取消
+pub fn send_cancel(session_id: &str) -> bool {
+    finish_session(session_id, IpcMessage::Cancel { session_id: session_id.to_string() })
+}
+
+/// 在 GUI 进程中监听反馈 socket
+#[cfg(unix)]
+pub async fn serve(app: AppHandle) -> Result<()> {
+    use std::os::unix::fs::PermissionsExt;
+
+    let endpoint = get_endpoint()?;
+    if std::path::Path::new(&endpoint).exists() {
+        // 仍能连接说明已有 GUI 实例在监听，否则是上次异常退出遗留的 socket 文件
+        if tokio::net::UnixStream::connect(&endpoint).await.is_ok() {
+            return Err(anyhow::anyhow!("Feedback socket {} is already in use", endpoint));
+        }
+        std::fs::remove_file(&endpoint)?;
+    }
+    if let Some(dir) = std::path::Path::new(&endpoint).parent() {
+        std::fs::create_dir_all(dir)?;
+    }
+
+    // 只允许当前用户连接；默认目录本身为 0700，bind 到 chmod 之间其他用户也无法访问 socket
+    let listener = tokio::net::UnixListener::bind(&endpoint)?;
+    std::fs::set_permissions(&endpoint, std::fs::Permissions::from_mode(0o600))?;
+    eprintln!("🔌 Feedback socket listening on {}", endpoint);
+
+    loop {
+        let (stream, _) = listener.accept().await?;
+        tokio::spawn(handle_connection(app.clone(), stream));
+    }
+}
+
+/// 在 GUI 进程中监听反馈命名管道
+#[cfg(windows)]
+pub async fn serve(app: AppHandle) -> Result<()> {
+    use tokio::net::windows::named_pipe::ServerOptions;
+
+    let endpoint = get_endpoint()?;
+    // 只允许当前用户的本机进程连接；first_pipe_instance 保证管道不是由其他进程抢先创建的
+    let security = OwnerOnlySecurity::new()?;
+    let mut options = ServerOptions::new();
+    options.reject_remote_clients(true);
+    let mut server = security.create_pipe(options.clone().first_pipe_instance(true), &endpoint)?;
+    eprintln!("🔌 Feedback pipe listening on {}", endpoint);
+
+    loop {
+        server.connect().await?;
+        // 先创建下一个管道实例再处理当前连接，避免客户端在间隙中连接失败
+        let connected = std::mem::replace(&mut server, security.create_pipe(&options, &endpoint)?);
+        tokio::spawn(handle_connection(app.clone(), connected));
+    }
+}
+
+async fn handle_connection<S>(app: AppHandle, stream: S)
+where
+    S: AsyncRead + AsyncWrite + Send + 'static,
+{
+    let (mut reader, mut writer) = tokio::io::split(stream);
+    let (tx, mut rx) = tokio::sync::mpsc::unbounded_channel::<IpcMessage>();
+    tokio::spawn(async move {
+        while let Some(message) = rx.recv().await {
+            if let Err(e) = write_frame(&mut writer, &message).await {
+                eprintln!("❌ Failed to write to feedback socket: {}", e);
+                break;
+            }
+        }
+    });
+
+    let mut connection_sessions = Vec::new();
+    loop {
+        match read_frame(&mut reader).await {
+            Ok(Some(IpcMessage::Request { request })) => {
+                let session_id = request["sessionId"].as_str().unwrap_or_default().to_string();
+                eprintln!("🔌 Received feedback request over socket: {}", session_id);
+                if let Ok(mut sessions) = IPC_SESSIONS.lock() {
+                    sessions.insert(session_id.clone(), IpcSession { request: request.clone(), tx: tx.clone() });
+                }
+                connection_sessions.push(session_id);
+                crate::emit_feedback_request(&app, &request, true);
+            }
+            Ok(Some(IpcMessage::Cancel { session_id })) if connection_sessions.contains(&session_id) => {
+                let removed = remove_session(&session_id);
+                if removed.is_some() {
+                    eprintln!("🛑 Feedback request {} was withdrawn, dismissing session", session_id);
+                    let _ = app.emit("feedback-cancelled", json!({ "sessionId": session_id }));
+                }
+            }
+            // 命令行客户端取消其他连接上的会话
+            Ok(Some(IpcMessage::Cancel { session_id })) => {
+                let error = if send_cancel(&session_id) {
+                    eprintln!("🛑 Feedback request {} was cancelled from the terminal", session_id);
+                    let _ = app.emit("feedback-cancelled", json!({ "sessionId": session_id }));
+                    None
+                } else {
+                    Some(format!("Feedback session {} is not pending", session_id))
+                };
+                let _ = tx.send(IpcMessage::Ack { session_id, error });
+            }
+            // 命令行客户端回复其他连接上的会话
+            Ok(Some(IpcMessage::Response { session_id, response })) => {
+                let error = match pending_request(&session_id) {
+                    None => Some(format!("Feedback session {} is not pending", session_id)),
+                    Some(request) => match crate::mcp_server::validate_feedback_response(&request, &response) {
+                        Err(e) => Some(e),
+                        Ok(()) if send_response(&session_id, response) => {
+                            eprintln!("✅ Feedback for {} was answered from the terminal", session_id);
+                            let _ = app.emit("feedback-answered", json!({ "sessionId": session_id }));
+                            None
+                        }
+                        Ok(()) => Some(format!("Feedback session {} is no longer connected", session_id)),
+                    },
+                };
+                let _ = tx.send(IpcMessage::Ack { session_id, error });
+            }
+            Ok(Some(IpcMessage::ListPending)) => {
+                let _ = tx.send(IpcMessage::Pending { sessions: pending_requests() });
+            }
+            Ok(Some(message)) => eprintln!("⚠️ Unexpected message on feedback socket: {:?}", message),
+            Ok(None) => break,
+            Err(e) => {
+                eprintln!("❌ Failed to read from feedback socket: {}", e);
+                break;
+            }
+        }
+    }
+
+    // 连接断开说明 MCP 进程已退出，关闭该连接上仍在等待的会话
+    for session_id in connection_sessions {
+        let removed = remove_session(&session_id);
+        if removed.is_some() {
+            eprintln!("🛑 MCP process for feedback request {} disconnected, dismissing session", session_id);
+            let _ = app.emit("feedback-cancelled", json!({ "sessionId": session_id }));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_frame_roundtrip() {
+        let (mut client, mut server) = tokio::io::duplex(1024);
+        let messages = vec![
+            IpcMessage::Request { request: json!({ "sessionId": "abc", "aiResponse": "done?" }) },
+            IpcMessage::Response {
+                session_id: "abc".to_string(),
+                response: FeedbackResponse {
+                    feedback: "looks good".to_string(),
+                    selected_options: Some(vec!["b".to_string()]),
+                    form_values: None,
+                    attachments: Vec::new(),
+                },
+            },
+            IpcMessage::ListPending,
+            IpcMessage::Ack { session_id: "abc".to_string(), error: Some("not pending".to_string()) },
+        ];
+        for message in &messages {
+            write_frame(&mut client, message).await.unwrap();
+        }
+        drop(client);
+
+        for message in &messages {
+            assert_eq!(read_frame(&mut server).await.unwrap().as_ref(), Some(message));
+        }
+        assert_eq!(read_frame(&mut server).await.unwrap(), None);
+    }
+
+    #[tokio::test]
+    async fn test_oversized_frame_is_rejected() {
+        let (mut client, mut server) = tokio::io::duplex(1024);
+        client.write_u32(MAX_FRAME_LEN + 1).await.unwrap();
+        assert!(read_frame(&mut server).await.is_err());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_ensure_private_dir() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let dir = std::env::temp_dir().join(format!("mcp_ipc_dir_{}", uuid::Uuid::new_v4()));
+        ensure_private_dir(&dir).unwrap();
+        assert_eq!(std::fs::metadata(&dir).unwrap().permissions().mode() & 0o777, 0o700);
+        ensure_private_dir(&dir).unwrap();
+
+        // 其他用户可访问的目录不能用于 socket
+        std::fs::set_permissions(&dir, std::fs::Permissions::from_mode(0o755)).unwrap();
+        assert!(ensure_private_dir(&dir).unwrap_err().to_string().contains("mode 0700"));
+        std::fs::remove_dir_all(&dir).ok();
+    }
+}
@@ -0,0 +1,75 @@
+use anyhow::Result;
+use serde_json::Value;
+
+use crate::feedback_ipc::{self, FeedbackResponse, IpcMessage};
+use crate::mcp_server;
+
+// 在 GUI 之外处理待回复的反馈请求 (命令行、终端界面)：
+// 文件 IPC 的请求直接读写共享请求/响应目录，socket 请求由 GUI 持有，通过 GUI 转交
+
+/// 待处理的反馈请求
+#[derive(Debug, Clone)]
+pub struct PendingSession {
+    pub request: Value,
+    /// 请求通过 socket 提交，只能经由 GUI 回复
+    pub via_socket: bool,
+}
+
+impl PendingSession {
+    pub fn session_id(&self) -> &str {
+        self.request["sessionId"].as_str().unwrap_or_default()
+    }
+}
+
+/// 列出所有待处理的反馈请求，按请求时间排序
+pub async fn list_pending_sessions() -> Vec<PendingSession> {
+    // GUI 未运行时只有文件 IPC 的请求
+    let socket_requests = feedback_ipc::list_pending().await.unwrap_or_default();
+    let mut sessions: Vec<PendingSession> = socket_requests
+        .into_iter()
+        .map(|request| PendingSession { request, via_socket: true })
+        .collect();
+    for request in mcp_server::list_pending_request_files() {
+        // GUI 运行时文件请求也会出现在 GUI 的待处理列表中
+        if !sessions.iter().any(|session| session.request["sessionId"] == request["sessionId"]) {
+            sessions.push(PendingSession { request, via_socket: false });
+        }
+    }
+    sessions.sort_by(|a, b| a.request["timestamp"].as_str().cmp(&b.request["timestamp"].as_str()));
+    sessions
+}
+
+/// 按会话 id 或唯一的 id 前缀查找待处理的请求
+pub async fn find_session(id: &str) -> Result<PendingSession> {
+    let sessions = list_pending_sessions().await;
+    if let Some(session) = sessions.iter().find(|session| session.session_id() == id) {
+        return Ok(session.clone());
+    }
+    let mut matches = sessions.into_iter().filter(|session| !id.is_empty() && session.session_id().starts_with(id));
+    match (matches.next(), matches.next()) {
+        (Some(session), None) => Ok(session),
+        (Some(_), Some(_)) => Err(anyhow::anyhow!("Session id '{}' is ambiguous", id)),
+        (None, _) => Err(anyhow::anyhow!("No pending feedback session matches '{}'", id)),
+    }
+}
+
+/// 回复待处理的请求，回复内容需符合请求中的选项和表单定义
+pub async fn reply(session: &PendingSession, response: FeedbackResponse) -> Result<()> {
+    mcp_server::validate_feedback_response(&session.request, &response).map_err(|e| anyhow::anyhow!(e))?;
+    let session_id = session.session_id().to_string();
+    if session.via_socket {
+        feedback_ipc::submit_to_gui(IpcMessage::Response { session_id, response }).await
+    } else {
+        crate::deliver_feedback_response(&session_id, response).map_err(|e| anyhow::anyhow!(e))
+    }
+}
+
+/// 取消待处理的请求，AI 客户端会收到取消结果
+pub async fn cancel(session: &PendingSession) -> Result<()> {
+    let session_id = session.session_id().to_string();
+    if session.via_socket {
+        feedback_ipc::submit_to_gui(IpcMessage::Cancel { session_id }).await
+    } else {
+        crate::cancel_feedback_session(&session_id).map_err(|e| anyhow::anyhow!(e))
+    }
+}
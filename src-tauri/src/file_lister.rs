@@ -0,0 +1,211 @@
+use anyhow::Result;
+use globset::{GlobBuilder, GlobMatcher};
+use ignore::WalkBuilder;
+use std::path::Path;
+
+use crate::sandbox::Sandbox;
+
+// list_directory 工具的目录遍历：深度限制、.gitignore 过滤、glob 匹配，并跳过沙箱的敏感文件规则
+
+pub const DEFAULT_MAX_ENTRIES: usize = 500;
+pub const MAX_ENTRIES_LIMIT: usize = 5000;
+/// glob 模式未指定深度时的最大遍历深度
+const MAX_DEPTH: usize = 32;
+
+#[derive(Debug, Clone)]
+pub struct ListOptions {
+    /// 遍历深度，1 表示只列出直接子项；None 表示不限 (最多 MAX_DEPTH 层)
+    pub depth: Option<usize>,
+    /// glob 模式：只返回相对路径匹配的文件
+    pub pattern: Option<GlobMatcher>,
+    pub include_hidden: bool,
+    pub respect_gitignore: bool,
+    pub max_entries: usize,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct DirectoryEntry {
+    /// 相对于列出目录的路径，使用 `/` 分隔
+    pub path: String,
+    pub is_dir: bool,
+    pub is_symlink: bool,
+    pub size: u64,
+    pub modified: Option<chrono::DateTime<chrono::Utc>>,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct Listing {
+    pub entries: Vec<DirectoryEntry>,
+    /// 达到 max_entries 后停止遍历
+    pub truncated: bool,
+}
+
+/// 编译 glob 模式，`*` 不匹配路径分隔符，`**` 匹配任意层目录
+pub fn compile_glob(pattern: &str) -> Result<GlobMatcher> {
+    let glob = GlobBuilder::new(pattern).literal_separator(true).build()?;
+    Ok(glob.compile_matcher())
+}
+
+/// 创建遍历工作区目录的 WalkBuilder：默认遵循 .gitignore (不要求位于 git 仓库中) 并跳过隐藏文件，
+/// 不跟随符号链接，始终跳过 .git 目录和匹配沙箱敏感文件规则的路径
+pub fn walker(base: &Path, include_hidden: bool, respect_gitignore: bool, sandbox: &Sandbox) -> WalkBuilder {
+    let mut builder = WalkBuilder::new(base);
+    builder
+        .standard_filters(respect_gitignore)
+        .require_git(false)
+        .hidden(!include_hidden)
+        .follow_links(false);
+    let sandbox = sandbox.clone();
+    builder.filter_entry(move |entry| entry.file_name() != ".git" && sandbox.denied_pattern(entry.path()).is_none());
+    builder
+}
+
+/// 列出目录内容，按路径排序 (目录内容紧跟在目录之后)
+pub fn list_directory(base: &Path, options: &ListOptions, sandbox: &Sandbox) -> Result<Listing> {
+    if !base.is_dir() {
+        return Err(anyhow::anyhow!("{} is not a directory", base.display()));
+    }
+    let depth = match (options.depth, &options.pattern) {
+        (Some(depth), _) => depth,
+        (None, Some(_)) => MAX_DEPTH,
+        (None, None) => 1,
+    };
+
+    let mut builder = walker(base, options.include_hidden, options.respect_gitignore, sandbox);
+    builder.max_depth(Some(depth)).sort_by_file_name(|a, b| a.cmp(b));
+
+    let mut listing = Listing::default();
+    for entry in builder.build() {
+        let entry = match entry {
+            Ok(entry) => entry,
+            Err(e) => {
+                eprintln!("⚠️ Skipping unreadable entry: {}", e);
+                continue;
+            }
+        };
+        // 深度 0 为列出的目录本身
+        if entry.depth() == 0 {
+            continue;
+        }
+        let Ok(relative) = entry.path().strip_prefix(base) else {
+            continue;
+        };
+        let relative = relative.components().map(|c| c.as_os_str().to_string_lossy()).collect::<Vec<_>>().join("/");
+        let file_type = entry.file_type();
+        let is_dir = file_type.is_some_and(|t| t.is_dir());
+        if let Some(pattern) = &options.pattern {
+            if is_dir || !pattern.is_match(&relative) {
+                continue;
+            }
+        }
+
+        if listing.entries.len() >= options.max_entries {
+            listing.truncated = true;
+            break;
+        }
+        let metadata = entry.metadata().ok();
+        listing.entries.push(DirectoryEntry {
+            path: relative,
+            is_dir,
+            is_symlink: file_type.is_some_and(|t| t.is_symlink()),
+            size: if is_dir { 0 } else { metadata.as_ref().map(|m| m.len()).unwrap_or(0) },
+            modified: metadata.and_then(|m| m.modified().ok()).map(chrono::DateTime::from),
+        });
+    }
+    Ok(listing)
+}
+
+/// 文本格式的列表，每行一项：目录以 `/` 结尾，文件附带大小和修改时间
+pub fn format_listing(listing: &Listing) -> String {
+    let width = listing.entries.iter().map(|entry| entry.path.len() + 1).max().unwrap_or(0);
+    let mut text = String::new();
+    for entry in &listing.entries {
+        let modified = entry.modified.map(|time| time.format("%Y-%m-%d %H:%M").to_string()).unwrap_or_default();
+        let line = if entry.is_dir {
+            format!("{}/", entry.path)
+        } else if entry.is_symlink {
+            format!("{:<width$}  symlink", entry.path)
+        } else {
+            format!("{:<width$}  {:>10}  {}", entry.path, format_size(entry.size), modified)
+        };
+        text.push_str(line.trim_end());
+        text.push('\n');
+    }
+    text
+}
+
+fn format_size(bytes: u64) -> String {
+    const UNITS: [&str; 4] = ["KB", "MB", "GB", "TB"];
+    if bytes < 1024 {
+        return format!("{} B", bytes);
+    }
+    let mut size = bytes as f64 / 1024.0;
+    let mut unit = 0;
+    while size >= 1024.0 && unit < UNITS.len() - 1 {
+        size /= 1024.0;
+        unit += 1;
+    }
+    format!("{:.1} {}", size, UNITS[unit])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    fn options(depth: Option<usize>, pattern: Option<&str>) -> ListOptions {
+        ListOptions {
+            depth,
+            pattern: pattern.map(|pattern| compile_glob(pattern).unwrap()),
+            include_hidden: false,
+            respect_gitignore: true,
+            max_entries: DEFAULT_MAX_ENTRIES,
+        }
+    }
+
+    fn paths(listing: &Listing) -> Vec<&str> {
+        listing.entries.iter().map(|entry| entry.path.as_str()).collect()
+    }
+
+    #[test]
+    fn test_list_directory() {
+        let root = std::env::temp_dir().join(format!("mcp_file_lister_{}", uuid::Uuid::new_v4()));
+        for dir in ["src/bin", "target/debug", ".hidden"] {
+            fs::create_dir_all(root.join(dir)).unwrap();
+        }
+        for file in ["src/lib.rs", "src/bin/main.rs", "target/debug/app", ".hidden/x", ".env", "README.md"] {
+            fs::write(root.join(file), "data").unwrap();
+        }
+        fs::write(root.join(".gitignore"), "target/\n").unwrap();
+        let sandbox = Sandbox::new(vec![root.clone()], &[]);
+        let root = fs::canonicalize(&root).unwrap();
+
+        let listing = list_directory(&root, &options(None, None), &sandbox).unwrap();
+        assert_eq!(paths(&listing), ["README.md", "src"]);
+        assert_eq!((listing.entries[0].size, listing.entries[1].is_dir), (4, true));
+
+        let listing = list_directory(&root, &options(Some(3), None), &sandbox).unwrap();
+        assert_eq!(paths(&listing), ["README.md", "src", "src/bin", "src/bin/main.rs", "src/lib.rs"]);
+
+        // 不遵循 .gitignore 并包含隐藏文件时，敏感文件和 .git 仍被跳过
+        let all = ListOptions { include_hidden: true, respect_gitignore: false, ..options(Some(3), None) };
+        let listing = list_directory(&root, &all, &sandbox).unwrap();
+        assert_eq!(paths(&listing), [".gitignore", ".hidden", ".hidden/x", "README.md", "src", "src/bin", "src/bin/main.rs", "src/lib.rs", "target", "target/debug", "target/debug/app"]);
+
+        let listing = list_directory(&root, &options(None, Some("**/*.rs")), &sandbox).unwrap();
+        assert_eq!(paths(&listing), ["src/bin/main.rs", "src/lib.rs"]);
+        let listing = list_directory(&root, &options(None, Some("src/*.rs")), &sandbox).unwrap();
+        assert_eq!(paths(&listing), ["src/lib.rs"]);
+
+        let listing = list_directory(&root, &ListOptions { max_entries: 1, ..options(Some(3), None) }, &sandbox).unwrap();
+        assert_eq!((listing.entries.len(), listing.truncated), (1, true));
+        fs::remove_dir_all(&root).ok();
+    }
+
+    #[test]
+    fn test_format_size() {
+        assert_eq!(format_size(512), "512 B");
+        assert_eq!(format_size(1536), "1.5 KB");
+        assert_eq!(format_size(5 * 1024 * 1024), "5.0 MB");
+    }
+}
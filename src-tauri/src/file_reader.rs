@@ -0,0 +1,299 @@
+use anyhow::Result;
+use encoding_rs::{Encoding, UTF_8};
+use std::path::Path;
+use tokio::io::{AsyncBufRead, AsyncBufReadExt, AsyncReadExt, BufReader};
+
+// file_read 工具的文件读取：按行范围流式读取 (大文件不会整体载入内存)，识别图片、二进制文件和文本编码
+
+/// 用于识别文件类型和编码的文件头长度
+const SNIFF_BYTES: usize = 8192;
+pub const DEFAULT_LINE_LIMIT: usize = 2000;
+const DEFAULT_MAX_BYTES: usize = 256 * 1024;
+const DEFAULT_MAX_IMAGE_BYTES: u64 = 5 * 1024 * 1024;
+/// UTF-16 文件无法按字节切分行，需要整体解码，超过此大小时拒绝读取
+const MAX_UTF16_BYTES: u64 = 64 * 1024 * 1024;
+
+#[derive(Debug, Clone)]
+pub struct ReadOptions {
+    /// 起始行号，从 1 开始
+    pub offset: usize,
+    pub limit: usize,
+    pub max_bytes: usize,
+    pub line_numbers: bool,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum FileContent {
+    Text(TextRange),
+    Image { bytes: Vec<u8>, mime_type: &'static str },
+    Binary { size: u64 },
+}
+
+/// 读取到的行范围
+#[derive(Debug, Clone, PartialEq)]
+pub struct TextRange {
+    pub text: String,
+    pub encoding: &'static str,
+    /// 实际返回的首行和末行行号，没有返回任何行时为 0
+    pub first_line: usize,
+    pub last_line: usize,
+    /// 文件还有更多内容时，下一次读取的起始行号
+    pub next_offset: Option<usize>,
+    /// 因字节数上限 (而不是行数上限) 截断
+    pub truncated_by_bytes: bool,
+    /// 读到文件末尾时的总行数
+    pub total_lines: Option<usize>,
+}
+
+/// 单次读取返回的最大文本字节数 (MCP_FILE_READ_MAX_BYTES 环境变量)
+pub fn get_max_read_bytes() -> usize {
+    std::env::var("MCP_FILE_READ_MAX_BYTES")
+        .ok()
+        .and_then(|value| value.trim().parse().ok())
+        .filter(|bytes| *bytes > 0)
+        .unwrap_or(DEFAULT_MAX_BYTES)
+}
+
+/// 以图片内容块返回的最大图片大小 (MCP_FILE_READ_MAX_IMAGE_BYTES 环境变量)
+pub fn get_max_image_bytes() -> u64 {
+    std::env::var("MCP_FILE_READ_MAX_IMAGE_BYTES")
+        .ok()
+        .and_then(|value| value.trim().parse().ok())
+        .filter(|bytes| *bytes > 0)
+        .unwrap_or(DEFAULT_MAX_IMAGE_BYTES)
+}
+
+pub async fn read_file(path: &Path, options: &ReadOptions) -> Result<FileContent> {
+    let metadata = tokio::fs::metadata(path).await?;
+    if metadata.is_dir() {
+        return Err(anyhow::anyhow!("{} is a directory", path.display()));
+    }
+
+    let mut file = tokio::fs::File::open(path).await?;
+    let mut head = Vec::with_capacity(SNIFF_BYTES);
+    (&mut file).take(SNIFF_BYTES as u64).read_to_end(&mut head).await?;
+
+    if let Some(mime_type) = detect_image(&head) {
+        let max_bytes = get_max_image_bytes();
+        if metadata.len() > max_bytes {
+            return Err(anyhow::anyhow!(
+                "{} is a {} image of {} bytes, larger than the {} byte limit",
+                path.display(), mime_type, metadata.len(), max_bytes
+            ));
+        }
+        let mut bytes = head;
+        file.read_to_end(&mut bytes).await?;
+        return Ok(FileContent::Image { bytes, mime_type });
+    }
+
+    let Some((encoding, bom_length)) = detect_encoding(&head, metadata.len() as usize <= head.len()) else {
+        return Ok(FileContent::Binary { size: metadata.len() });
+    };
+
+    let range = if encoding.is_ascii_compatible() {
+        let mut reader = BufReader::new(std::io::Cursor::new(head).chain(file));
+        read_lines(&mut reader, encoding, options).await?
+    } else {
+        if metadata.len() > MAX_UTF16_BYTES {
+            return Err(anyhow::anyhow!(
+                "{} is a {} file of {} bytes, larger than the {} byte limit for this encoding",
+                path.display(), encoding.name(), metadata.len(), MAX_UTF16_BYTES
+            ));
+        }
+        let mut bytes = head;
+        file.read_to_end(&mut bytes).await?;
+        let (text, _) = encoding.decode_without_bom_handling(&bytes[bom_length..]);
+        let mut reader = BufReader::new(text.as_bytes());
+        TextRange { encoding: encoding.name(), ..read_lines(&mut reader, UTF_8, options).await? }
+    };
+    Ok(FileContent::Text(range))
+}
+
+// 按文件头识别 MCP 支持的图片格式
+fn detect_image(head: &[u8]) -> Option<&'static str> {
+    match head {
+        [0x89, b'P', b'N', b'G', b'\r', b'\n', 0x1a, b'\n', ..] => Some("image/png"),
+        [0xff, 0xd8, 0xff, ..] => Some("image/jpeg"),
+        [b'G', b'I', b'F', b'8', b'7' | b'9', b'a', ..] => Some("image/gif"),
+        [b'R', b'I', b'F', b'F', _, _, _, _, b'W', b'E', b'B', b'P', ..] => Some("image/webp"),
+        _ => None,
+    }
+}
+
+// 识别文本编码，返回编码和 BOM 长度；判断为二进制文件时返回 None。
+// complete 表示 head 已包含整个文件，否则末尾被截断的多字节字符不影响 UTF-8 判断
+fn detect_encoding(head: &[u8], complete: bool) -> Option<(&'static Encoding, usize)> {
+    if let Some((encoding, bom_length)) = Encoding::for_bom(head) {
+        return Some((encoding, bom_length));
+    }
+    // 与 git 相同，含有 NUL 字节的文件视为二进制文件
+    if head.contains(&0) {
+        return None;
+    }
+    match std::str::from_utf8(head) {
+        Ok(_) => return Some((UTF_8, 0)),
+        Err(e) if e.error_len().is_none() && !complete => return Some((UTF_8, 0)),
+        Err(_) => {}
+    }
+    let mut detector = chardetng::EncodingDetector::new();
+    detector.feed(head, complete);
+    Some((detector.guess(None, true), 0))
+}
+
+async fn read_lines<R: AsyncBufRead + Unpin>(reader: &mut R, encoding: &'static Encoding, options: &ReadOptions) -> Result<TextRange> {
+    let mut range = TextRange {
+        text: String::new(),
+        encoding: encoding.name(),
+        first_line: 0,
+        last_line: 0,
+        next_offset: None,
+        truncated_by_bytes: false,
+        total_lines: None,
+    };
+    let mut line = Vec::new();
+    let mut line_number = 0;
+    let mut shown = 0;
+    loop {
+        let remaining = options.max_bytes.saturating_sub(range.text.len());
+        let full = shown >= options.limit || remaining == 0;
+        let wanted = line_number + 1 >= options.offset && !full;
+
+        line.clear();
+        let (consumed, line_truncated) = read_line_capped(reader, &mut line, if wanted { remaining } else { 0 }).await?;
+        if consumed == 0 {
+            range.total_lines = Some(line_number);
+            break;
+        }
+        line_number += 1;
+        if full {
+            range.next_offset = Some(line_number);
+            range.truncated_by_bytes = remaining == 0;
+            break;
+        }
+        if !wanted {
+            continue;
+        }
+
+        let (decoded, _) = encoding.decode_without_bom_handling(&line);
+        let mut text = decoded.into_owned();
+        if line_number == 1 {
+            text = text.trim_start_matches('\u{feff}').to_string();
+        }
+        let entry = if options.line_numbers || line_truncated {
+            let content = text.trim_end_matches(['\n', '\r']);
+            let content = if line_truncated { format!("{} … [line truncated]", content) } else { content.to_string() };
+            if options.line_numbers { format!("{:>6}\t{}\n", line_number, content) } else { format!("{}\n", content) }
+        } else {
+            text
+        };
+
+        // 放不下的行留到下一次读取，除非这是第一行
+        if shown > 0 && (line_truncated || range.text.len() + entry.len() > options.max_bytes) {
+            range.next_offset = Some(line_number);
+            range.truncated_by_bytes = true;
+            break;
+        }
+        range.text.push_str(&entry);
+        if shown == 0 {
+            range.first_line = line_number;
+        }
+        range.last_line = line_number;
+        shown += 1;
+    }
+    Ok(range)
+}
+
+// 读取一行 (包含换行符)，最多保存 cap 字节，超出部分被跳过；返回消耗的字节数和该行是否被截断
+async fn read_line_capped<R: AsyncBufRead + Unpin>(reader: &mut R, line: &mut Vec<u8>, cap: usize) -> std::io::Result<(usize, bool)> {
+    let mut consumed = 0;
+    let mut truncated = false;
+    loop {
+        let available = reader.fill_buf().await?;
+        if available.is_empty() {
+            return Ok((consumed, truncated));
+        }
+        let (chunk, done) = match available.iter().position(|byte| *byte == b'\n') {
+            Some(end) => (&available[..=end], true),
+            None => (available, false),
+        };
+        let room = cap.saturating_sub(line.len());
+        if chunk.len() > room {
+            line.extend_from_slice(&chunk[..room]);
+            truncated = true;
+        } else {
+            line.extend_from_slice(chunk);
+        }
+        let length = chunk.len();
+        reader.consume(length);
+        consumed += length;
+        if done {
+            return Ok((consumed, truncated));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn options(offset: usize, limit: usize, max_bytes: usize, line_numbers: bool) -> ReadOptions {
+        ReadOptions { offset, limit, max_bytes, line_numbers }
+    }
+
+    async fn read_bytes(bytes: &[u8], options: &ReadOptions) -> FileContent {
+        let path = std::env::temp_dir().join(format!("mcp_file_reader_{}", uuid::Uuid::new_v4()));
+        std::fs::write(&path, bytes).unwrap();
+        let content = read_file(&path, options).await.unwrap();
+        std::fs::remove_file(&path).ok();
+        content
+    }
+
+    async fn read_text(bytes: &[u8], options: &ReadOptions) -> TextRange {
+        match read_bytes(bytes, options).await {
+            FileContent::Text(range) => range,
+            other => panic!("expected text, got {:?}", other),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_line_ranges_and_limits() {
+        let file = b"one\ntwo\r\nthree\nfour";
+        let range = read_text(file, &options(1, 100, 1000, false)).await;
+        assert_eq!(range.text, "one\ntwo\r\nthree\nfour");
+        assert_eq!((range.first_line, range.last_line, range.total_lines), (1, 4, Some(4)));
+
+        let range = read_text(file, &options(2, 2, 1000, true)).await;
+        assert_eq!(range.text, "     2\ttwo\n     3\tthree\n");
+        assert_eq!((range.next_offset, range.truncated_by_bytes), (Some(4), false));
+
+        // 字节数上限：放不下的行留到下一次读取
+        let range = read_text(file, &options(1, 100, 8, false)).await;
+        assert_eq!(range.text, "one\n");
+        assert_eq!((range.next_offset, range.truncated_by_bytes), (Some(2), true));
+
+        // 超长的首行被截断
+        let range = read_text(&[b'x'; 100], &options(1, 100, 10, false)).await;
+        assert_eq!(range.text, "xxxxxxxxxx … [line truncated]\n");
+        assert_eq!(range.next_offset, None);
+
+        let range = read_text(file, &options(10, 100, 1000, true)).await;
+        assert_eq!((range.text.as_str(), range.first_line, range.total_lines), ("", 0, Some(4)));
+    }
+
+    #[tokio::test]
+    async fn test_binary_and_encoding_detection() {
+        let png = [0x89, b'P', b'N', b'G', b'\r', b'\n', 0x1a, b'\n', 0, 0, 0, 13];
+        assert!(matches!(read_bytes(&png, &options(1, 10, 100, false)).await, FileContent::Image { mime_type: "image/png", .. }));
+        assert_eq!(read_bytes(&[1, 2, 0, 3], &options(1, 10, 100, false)).await, FileContent::Binary { size: 4 });
+
+        // UTF-16 (带 BOM) 和 GBK
+        let utf16: Vec<u8> = [0xff, 0xfe].into_iter().chain("héllo\nwörld".encode_utf16().flat_map(u16::to_le_bytes)).collect();
+        let range = read_text(&utf16, &options(2, 10, 100, false)).await;
+        assert_eq!((range.text.as_str(), range.encoding), ("wörld", "UTF-16LE"));
+
+        let (gbk, _, _) = encoding_rs::GBK.encode("你好，世界。这是一段用于检测编码的中文文本。\n");
+        let range = read_text(&gbk, &options(1, 10, 1000, false)).await;
+        assert_eq!(range.text, "你好，世界。这是一段用于检测编码的中文文本。\n");
+        assert_ne!(range.encoding, "UTF-8");
+    }
+}
@@ -0,0 +1,283 @@
+use anyhow::Result;
+use globset::{GlobBuilder, GlobSet, GlobSetBuilder};
+use ignore::WalkState;
+use regex::{Regex, RegexBuilder};
+use std::path::Path;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::Mutex;
+use tokio_util::sync::CancellationToken;
+
+use crate::file_lister;
+use crate::sandbox::Sandbox;
+
+// search_files 工具的内容搜索：并行遍历工作区 (遵循 .gitignore)，逐行匹配正则或字面量
+
+pub const DEFAULT_MAX_RESULTS: usize = 100;
+pub const MAX_RESULTS_LIMIT: usize = 1000;
+pub const MAX_CONTEXT_LINES: usize = 10;
+/// 超过此大小的文件不搜索
+const MAX_FILE_BYTES: u64 = 10 * 1024 * 1024;
+/// 结果中单行的最大字符数
+const MAX_LINE_CHARS: usize = 500;
+
+#[derive(Debug, Clone)]
+pub struct SearchOptions {
+    pub regex: Regex,
+    /// 只搜索匹配的文件，不含 `/` 的 glob 匹配文件名，否则匹配相对路径
+    pub include: Option<GlobSet>,
+    pub exclude: Option<GlobSet>,
+    pub context_lines: usize,
+    pub max_results: usize,
+    pub include_hidden: bool,
+    pub respect_gitignore: bool,
+}
+
+/// 匹配行或上下文行
+#[derive(Debug, Clone, PartialEq)]
+pub struct SearchLine {
+    pub line_number: usize,
+    pub text: String,
+    pub is_match: bool,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct FileMatches {
+    /// 相对于搜索目录的路径，使用 `/` 分隔
+    pub path: String,
+    pub lines: Vec<SearchLine>,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct SearchResults {
+    pub files: Vec<FileMatches>,
+    pub files_searched: usize,
+    pub match_count: usize,
+    /// 找到的匹配超过 max_results，结果不完整。并行搜索时保留哪些匹配取决于线程调度，
+    /// 因此截断的结果在多次搜索之间可能不同
+    pub truncated: bool,
+}
+
+/// 编译搜索模式，literal 为 true 时按字面量匹配
+pub fn build_regex(pattern: &str, literal: bool, case_insensitive: bool) -> Result<Regex> {
+    let pattern = if literal { regex::escape(pattern) } else { pattern.to_string() };
+    Ok(RegexBuilder::new(&pattern).case_insensitive(case_insensitive).size_limit(1 << 20).build()?)
+}
+
+/// 编译 include / exclude glob 列表
+pub fn build_globs(patterns: &[String]) -> Result<Option<GlobSet>> {
+    if patterns.is_empty() {
+        return Ok(None);
+    }
+    let mut builder = GlobSetBuilder::new();
+    for pattern in patterns {
+        builder.add(GlobBuilder::new(pattern).literal_separator(true).build()?);
+    }
+    Ok(Some(builder.build()?))
+}
+
+fn glob_matches(globs: &GlobSet, relative: &str) -> bool {
+    let file_name = relative.rsplit('/').next().unwrap_or(relative);
+    globs.is_match(relative) || globs.is_match(file_name)
+}
+
+/// 搜索目录 (或单个文件)，结果按路径排序。最多保留 max_results 个匹配，之后再找到匹配时停止搜索
+pub fn search(base: &Path, options: &SearchOptions, sandbox: &Sandbox, cancellation_token: &CancellationToken) -> Result<SearchResults> {
+    if !base.exists() {
+        return Err(anyhow::anyhow!("{} does not exist", base.display()));
+    }
+    let files = Mutex::new(Vec::new());
+    let files_searched = AtomicUsize::new(0);
+    let match_count = AtomicUsize::new(0);
+    // 结果已满后又找到了匹配；恰好有 max_results 个匹配时搜索会继续到结束，不算截断
+    let overflowed = AtomicBool::new(false);
+
+    let root = if base.is_file() { base.parent().unwrap_or(base) } else { base };
+    file_lister::walker(base, options.include_hidden, options.respect_gitignore, sandbox)
+        .build_parallel()
+        .run(|| {
+            Box::new(|entry| {
+                if cancellation_token.is_cancelled() || overflowed.load(Ordering::Relaxed) {
+                    return WalkState::Quit;
+                }
+                let Ok(entry) = entry else {
+                    return WalkState::Continue;
+                };
+                if !entry.file_type().is_some_and(|t| t.is_file()) {
+                    return WalkState::Continue;
+                }
+                let relative = entry.path().strip_prefix(root).unwrap_or(entry.path());
+                let relative = relative.components().map(|c| c.as_os_str().to_string_lossy()).collect::<Vec<_>>().join("/");
+                if options.include.as_ref().is_some_and(|globs| !glob_matches(globs, &relative))
+                    || options.exclude.as_ref().is_some_and(|globs| glob_matches(globs, &relative))
+                {
+                    return WalkState::Continue;
+                }
+
+                match search_file(entry.path(), options, &files_searched, &match_count, &overflowed) {
+                    Ok(Some(lines)) => files.lock().unwrap().push(FileMatches { path: relative, lines }),
+                    Ok(None) => {}
+                    Err(e) => eprintln!("⚠️ Skipping {}: {}", entry.path().display(), e),
+                }
+                WalkState::Continue
+            })
+        });
+
+    let mut files = files.into_inner().unwrap();
+    files.sort_by(|a, b| a.path.cmp(&b.path));
+    let match_count = match_count.load(Ordering::Relaxed).min(options.max_results);
+    Ok(SearchResults {
+        files,
+        files_searched: files_searched.load(Ordering::Relaxed),
+        match_count,
+        truncated: overflowed.load(Ordering::Relaxed),
+    })
+}
+
+// 搜索单个文件，返回匹配行及其上下文；跳过二进制文件和过大的文件 (不计入已搜索的文件数)
+fn search_file(
+    path: &Path,
+    options: &SearchOptions,
+    files_searched: &AtomicUsize,
+    match_count: &AtomicUsize,
+    overflowed: &AtomicBool,
+) -> Result<Option<Vec<SearchLine>>> {
+    if std::fs::metadata(path)?.len() > MAX_FILE_BYTES {
+        return Ok(None);
+    }
+    let bytes = std::fs::read(path)?;
+    if bytes[..bytes.len().min(8192)].contains(&0) {
+        return Ok(None);
+    }
+    files_searched.fetch_add(1, Ordering::Relaxed);
+    let text = String::from_utf8_lossy(&bytes);
+    let lines: Vec<&str> = text.lines().collect();
+
+    let mut matched = Vec::new();
+    for (index, line) in lines.iter().enumerate() {
+        if !options.regex.is_match(line) {
+            continue;
+        }
+        // 多个线程共享结果上限，先占位再记录
+        if match_count.fetch_add(1, Ordering::Relaxed) >= options.max_results {
+            overflowed.store(true, Ordering::Relaxed);
+            break;
+        }
+        matched.push(index);
+    }
+    if matched.is_empty() {
+        return Ok(None);
+    }
+
+    let mut result: Vec<SearchLine> = Vec::new();
+    for &index in &matched {
+        let start = index.saturating_sub(options.context_lines);
+        let end = (index + options.context_lines).min(lines.len() - 1);
+        for (line_index, line) in lines.iter().enumerate().take(end + 1).skip(start) {
+            let line_number = line_index + 1;
+            let is_match = matched.binary_search(&line_index).is_ok();
+            match result.last() {
+                Some(last) if last.line_number >= line_number => continue,
+                _ => result.push(SearchLine { line_number, text: truncate_line(line), is_match }),
+            }
+        }
+    }
+    Ok(Some(result))
+}
+
+fn truncate_line(line: &str) -> String {
+    match line.char_indices().nth(MAX_LINE_CHARS) {
+        Some((end, _)) => format!("{} …", &line[..end]),
+        None => line.to_string(),
+    }
+}
+
+/// grep 风格的文本结果：匹配行为 `路径:行号:内容`，上下文行为 `路径-行号-内容`，不连续的片段之间用 `--` 分隔
+pub fn format_results(results: &SearchResults) -> String {
+    let mut text = String::new();
+    for file in &results.files {
+        let mut previous: Option<usize> = None;
+        for line in &file.lines {
+            if previous.is_some_and(|previous| line.line_number > previous + 1) {
+                text.push_str("--\n");
+            }
+            let separator = if line.is_match { ':' } else { '-' };
+            text.push_str(&format!("{}{}{}{}{}\n", file.path, separator, line.line_number, separator, line.text));
+            previous = Some(line.line_number);
+        }
+    }
+    text
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    fn options(pattern: &str, literal: bool, case_insensitive: bool) -> SearchOptions {
+        SearchOptions {
+            regex: build_regex(pattern, literal, case_insensitive).unwrap(),
+            include: None,
+            exclude: None,
+            context_lines: 0,
+            max_results: DEFAULT_MAX_RESULTS,
+            include_hidden: false,
+            respect_gitignore: true,
+        }
+    }
+
+    #[test]
+    fn test_search() {
+        let root = std::env::temp_dir().join(format!("mcp_file_searcher_{}", uuid::Uuid::new_v4()));
+        fs::create_dir_all(root.join("src")).unwrap();
+        fs::create_dir_all(root.join("build")).unwrap();
+        fs::write(root.join("src/lib.rs"), "use std::fs;\n\nfn Load() {}\nfn load_all() {}\n// load(x)\n").unwrap();
+        fs::write(root.join("src/app.ts"), "export function load() {}\n").unwrap();
+        fs::write(root.join("build/out.rs"), "fn load() {}\n").unwrap();
+        fs::write(root.join("data.bin"), b"load\0\x01").unwrap();
+        fs::write(root.join(".gitignore"), "build/\n").unwrap();
+        let sandbox = Sandbox::new(vec![root.clone()], &[]);
+        let root = fs::canonicalize(&root).unwrap();
+        let token = CancellationToken::new();
+        let run = |options: &SearchOptions| search(&root, options, &sandbox, &token).unwrap();
+
+        let results = run(&options(r"fn load\w*", false, false));
+        assert_eq!(format_results(&results), "src/lib.rs:4:fn load_all() {}\n");
+
+        let results = run(&options("LOAD(X)", true, true));
+        assert_eq!(format_results(&results), "src/lib.rs:5:// load(x)\n");
+        // data.bin 是二进制文件，不计入已搜索的文件数
+        assert_eq!(results.files_searched, 2);
+
+        let results = run(&SearchOptions { include: build_globs(&["*.rs".to_string()]).unwrap(), context_lines: 1, ..options("load", false, true) });
+        assert_eq!(format_results(&results), "src/lib.rs-2-\nsrc/lib.rs:3:fn Load() {}\nsrc/lib.rs:4:fn load_all() {}\nsrc/lib.rs:5:// load(x)\n");
+
+        let results = run(&SearchOptions { exclude: build_globs(&["src/*.rs".to_string()]).unwrap(), ..options("load", false, false) });
+        assert_eq!(format_results(&results), "src/app.ts:1:export function load() {}\n");
+
+        let results = run(&SearchOptions { max_results: 2, ..options("load", false, true) });
+        assert_eq!((results.match_count, results.truncated), (2, true));
+        assert_eq!(results.files.iter().map(|file| file.lines.len()).sum::<usize>(), 2);
+
+        // 恰好有 max_results 个匹配时不算截断
+        let results = run(&SearchOptions { max_results: 4, ..options("load", false, true) });
+        assert_eq!((results.match_count, results.truncated), (4, false));
+        let results = run(&SearchOptions { max_results: 3, ..options("load", false, true) });
+        assert_eq!((results.match_count, results.truncated), (3, true));
+
+        // 搜索单个文件
+        let results = search(&root.join("src/app.ts"), &options("load", false, false), &sandbox, &token).unwrap();
+        assert_eq!(format_results(&results), "app.ts:1:export function load() {}\n");
+        fs::remove_dir_all(&root).ok();
+    }
+
+    #[test]
+    fn test_format_results_separates_groups() {
+        let line = |line_number, is_match| SearchLine { line_number, text: "x".to_string(), is_match };
+        let results = SearchResults {
+            files: vec![FileMatches { path: "a.txt".to_string(), lines: vec![line(1, true), line(2, false), line(9, true)] }],
+            ..Default::default()
+        };
+        assert_eq!(format_results(&results), "a.txt:1:x\na.txt-2-x\n--\na.txt:9:x\n");
+        assert_eq!(truncate_line(&"é".repeat(MAX_LINE_CHARS + 1)), format!("{} …", "é".repeat(MAX_LINE_CHARS)));
+    }
+}
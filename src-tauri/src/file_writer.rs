@@ -0,0 +1,171 @@
+use anyhow::Result;
+use std::path::Path;
+use std::time::SystemTime;
+
+// file_write / file_edit 工具的文件修改：精确替换、应用统一格式补丁、生成差异预览和原子写入
+
+/// 编辑和预览时整体读入内存的最大文件大小
+pub const MAX_EDIT_BYTES: u64 = 16 * 1024 * 1024;
+
+/// 对文件内容的修改
+#[derive(Debug, Clone, Copy)]
+pub enum Edit<'a> {
+    /// 把 old 替换为 new，replace_all 为 false 时 old 必须在文件中唯一
+    Replace { old: &'a str, new: &'a str, replace_all: bool },
+    /// 统一格式 (unified diff) 补丁
+    Patch(&'a str),
+}
+
+/// 修改前需要通过反馈界面由用户确认 (MCP_FILE_WRITE_REQUIRE_APPROVAL 环境变量)
+pub fn requires_approval() -> bool {
+    std::env::var("MCP_FILE_WRITE_REQUIRE_APPROVAL").is_ok_and(|value| matches!(value.trim(), "1" | "true" | "yes"))
+}
+
+/// 修改前的文件状态
+#[derive(Debug, Clone, PartialEq)]
+pub enum Original {
+    Missing,
+    Text(String),
+    /// 二进制文件或超过 MAX_EDIT_BYTES 的文件：不能编辑或显示差异，只能整体覆盖
+    Unreadable { size: u64, reason: String },
+}
+
+/// 文件大小和修改时间，用于检测等待审批期间文件是否被修改
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Fingerprint {
+    len: u64,
+    modified: Option<SystemTime>,
+}
+
+/// 读取文件的当前状态，目录和其他 I/O 错误返回 Err
+pub async fn read_original(path: &Path) -> Result<Original> {
+    let metadata = match tokio::fs::metadata(path).await {
+        Ok(metadata) => metadata,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Original::Missing),
+        Err(e) => return Err(e.into()),
+    };
+    if metadata.is_dir() {
+        return Err(anyhow::anyhow!("{} is a directory", path.display()));
+    }
+    if metadata.len() > MAX_EDIT_BYTES {
+        return Ok(Original::Unreadable {
+            size: metadata.len(),
+            reason: format!("larger than the {} byte limit for editing", MAX_EDIT_BYTES),
+        });
+    }
+    let bytes = tokio::fs::read(path).await?;
+    let size = bytes.len() as u64;
+    Ok(match String::from_utf8(bytes) {
+        Ok(text) => Original::Text(text),
+        Err(_) => Original::Unreadable { size, reason: "not UTF-8 text".to_string() },
+    })
+}
+
+/// 读取文件的大小和修改时间，文件不存在时返回 None
+pub async fn fingerprint(path: &Path) -> Result<Option<Fingerprint>> {
+    match tokio::fs::metadata(path).await {
+        Ok(metadata) => Ok(Some(Fingerprint { len: metadata.len(), modified: metadata.modified().ok() })),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(None),
+        Err(e) => Err(e.into()),
+    }
+}
+
+/// 应用修改，返回新内容和替换次数 (补丁为 hunk 数)
+pub fn apply_edit(original: &str, edit: Edit) -> Result<(String, usize)> {
+    match edit {
+        Edit::Replace { old, new, replace_all } => {
+            let count = original.matches(old).count();
+            match count {
+                0 => Err(anyhow::anyhow!("old_string was not found in the file")),
+                1 => Ok((original.replacen(old, new, 1), 1)),
+                _ if replace_all => Ok((original.replace(old, new), count)),
+                _ => Err(anyhow::anyhow!(
+                    "old_string matches {} times; include more surrounding context or set replace_all",
+                    count
+                )),
+            }
+        }
+        Edit::Patch(patch) => {
+            let patch = diffy::Patch::from_str(patch).map_err(|e| anyhow::anyhow!("Invalid patch: {}", e))?;
+            if patch.hunks().is_empty() {
+                return Err(anyhow::anyhow!("Invalid patch: no hunks found"));
+            }
+            let modified = diffy::apply(original, &patch).map_err(|e| {
+                anyhow::anyhow!("Patch does not apply to the current file content ({})", e)
+            })?;
+            Ok((modified, patch.hunks().len()))
+        }
+    }
+}
+
+/// 生成统一格式的差异预览，内容相同时返回空字符串
+pub fn unified_diff(label: &str, original: &str, modified: &str) -> String {
+    if original == modified {
+        return String::new();
+    }
+    let mut options = diffy::DiffOptions::new();
+    options
+        .set_original_filename(format!("a/{}", label))
+        .set_modified_filename(format!("b/{}", label));
+    options.create_patch(original, modified).to_string()
+}
+
+/// 先写入同目录下的临时文件再重命名，避免写入中断留下不完整的文件；会创建缺失的父目录并保留原文件权限
+pub async fn write_atomic(path: &Path, content: &str) -> Result<()> {
+    let parent = path.parent().ok_or_else(|| anyhow::anyhow!("{} has no parent directory", path.display()))?;
+    tokio::fs::create_dir_all(parent).await?;
+
+    let file_name = path.file_name().and_then(|name| name.to_str()).unwrap_or("file");
+    let temp_path = parent.join(format!(".{}.{}.tmp", file_name, uuid::Uuid::new_v4()));
+    tokio::fs::write(&temp_path, content).await?;
+    if let Ok(metadata) = tokio::fs::metadata(path).await {
+        tokio::fs::set_permissions(&temp_path, metadata.permissions()).await.ok();
+    }
+    if let Err(e) = tokio::fs::rename(&temp_path, path).await {
+        tokio::fs::remove_file(&temp_path).await.ok();
+        return Err(e.into());
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_apply_edit() {
+        let original = "fn a() {}\nfn b() {}\nfn a() {}\n";
+        let replace = |old, new, replace_all| apply_edit(original, Edit::Replace { old, new, replace_all });
+
+        assert_eq!(replace("fn b", "fn c", false).unwrap(), ("fn a() {}\nfn c() {}\nfn a() {}\n".to_string(), 1));
+        assert!(replace("fn a", "fn c", false).unwrap_err().to_string().contains("matches 2 times"));
+        assert_eq!(replace("fn a", "fn c", true).unwrap().1, 2);
+        assert!(replace("fn d", "fn c", false).unwrap_err().to_string().contains("not found"));
+
+        let patch = unified_diff("lib.rs", original, "fn a() {}\nfn b() { todo!() }\nfn a() {}\n");
+        assert!(patch.starts_with("--- a/lib.rs\n+++ b/lib.rs\n@@ -1,3 +1,3 @@"));
+        assert_eq!(apply_edit(original, Edit::Patch(&patch)).unwrap().0, "fn a() {}\nfn b() { todo!() }\nfn a() {}\n");
+        assert!(apply_edit("something else\n", Edit::Patch(&patch)).unwrap_err().to_string().contains("does not apply"));
+        assert!(apply_edit(original, Edit::Patch("not a patch")).is_err());
+        assert_eq!(unified_diff("lib.rs", original, original), "");
+    }
+
+    #[tokio::test]
+    async fn test_write_atomic() {
+        let dir = std::env::temp_dir().join(format!("mcp_file_writer_{}", uuid::Uuid::new_v4()));
+        let path = dir.join("nested").join("file.txt");
+        write_atomic(&path, "first").await.unwrap();
+        write_atomic(&path, "second").await.unwrap();
+
+        assert_eq!(read_original(&path).await.unwrap(), Original::Text("second".to_string()));
+        assert_eq!(std::fs::read_dir(path.parent().unwrap()).unwrap().count(), 1);
+        assert_eq!(read_original(&dir.join("missing.txt")).await.unwrap(), Original::Missing);
+        assert_eq!(fingerprint(&dir.join("missing.txt")).await.unwrap(), None);
+
+        let binary = dir.join("data.bin");
+        std::fs::write(&binary, b"\xff\xfe\x00\x01").unwrap();
+        assert_eq!(read_original(&binary).await.unwrap(), Original::Unreadable { size: 4, reason: "not UTF-8 text".to_string() });
+        assert!(read_original(&dir).await.is_err());
+        std::fs::remove_dir_all(&dir).ok();
+    }
+}
@@ -0,0 +1,496 @@
+use anyhow::Result;
+use rusqlite::{params, Connection};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+// 反馈历史记录：每次反馈会话结束后由 MCP 进程写入本地 SQLite 数据库，GUI 通过 Tauri 命令查询
+
+const DEFAULT_RETENTION_DAYS: i64 = 90;
+const DEFAULT_MAX_ENTRIES: i64 = 10_000;
+const DEFAULT_QUERY_LIMIT: i64 = 100;
+
+const SCHEMA: &str = "
+CREATE TABLE IF NOT EXISTS feedback_sessions (
+    id INTEGER PRIMARY KEY,
+    session_id TEXT NOT NULL UNIQUE,
+    ai_response TEXT NOT NULL,
+    context TEXT NOT NULL,
+    ai_source TEXT NOT NULL,
+    ai_source_display TEXT NOT NULL,
+    status TEXT NOT NULL,
+    user_feedback TEXT,
+    details TEXT,
+    requested_at TEXT NOT NULL,
+    finished_at TEXT NOT NULL,
+    duration_ms INTEGER NOT NULL
+);
+CREATE INDEX IF NOT EXISTS idx_feedback_sessions_requested_at ON feedback_sessions(requested_at);
+
+CREATE VIRTUAL TABLE IF NOT EXISTS feedback_fts USING fts5(
+    ai_response, context, user_feedback,
+    content = 'feedback_sessions', content_rowid = 'id'
+);
+CREATE TRIGGER IF NOT EXISTS feedback_sessions_ai AFTER INSERT ON feedback_sessions BEGIN
+    INSERT INTO feedback_fts(rowid, ai_response, context, user_feedback)
+    VALUES (new.id, new.ai_response, new.context, new.user_feedback);
+END;
+CREATE TRIGGER IF NOT EXISTS feedback_sessions_ad AFTER DELETE ON feedback_sessions BEGIN
+    INSERT INTO feedback_fts(feedback_fts, rowid, ai_response, context, user_feedback)
+    VALUES ('delete', old.id, old.ai_response, old.context, old.user_feedback);
+END;
+";
+
+/// 一次已结束的反馈会话
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct HistoryEntry {
+    pub session_id: String,
+    pub ai_response: String,
+    pub context: String,
+    pub ai_source: String,
+    pub ai_source_display: String,
+    /// answered / cancelled / timed_out
+    pub status: String,
+    pub user_feedback: Option<String>,
+    /// 选项、表单和附件等结构化回复内容
+    pub details: Option<Value>,
+    pub requested_at: String,
+    pub finished_at: String,
+    pub duration_ms: i64,
+}
+
+/// 历史记录查询条件，所有条件均可省略
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct HistoryQuery {
+    /// 全文搜索，按空白分词，每个词按字面量匹配且都需出现
+    pub query: Option<String>,
+    pub ai_source: Option<String>,
+    pub context: Option<String>,
+    pub status: Option<String>,
+    /// RFC 3339 时间，包含边界
+    pub since: Option<String>,
+    pub until: Option<String>,
+    pub limit: Option<i64>,
+    pub offset: Option<i64>,
+}
+
+/// 历史记录导出格式
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ExportFormat {
+    Json,
+    Jsonl,
+    Markdown,
+    Csv,
+}
+
+impl ExportFormat {
+    pub fn from_string(format: &str) -> Option<Self> {
+        match format.trim().to_lowercase().as_str() {
+            "json" => Some(Self::Json),
+            "jsonl" | "ndjson" => Some(Self::Jsonl),
+            "markdown" | "md" => Some(Self::Markdown),
+            "csv" => Some(Self::Csv),
+            _ => None,
+        }
+    }
+}
+
+/// 历史记录保留策略 (MCP_HISTORY_RETENTION_DAYS、MCP_HISTORY_MAX_ENTRIES 环境变量，0 表示不限制)
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RetentionPolicy {
+    pub max_age_days: i64,
+    pub max_entries: i64,
+}
+
+pub fn get_retention_policy() -> RetentionPolicy {
+    let env = |name: &str| std::env::var(name).ok().and_then(|value| value.trim().parse::<i64>().ok());
+    RetentionPolicy {
+        max_age_days: env("MCP_HISTORY_RETENTION_DAYS").unwrap_or(DEFAULT_RETENTION_DAYS),
+        max_entries: env("MCP_HISTORY_MAX_ENTRIES").unwrap_or(DEFAULT_MAX_ENTRIES),
+    }
+}
+
+/// 历史数据库路径 (MCP_HISTORY_DB 环境变量，默认为用户数据目录下的 cc-custom-mcp/feedback_history.db)
+pub fn get_history_db_path() -> PathBuf {
+    if let Some(path) = std::env::var_os("MCP_HISTORY_DB").filter(|path| !path.is_empty()) {
+        return PathBuf::from(path);
+    }
+
+    // 临时目录可能在重启后被清理，历史记录优先放在用户数据目录
+    let data_dir = if cfg!(windows) {
+        std::env::var_os("APPDATA").map(PathBuf::from)
+    } else if cfg!(target_os = "macos") {
+        std::env::var_os("HOME").map(|home| PathBuf::from(home).join("Library").join("Application Support"))
+    } else {
+        std::env::var_os("XDG_DATA_HOME")
+            .filter(|dir| !dir.is_empty())
+            .map(PathBuf::from)
+            .or_else(|| std::env::var_os("HOME").map(|home| PathBuf::from(home).join(".local").join("share")))
+    };
+    let mut path = data_dir.map(|dir| dir.join("cc-custom-mcp")).unwrap_or_else(crate::get_shared_storage_dir);
+    path.push("feedback_history.db");
+    path
+}
+
+pub struct FeedbackHistory {
+    conn: Connection,
+}
+
+impl FeedbackHistory {
+    pub fn open(path: &Path) -> Result<Self> {
+        if let Some(dir) = path.parent() {
+            std::fs::create_dir_all(dir)?;
+        }
+        let conn = Connection::open(path)?;
+        // GUI 和多个 MCP 进程可能同时访问数据库
+        conn.busy_timeout(Duration::from_secs(5))?;
+        conn.pragma_update(None, "journal_mode", "WAL")?;
+        conn.execute_batch(SCHEMA)?;
+        Ok(Self { conn })
+    }
+
+    pub fn open_default() -> Result<Self> {
+        Self::open(&get_history_db_path())
+    }
+
+    pub fn record(&mut self, entry: &HistoryEntry) -> Result<()> {
+        // 先删除再插入，让触发器同步更新全文索引 (REPLACE 默认不触发删除触发器)
+        let tx = self.conn.transaction()?;
+        tx.execute("DELETE FROM feedback_sessions WHERE session_id = ?1", params![entry.session_id])?;
+        tx.execute(
+            "INSERT INTO feedback_sessions
+                (session_id, ai_response, context, ai_source, ai_source_display, status,
+                 user_feedback, details, requested_at, finished_at, duration_ms)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11)",
+            params![
+                entry.session_id,
+                entry.ai_response,
+                entry.context,
+                entry.ai_source,
+                entry.ai_source_display,
+                entry.status,
+                entry.user_feedback,
+                entry.details.as_ref().map(Value::to_string),
+                entry.requested_at,
+                entry.finished_at,
+                entry.duration_ms,
+            ],
+        )?;
+        tx.commit()?;
+        Ok(())
+    }
+
+    pub fn search(&self, query: &HistoryQuery) -> Result<Vec<HistoryEntry>> {
+        let mut conditions: Vec<&str> = Vec::new();
+        let mut values: Vec<String> = Vec::new();
+        let mut filter = |condition: &'static str, value: String| {
+            conditions.push(condition);
+            values.push(value);
+        };
+
+        if let Some(text) = query.query.as_deref().map(str::trim).filter(|text| !text.is_empty()) {
+            filter("s.id IN (SELECT rowid FROM feedback_fts WHERE feedback_fts MATCH ?)", fts_query(text));
+        }
+        if let Some(ai_source) = query.ai_source.as_deref().filter(|v| !v.is_empty()) {
+            filter("s.ai_source = ?", ai_source.to_string());
+        }
+        if let Some(context) = query.context.as_deref().filter(|v| !v.is_empty()) {
+            filter("s.context = ?", context.to_string());
+        }
+        if let Some(status) = query.status.as_deref().filter(|v| !v.is_empty()) {
+            filter("s.status = ?", status.to_string());
+        }
+        if let Some(since) = query.since.as_deref().filter(|v| !v.is_empty()) {
+            filter("s.requested_at >= ?", normalize_timestamp(since, false)?);
+        }
+        if let Some(until) = query.until.as_deref().filter(|v| !v.is_empty()) {
+            filter("s.requested_at <= ?", normalize_timestamp(until, true)?);
+        }
+
+        let mut sql = String::from(
+            "SELECT s.session_id, s.ai_response, s.context, s.ai_source, s.ai_source_display, s.status,
+                    s.user_feedback, s.details, s.requested_at, s.finished_at, s.duration_ms
+             FROM feedback_sessions s",
+        );
+        if !conditions.is_empty() {
+            sql.push_str(" WHERE ");
+            sql.push_str(&conditions.join(" AND "));
+        }
+        // 负数 limit 表示不限制条数，用于导出
+        sql.push_str(&format!(
+            " ORDER BY s.requested_at DESC LIMIT {} OFFSET {}",
+            query.limit.unwrap_or(DEFAULT_QUERY_LIMIT),
+            query.offset.unwrap_or(0).max(0)
+        ));
+
+        let mut stmt = self.conn.prepare(&sql)?;
+        let rows = stmt.query_map(rusqlite::params_from_iter(values.iter()), row_to_entry)?;
+        Ok(rows.collect::<rusqlite::Result<Vec<_>>>()?)
+    }
+
+    /// 导出符合条件的全部记录，按时间先后排列便于回顾；未指定 limit 时不限制条数
+    pub fn export(&self, query: &HistoryQuery, format: ExportFormat) -> Result<String> {
+        let mut query = query.clone();
+        query.limit.get_or_insert(-1);
+        let mut entries = self.search(&query)?;
+        entries.reverse();
+        export_entries(&entries, format)
+    }
+
+    pub fn delete(&self, session_id: &str) -> Result<bool> {
+        Ok(self.conn.execute("DELETE FROM feedback_sessions WHERE session_id = ?1", params![session_id])? > 0)
+    }
+
+    /// 按保留策略删除过期和超出数量上限的记录，返回删除条数
+    pub fn apply_retention(&self, policy: RetentionPolicy) -> Result<usize> {
+        let mut removed = 0;
+        if policy.max_age_days > 0 {
+            let cutoff = (chrono::Utc::now() - chrono::Duration::days(policy.max_age_days)).to_rfc3339();
+            removed += self.conn.execute("DELETE FROM feedback_sessions WHERE requested_at < ?1", params![cutoff])?;
+        }
+        if policy.max_entries > 0 {
+            removed += self.conn.execute(
+                "DELETE FROM feedback_sessions WHERE id NOT IN
+                    (SELECT id FROM feedback_sessions ORDER BY requested_at DESC LIMIT ?1)",
+                params![policy.max_entries],
+            )?;
+        }
+        Ok(removed)
+    }
+}
+
+pub fn export_entries(entries: &[HistoryEntry], format: ExportFormat) -> Result<String> {
+    match format {
+        ExportFormat::Json => Ok(serde_json::to_string_pretty(entries)?),
+        ExportFormat::Jsonl => {
+            let mut output = String::new();
+            for entry in entries {
+                output.push_str(&serde_json::to_string(entry)?);
+                output.push('\n');
+            }
+            Ok(output)
+        }
+        ExportFormat::Markdown => Ok(export_markdown(entries)),
+        ExportFormat::Csv => export_csv(entries),
+    }
+}
+
+// Markdown 对话记录，每个会话一节
+fn export_markdown(entries: &[HistoryEntry]) -> String {
+    let mut output = String::from("# Feedback History\n");
+    for entry in entries {
+        output.push_str(&format!(
+            "\n## {} · {} · {}\n\n- Session: `{}`\n- Status: {}\n- Duration: {:.1}s\n\n### AI\n\n{}\n",
+            entry.requested_at,
+            entry.ai_source_display,
+            entry.context,
+            entry.session_id,
+            entry.status,
+            entry.duration_ms as f64 / 1000.0,
+            entry.ai_response.trim_end(),
+        ));
+        if let Some(feedback) = &entry.user_feedback {
+            // 取消和超时记录的是原因而不是用户回复
+            let heading = if entry.status == "answered" { "User" } else { "Outcome" };
+            output.push_str(&format!("\n### {}\n\n{}\n", heading, feedback.trim_end()));
+        }
+        if let Some(details) = &entry.details {
+            let details = serde_json::to_string_pretty(details).unwrap_or_default();
+            output.push_str(&format!("\n```json\n{}\n```\n", details));
+        }
+        output.push_str("\n---\n");
+    }
+    output
+}
+
+fn export_csv(entries: &[HistoryEntry]) -> Result<String> {
+    let mut writer = csv::Writer::from_writer(Vec::new());
+    writer.write_record([
+        "session_id", "requested_at", "finished_at", "duration_ms", "ai_source", "ai_source_display",
+        "context", "status", "ai_response", "user_feedback", "details",
+    ])?;
+    for entry in entries {
+        writer.write_record([
+            entry.session_id.clone(),
+            entry.requested_at.clone(),
+            entry.finished_at.clone(),
+            entry.duration_ms.to_string(),
+            entry.ai_source.clone(),
+            entry.ai_source_display.clone(),
+            entry.context.clone(),
+            entry.status.clone(),
+            entry.ai_response.clone(),
+            entry.user_feedback.clone().unwrap_or_default(),
+            entry.details.as_ref().map(Value::to_string).unwrap_or_default(),
+        ])?;
+    }
+    Ok(String::from_utf8(writer.into_inner().map_err(|e| anyhow::anyhow!("{}", e))?)?)
+}
+
+// 统一转换为 UTC RFC 3339 格式，保证与存储的时间字符串可以直接比较；只有日期时按当天开始或结束处理
+fn normalize_timestamp(value: &str, end_of_day: bool) -> Result<String> {
+    let value = value.trim();
+    if let Ok(datetime) = chrono::DateTime::parse_from_rfc3339(value) {
+        return Ok(datetime.with_timezone(&chrono::Utc).to_rfc3339());
+    }
+    let date = chrono::NaiveDate::parse_from_str(value, "%Y-%m-%d")
+        .map_err(|_| anyhow::anyhow!("Invalid date '{}', expected YYYY-MM-DD or RFC 3339", value))?;
+    let time = if end_of_day { date.and_hms_milli_opt(23, 59, 59, 999) } else { date.and_hms_opt(0, 0, 0) };
+    Ok(time.unwrap().and_utc().to_rfc3339())
+}
+
+// 把每个词包成 FTS5 字符串 (`"` 写成 `""`)，避免 `foo-bar`、`c++` 之类的输入被当作查询语法
+fn fts_query(text: &str) -> String {
+    text.split_whitespace().map(|term| format!("\"{}\"", term.replace('"', "\"\""))).collect::<Vec<_>>().join(" ")
+}
+
+fn row_to_entry(row: &rusqlite::Row) -> rusqlite::Result<HistoryEntry> {
+    let details: Option<String> = row.get(7)?;
+    Ok(HistoryEntry {
+        session_id: row.get(0)?,
+        ai_response: row.get(1)?,
+        context: row.get(2)?,
+        ai_source: row.get(3)?,
+        ai_source_display: row.get(4)?,
+        status: row.get(5)?,
+        user_feedback: row.get(6)?,
+        details: details.and_then(|details| serde_json::from_str(&details).ok()),
+        requested_at: row.get(8)?,
+        finished_at: row.get(9)?,
+        duration_ms: row.get(10)?,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(session_id: &str, ai_response: &str, user_feedback: &str, requested_at: &str) -> HistoryEntry {
+        HistoryEntry {
+            session_id: session_id.to_string(),
+            ai_response: ai_response.to_string(),
+            context: "Feedback Session".to_string(),
+            ai_source: "cursor".to_string(),
+            ai_source_display: "Cursor AI".to_string(),
+            status: "answered".to_string(),
+            user_feedback: Some(user_feedback.to_string()),
+            details: None,
+            requested_at: requested_at.to_string(),
+            finished_at: requested_at.to_string(),
+            duration_ms: 1000,
+        }
+    }
+
+    fn open_temp() -> (FeedbackHistory, PathBuf) {
+        let path = std::env::temp_dir().join(format!("mcp_history_test_{}.db", uuid::Uuid::new_v4()));
+        (FeedbackHistory::open(&path).unwrap(), path)
+    }
+
+    #[test]
+    fn test_record_and_search() {
+        let (mut history, path) = open_temp();
+        history.record(&entry("a", "Refactored the parser", "looks good", "2026-01-01T10:00:00+00:00")).unwrap();
+        history.record(&entry("b", "Added database migrations", "rename the table", "2026-01-02T10:00:00+00:00")).unwrap();
+
+        let all = history.search(&HistoryQuery::default()).unwrap();
+        assert_eq!(all.iter().map(|e| e.session_id.as_str()).collect::<Vec<_>>(), vec!["b", "a"]);
+
+        let found = history.search(&HistoryQuery { query: Some("parser".to_string()), ..Default::default() }).unwrap();
+        assert_eq!(found.len(), 1);
+        assert_eq!(found[0].session_id, "a");
+        let found = history.search(&HistoryQuery { query: Some("rename".to_string()), ..Default::default() }).unwrap();
+        assert_eq!(found[0].session_id, "b");
+
+        let found = history.search(&HistoryQuery { since: Some("2026-01-02".to_string()), ..Default::default() }).unwrap();
+        assert_eq!(found.len(), 1);
+        let found = history.search(&HistoryQuery { until: Some("2026-01-01".to_string()), ..Default::default() }).unwrap();
+        assert_eq!(found[0].session_id, "a");
+        assert!(history.search(&HistoryQuery { since: Some("yesterday".to_string()), ..Default::default() }).is_err());
+
+        // 重复记录同一会话时替换旧内容
+        history.record(&entry("b", "Added database migrations", "keep the table name", "2026-01-02T10:00:00+00:00")).unwrap();
+        assert!(history.search(&HistoryQuery { query: Some("rename".to_string()), ..Default::default() }).unwrap().is_empty());
+        assert_eq!(history.search(&HistoryQuery::default()).unwrap().len(), 2);
+
+        // 删除后全文索引同步更新
+        assert!(history.delete("a").unwrap());
+        assert!(history.search(&HistoryQuery { query: Some("parser".to_string()), ..Default::default() }).unwrap().is_empty());
+        let remaining = history.search(&HistoryQuery::default()).unwrap();
+        assert_eq!(remaining.len(), 1);
+        assert_eq!(remaining[0].user_feedback.as_deref(), Some("keep the table name"));
+
+        std::fs::remove_file(&path).ok();
+    }
+
+    #[test]
+    fn test_search_with_punctuation() {
+        let (mut history, path) = open_temp();
+        history.record(&entry("a", "Fixed the foo-bar module", "port it to c++", "2026-01-01T10:00:00+00:00")).unwrap();
+        history.record(&entry("b", "Escaped a\"b in the parser", "thanks", "2026-01-02T10:00:00+00:00")).unwrap();
+
+        let search = |text: &str| {
+            let found = history.search(&HistoryQuery { query: Some(text.to_string()), ..Default::default() }).unwrap();
+            found.into_iter().map(|e| e.session_id).collect::<Vec<_>>()
+        };
+        assert_eq!(search("foo-bar"), vec!["a"]);
+        assert_eq!(search("c++"), vec!["a"]);
+        assert_eq!(search("a\"b"), vec!["b"]);
+        // 运算符也按普通词匹配
+        assert_eq!(search("NOT parser"), Vec::<String>::new());
+        assert_eq!(search("foo-bar thanks"), Vec::<String>::new());
+
+        std::fs::remove_file(&path).ok();
+    }
+
+    #[test]
+    fn test_export_formats() {
+        let (mut history, path) = open_temp();
+        history.record(&entry("b", "Second, \"quoted\"", "ok", "2026-01-02T10:00:00+00:00")).unwrap();
+        history.record(&entry("a", "First", "line one\nline two", "2026-01-01T10:00:00+00:00")).unwrap();
+        let query = HistoryQuery::default();
+
+        let jsonl = history.export(&query, ExportFormat::Jsonl).unwrap();
+        let sessions: Vec<String> = jsonl
+            .lines()
+            .map(|line| serde_json::from_str::<HistoryEntry>(line).unwrap().session_id)
+            .collect();
+        assert_eq!(sessions, vec!["a", "b"]);
+
+        let markdown = history.export(&query, ExportFormat::Markdown).unwrap();
+        assert!(markdown.find("### AI\n\nFirst").unwrap() < markdown.find("Second").unwrap());
+        assert!(markdown.contains("### User\n\nline one\nline two"));
+
+        let csv = history.export(&query, ExportFormat::Csv).unwrap();
+        let mut reader = csv::Reader::from_reader(csv.as_bytes());
+        let rows: Vec<csv::StringRecord> = reader.records().map(|row| row.unwrap()).collect();
+        assert_eq!(rows.len(), 2);
+        assert_eq!(&rows[1][8], "Second, \"quoted\"");
+        assert_eq!(&rows[0][9], "line one\nline two");
+
+        assert_eq!(ExportFormat::from_string("MD"), Some(ExportFormat::Markdown));
+        assert_eq!(ExportFormat::from_string("xml"), None);
+
+        std::fs::remove_file(&path).ok();
+    }
+
+    #[test]
+    fn test_retention_policy() {
+        let (mut history, path) = open_temp();
+        let now = chrono::Utc::now();
+        history.record(&entry("old", "x", "y", &(now - chrono::Duration::days(100)).to_rfc3339())).unwrap();
+        for (index, days) in [3, 2, 1].iter().enumerate() {
+            let requested_at = (now - chrono::Duration::days(*days)).to_rfc3339();
+            history.record(&entry(&format!("recent-{}", index), "x", "y", &requested_at)).unwrap();
+        }
+
+        assert_eq!(history.apply_retention(RetentionPolicy { max_age_days: 90, max_entries: 2 }).unwrap(), 2);
+        let remaining = history.search(&HistoryQuery::default()).unwrap();
+        assert_eq!(remaining.iter().map(|e| e.session_id.as_str()).collect::<Vec<_>>(), vec!["recent-2", "recent-1"]);
+
+        std::fs::remove_file(&path).ok();
+    }
+}
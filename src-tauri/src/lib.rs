@@ -1,19 +1,40 @@
 #![allow(unexpected_cfgs)]
 
+mod attachments;
+mod auto_reply;
+mod cli;
+mod feedback_ipc;
+mod feedback_store;
+mod file_lister;
+mod file_reader;
+mod file_searcher;
+mod file_writer;
+mod history;
+mod mcp_http;
 mod mcp_server;
+mod sandbox;
 mod system_sound;
+mod templates;
+mod tui;
 
-use mcp_server::LocalMcpServer;
+use attachments::{AttachmentLimits, AttachmentUpload};
+use auto_reply::{AutoReplyRule, RouteTarget, RuleAction, RuleSet};
+use feedback_ipc::FeedbackResponse;
+use history::{ExportFormat, FeedbackHistory, HistoryEntry, HistoryQuery};
+use mcp_server::{validate_feedback_response, FeedbackTimeoutSettings, LocalMcpServer};
 use system_sound::play_notification_sound_async;
+use templates::{ReplyTemplate, TemplateLibrary};
+use std::collections::{HashMap, HashSet};
 use std::sync::{Arc, Mutex};
 use std::sync::atomic::{AtomicBool, Ordering};
 use std::thread;
-use std::time::Duration;
+use std::time::{Duration, Instant};
 use std::fs;
 use std::path::PathBuf;
 use tauri::{AppHandle, State, Emitter, Manager};
 use serde_json::{json, Value};
 use serde::{Deserialize, Serialize};
+use tokio_util::sync::CancellationToken;
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
 struct WindowSize {
@@ -37,14 +58,50 @@ impl Default for WindowSize {
 }
 
 struct AppState {
-    mcp_server: Arc<Mutex<Option<LocalMcpServer>>>,
+    mcp_server: Arc<Mutex<Option<Arc<LocalMcpServer>>>>,
     server_running: Arc<Mutex<bool>>,
+    http_shutdown: Arc<Mutex<Option<CancellationToken>>>,
     file_watcher_stop: Arc<AtomicBool>,
 }
 
+lazy_static::lazy_static! {
+    // 已在 GUI 中提交或取消的会话，用于区分 GUI 侧结束和客户端取消
+    // 请求文件消失后由文件监听器移除
+    static ref GUI_CLOSED_SESSIONS: Mutex<HashSet<String>> = Mutex::new(HashSet::new());
+    // 已应用自动回复规则的会话，false 表示定时动作尚未执行；会话结束时移除
+    static ref AUTO_RULE_SESSIONS: Mutex<HashMap<String, bool>> = Mutex::new(HashMap::new());
+    // 自动规则会话中用户最后一次编辑回复的时间，定时动作从这里重新计时；会话结束时移除
+    static ref AUTO_RULE_ACTIVITY: Mutex<HashMap<String, Instant>> = Mutex::new(HashMap::new());
+}
+
+// 会话已回复、取消或撤回，清除它的自动规则记录
+pub(crate) fn forget_feedback_session(session_id: &str) {
+    if let Ok(mut sessions) = AUTO_RULE_SESSIONS.lock() {
+        sessions.remove(session_id);
+    }
+    if let Ok(mut activity) = AUTO_RULE_ACTIVITY.lock() {
+        activity.remove(session_id);
+    }
+}
+
+// 用户正在会话卡片中编辑回复，推迟按规则定时的自动回复
+#[tauri::command]
+fn touch_feedback_session(session_id: String) {
+    let has_rule = AUTO_RULE_SESSIONS.lock().is_ok_and(|sessions| sessions.contains_key(&session_id));
+    if has_rule {
+        if let Ok(mut activity) = AUTO_RULE_ACTIVITY.lock() {
+            activity.insert(session_id, Instant::now());
+        }
+    }
+}
+
 // --- 文件系统 IPC 辅助函数 ---
 
+// 共享存储目录，可通过 MCP_SHARED_DIR 环境变量修改 (GUI 和 MCP 进程需使用相同的设置)
 fn get_shared_storage_dir() -> PathBuf {
+    if let Some(dir) = std::env::var_os("MCP_SHARED_DIR").filter(|dir| !dir.is_empty()) {
+        return PathBuf::from(dir);
+    }
     let mut path = std::env::temp_dir();
     path.push("mcp_manager");
     path
@@ -112,6 +169,174 @@ fn get_feedback_response_path(session_id: &str) -> PathBuf {
 
 // --- 文件监听器 ---
 
+// 向前端发送反馈请求事件 (文件和 socket 两种通道共用)
+// 发送反馈请求到前端前先匹配自动回复规则
+fn emit_feedback_request(app: &AppHandle, request_data: &Value, play_sound: bool) -> bool {
+    let session_id = request_data["sessionId"].as_str().unwrap_or_default().to_string();
+
+    // 同一会话可能因为文件监听重复到达，规则只应用一次
+    let applied = AUTO_RULE_SESSIONS.lock().ok().and_then(|sessions| sessions.get(&session_id).copied());
+    match applied {
+        Some(false) => return true,
+        Some(true) => return show_feedback_request(app, request_data, play_sound, false),
+        None => {}
+    }
+
+    let rule = match RuleSet::load_default() {
+        Ok(rules) => rules.find_match(request_data).cloned(),
+        Err(e) => {
+            eprintln!("⚠️ Failed to load auto-reply rules: {}", e);
+            None
+        }
+    };
+    let Some(rule) = rule else {
+        return show_feedback_request(app, request_data, play_sound, false);
+    };
+
+    let mark_applied = |done: bool| {
+        if let Ok(mut sessions) = AUTO_RULE_SESSIONS.lock() {
+            sessions.insert(session_id.clone(), done);
+        }
+    };
+    match rule.action.clone() {
+        RuleAction::Reply { text, after_seconds: 0 } => {
+            mark_applied(true);
+            if auto_reply_feedback(app, &rule, request_data, &text) {
+                return true;
+            }
+            show_feedback_request(app, request_data, play_sound, false)
+        }
+        RuleAction::Reply { text, after_seconds } => {
+            mark_applied(true);
+            schedule_auto_rule(app, rule, request_data, after_seconds, move |app, rule, request_data| {
+                auto_reply_feedback(app, rule, request_data, &text);
+            });
+            show_feedback_request(app, request_data, play_sound, false)
+        }
+        RuleAction::Delay { seconds } => {
+            mark_applied(false);
+            auto_reply::log_action(&session_id, &rule, "delayed", &format!("{} seconds", seconds));
+            schedule_auto_rule(app, rule, request_data, seconds, move |app, _, request_data| {
+                // 等待期间会话已结束时记录已被清除，不再重新加入
+                if let Ok(mut sessions) = AUTO_RULE_SESSIONS.lock() {
+                    if let Some(applied) = sessions.get_mut(request_data["sessionId"].as_str().unwrap_or_default()) {
+                        *applied = true;
+                    }
+                }
+                show_feedback_request(app, request_data, play_sound, false);
+            });
+            true
+        }
+        RuleAction::Route { to: RouteTarget::Silent, .. } => {
+            mark_applied(true);
+            auto_reply::log_action(&session_id, &rule, "routed", "silent");
+            show_feedback_request(app, request_data, false, true)
+        }
+        RuleAction::Route { to: RouteTarget::Cancel, message } => {
+            mark_applied(true);
+            auto_reply::log_action(&session_id, &rule, "cancelled", message.as_deref().unwrap_or_default());
+            if let Err(e) = cancel_feedback_session(&session_id) {
+                eprintln!("❌ Failed to cancel feedback session {}: {}", session_id, e);
+            }
+            true
+        }
+    }
+}
+
+// 在后台等待用户无操作一段时间后执行规则动作，期间用户已回复或客户端已取消则跳过
+// 用户在会话卡片中编辑回复时从最后一次编辑重新计时 (延迟显示的请求尚未显示，不会有编辑)
+fn schedule_auto_rule<F>(app: &AppHandle, rule: AutoReplyRule, request_data: &Value, seconds: u64, action: F)
+where
+    F: FnOnce(&AppHandle, &AutoReplyRule, &Value) + Send + 'static,
+{
+    let app = app.clone();
+    let request_data = request_data.clone();
+    thread::spawn(move || {
+        let session_id = request_data["sessionId"].as_str().unwrap_or_default();
+        let idle = Duration::from_secs(seconds);
+        let mut deadline = Instant::now() + idle;
+        loop {
+            thread::sleep(deadline.saturating_duration_since(Instant::now()));
+            let last_activity = AUTO_RULE_ACTIVITY.lock().ok().and_then(|activity| activity.get(session_id).copied());
+            match last_activity {
+                Some(last_activity) if last_activity + idle > Instant::now() => deadline = last_activity + idle,
+                _ => break,
+            }
+        }
+        if is_feedback_pending(session_id) {
+            action(&app, &rule, &request_data);
+        }
+    });
+}
+
+// 会话是否仍在等待回复 (socket 连接中，或请求文件存在且未处理)
+fn is_feedback_pending(session_id: &str) -> bool {
+    feedback_ipc::pending_request(session_id).is_some() || is_request_file_pending(&get_feedback_request_path(session_id))
+}
+
+fn is_request_file_pending(path: &std::path::Path) -> bool {
+    fs::read_to_string(path)
+        .ok()
+        .and_then(|content| serde_json::from_str::<Value>(&content).ok())
+        .is_some_and(|request| !request.get("processed").and_then(|v| v.as_bool()).unwrap_or(false))
+}
+
+// 按规则自动回复，回复不符合请求要求 (如必须选择选项) 时返回 false
+fn auto_reply_feedback(app: &AppHandle, rule: &AutoReplyRule, request_data: &Value, text: &str) -> bool {
+    let session_id = request_data["sessionId"].as_str().unwrap_or_default();
+    let reply = auto_reply::render_reply(text, request_data);
+    let response = FeedbackResponse { feedback: reply.clone(), ..Default::default() };
+    if let Err(e) = validate_feedback_response(request_data, &response) {
+        eprintln!("⚠️ Auto-reply rule '{}' skipped for {}: {}", rule.name, session_id, e);
+        return false;
+    }
+    if let Err(e) = deliver_feedback_response(session_id, response) {
+        eprintln!("❌ Failed to deliver auto-reply for {}: {}", session_id, e);
+        return false;
+    }
+
+    auto_reply::log_action(session_id, rule, "replied", &reply);
+    // 通知前端关闭已显示的会话
+    let _ = app.emit("feedback-auto-replied", json!({ "sessionId": session_id, "rule": rule.name, "reply": reply }));
+    true
+}
+
+// silent 为 true 时前端不置顶窗口
+fn show_feedback_request(app: &AppHandle, request_data: &Value, play_sound: bool, silent: bool) -> bool {
+    let feedback_data = json!({
+        "sessionId": request_data["sessionId"],
+        "aiResponse": request_data["aiResponse"],
+        "context": request_data["context"],
+        "timestamp": request_data["timestamp"],
+        "aiSource": request_data.get("aiSource").and_then(|v| v.as_str()).unwrap_or("unknown"),
+        "aiSourceDisplay": request_data.get("aiSourceDisplay").and_then(|v| v.as_str()).unwrap_or("Unknown AI Tool"),
+        "timeoutSeconds": request_data["timeoutSeconds"],
+        "onTimeout": request_data["onTimeout"],
+        "expiresAt": request_data["expiresAt"],
+        "options": request_data["options"],
+        "multiSelect": request_data["multiSelect"],
+        "fields": request_data["fields"],
+        "allowFreeText": request_data.get("allowFreeText").and_then(|v| v.as_bool()).unwrap_or(true),
+        "silent": silent
+    });
+
+    if app.emit("feedback-request", &feedback_data).is_err() {
+        return false;
+    }
+    if play_sound {
+        thread::spawn(|| {
+            if let Ok(rt) = tokio::runtime::Runtime::new() {
+                rt.block_on(async {
+                    if let Err(e) = play_notification_sound_async().await {
+                        eprintln!("🔔 Failed to play notification sound: {}", e);
+                    }
+                });
+            }
+        });
+    }
+    true
+}
+
 // 处理单个 feedback 请求文件的通用函数
 fn process_feedback_request_file(app: &AppHandle, path: &std::path::Path, is_startup_scan: bool) -> bool {
     match fs::read_to_string(path) {
@@ -152,29 +377,12 @@ fn process_feedback_request_file(app: &AppHandle, path: &std::path::Path, is_sta
                         return false;
                     }
 
-                    let feedback_data = json!({
-                        "sessionId": request_data["sessionId"],
-                        "aiResponse": request_data["aiResponse"],
-                        "context": request_data["context"],
-                        "timestamp": request_data["timestamp"],
-                        "aiSource": request_data.get("aiSource").and_then(|v| v.as_str()).unwrap_or("unknown"),
-                        "aiSourceDisplay": request_data.get("aiSourceDisplay").and_then(|v| v.as_str()).unwrap_or("Unknown AI Tool")
-                    });
-
-                    if app.emit("feedback-request", &feedback_data).is_ok() {
-                        // 只在非启动扫描时播放通知声音
-                        if !is_startup_scan {
-                            thread::spawn(|| {
-                                if let Ok(rt) = tokio::runtime::Runtime::new() {
-                                    rt.block_on(async {
-                                        if let Err(e) = play_notification_sound_async().await {
-                                            eprintln!("🔔 Failed to play notification sound: {}", e);
-                                        }
-                                    });
-                                }
-                            });
+                    // 只在非启动扫描时播放通知声音
+                    if emit_feedback_request(app, &request_data, !is_startup_scan) {
+                        // 自动回复可能已经把请求标记为已处理，此时不再覆盖请求文件
+                        if !is_request_file_pending(path) {
+                            return true;
                         }
-
                         // 更新处理时间，但不标记为已完成处理
                         request_data["last_processed_at"] = json!(now);
                         if let Err(e) = fs::write(path, serde_json::to_string_pretty(&request_data).unwrap()) {
@@ -242,22 +450,44 @@ fn start_file_watcher(app: AppHandle, stop_signal: Arc<AtomicBool>) {
         // 启动时执行初始扫描
         perform_initial_scan(&app);
 
+        let mut known_sessions: HashSet<String> = HashSet::new();
+
         loop {
             if stop_signal.load(Ordering::Relaxed) {
                 eprintln!("🛑 File watcher stopping due to stop signal");
                 break;
             }
 
-            thread::sleep(Duration::from_millis(1000)); // 增加间隔以减少CPU使用
+            // 反馈请求优先通过 socket 送达，目录扫描只作为回退通道
+            thread::sleep(Duration::from_millis(3000));
 
             match fs::read_dir(&requests_dir) {
                 Ok(entries) => {
+                    let mut current_sessions = HashSet::new();
                     for entry in entries.flatten() {
                         let path = entry.path();
                         if path.extension().and_then(|s| s.to_str()) == Some("json") {
                             process_feedback_request_file(&app, &path, false);
+                            if let Some(stem) = path.file_stem().and_then(|s| s.to_str()) {
+                                current_sessions.insert(stem.to_string());
+                            }
                         }
                     }
+
+                    // 请求文件消失但会话并非在 GUI 中结束，说明客户端已取消，通知前端关闭会话
+                    for session_id in known_sessions.difference(&current_sessions) {
+                        let closed_in_gui = GUI_CLOSED_SESSIONS.lock().map(|mut closed| closed.remove(session_id)).unwrap_or(false);
+                        if !closed_in_gui {
+                            eprintln!("🛑 Feedback request {} was withdrawn, dismissing session", session_id);
+                            forget_feedback_session(session_id);
+                            let _ = app.emit("feedback-cancelled", json!({ "sessionId": session_id }));
+                        }
+                    }
+                    // 请求文件在被扫描到之前就已删除的会话不会出现在 known_sessions 中，一并清除
+                    if let Ok(mut closed) = GUI_CLOSED_SESSIONS.lock() {
+                        closed.retain(|session_id| get_feedback_request_path(session_id).exists());
+                    }
+                    known_sessions = current_sessions;
                 }
                 Err(e) => {
                     eprintln!("❌ Failed to read requests directory {:?}: {}", requests_dir, e);
@@ -269,21 +499,40 @@ fn start_file_watcher(app: AppHandle, stop_signal: Arc<AtomicBool>) {
 
 // --- Tauri 命令 ---
 
-#[tauri::command]
-async fn start_mcp_server(app: AppHandle, state: State<'_, AppState>) -> Result<String, String> {
-    let mut server_guard = state.mcp_server.lock().unwrap();
-    let mut running_guard = state.server_running.lock().unwrap();
-
-    if *running_guard {
+// 在 GUI 进程中启动 MCP HTTP 传输，多个 AI 客户端可共享同一个服务器和反馈窗口
+async fn start_http_transport(app: &AppHandle, state: &AppState) -> Result<String, String> {
+    if *state.server_running.lock().unwrap() {
         return Ok("MCP server is already running".to_string());
     }
 
+    let port = mcp_http::get_http_port();
+    let listener = tokio::net::TcpListener::bind(("127.0.0.1", port))
+        .await
+        .map_err(|e| format!("Failed to bind MCP HTTP transport on port {}: {}", port, e))?;
+
     let mut server = LocalMcpServer::new();
     server.set_app_handle(app.clone());
-    *server_guard = Some(server);
-    *running_guard = true;
+    let server = Arc::new(server);
+    let shutdown = CancellationToken::new();
 
-    Ok("MCP server started successfully".to_string())
+    let http_server = server.clone();
+    let http_shutdown = shutdown.clone();
+    tauri::async_runtime::spawn(async move {
+        if let Err(e) = mcp_http::serve(http_server, listener, http_shutdown).await {
+            eprintln!("❌ MCP HTTP transport failed: {}", e);
+        }
+    });
+
+    *state.mcp_server.lock().unwrap() = Some(server);
+    *state.http_shutdown.lock().unwrap() = Some(shutdown);
+    *state.server_running.lock().unwrap() = true;
+
+    Ok(format!("MCP server started successfully at {}", mcp_http::endpoint_url(port)))
+}
+
+#[tauri::command]
+async fn start_mcp_server(app: AppHandle, state: State<'_, AppState>) -> Result<String, String> {
+    start_http_transport(&app, &state).await
 }
 
 #[tauri::command]
@@ -291,6 +540,9 @@ fn stop_mcp_server(state: State<'_, AppState>) -> Result<String, String> {
     let mut server_guard = state.mcp_server.lock().unwrap();
     let mut running_guard = state.server_running.lock().unwrap();
 
+    if let Some(shutdown) = state.http_shutdown.lock().unwrap().take() {
+        shutdown.cancel();
+    }
     *server_guard = None;
     *running_guard = false;
 
@@ -303,7 +555,7 @@ fn get_server_status(state: State<'_, AppState>) -> String {
 }
 
 #[tauri::command]
-fn list_available_tools(state: State<'_, AppState>) -> Result<Vec<serde_json::Value>, String> {
+fn list_available_tools(state: State<'_, AppState>) -> Result<Vec<rmcp::model::Tool>, String> {
     let server_guard = state.mcp_server.lock().unwrap();
     if let Some(server) = server_guard.as_ref() {
         Ok(server.list_tools())
@@ -313,18 +565,58 @@ fn list_available_tools(state: State<'_, AppState>) -> Result<Vec<serde_json::Va
 }
 
 #[tauri::command]
-async fn submit_feedback(session_id: String, feedback_content: String) -> Result<(), String> {
-    let response_path = get_feedback_response_path(&session_id);
-    let response_data = json!({
-        "feedback": feedback_content,
-        "timestamp": chrono::Utc::now().to_rfc3339()
+async fn submit_feedback(
+    session_id: String,
+    feedback_content: String,
+    selected_options: Option<Vec<String>>,
+    form_values: Option<Value>,
+    attachments: Option<Vec<AttachmentUpload>>,
+) -> Result<(), String> {
+    let mut response = FeedbackResponse { feedback: feedback_content, selected_options, form_values, attachments: Vec::new() };
+
+    // 按请求中的选项和表单定义校验回复，校验失败时前端可修改后重新提交
+    let request_path = get_feedback_request_path(&session_id);
+    let request_data = feedback_ipc::pending_request(&session_id).or_else(|| {
+        fs::read_to_string(&request_path).ok().and_then(|content| serde_json::from_str::<Value>(&content).ok())
     });
+    if let Some(request_data) = &request_data {
+        validate_feedback_response(request_data, &response)?;
+    }
+
+    // 附件保存到共享存储目录，反馈中只传递文件路径
+    if let Some(uploads) = attachments {
+        response.attachments = attachments::save_attachments(
+            &attachments::get_attachments_root(),
+            &session_id,
+            uploads,
+            attachments::get_attachment_limits(),
+        )
+        .map_err(|e| e.to_string())?;
+    }
+
+    deliver_feedback_response(&session_id, response)
+}
+
+// 优先通过 socket 回复，否则写入响应文件并把请求标记为已处理
+fn deliver_feedback_response(session_id: &str, response: FeedbackResponse) -> Result<(), String> {
+    forget_feedback_session(session_id);
+    if feedback_ipc::send_response(session_id, response.clone()) {
+        eprintln!("✅ Feedback delivered over socket: {}", session_id);
+        return Ok(());
+    }
+
+    let request_path = get_feedback_request_path(session_id);
+    let response_path = get_feedback_response_path(session_id);
+    let mut response_data = serde_json::to_value(&response).map_err(|e| e.to_string())?;
+    response_data["timestamp"] = json!(chrono::Utc::now().to_rfc3339());
 
     fs::write(&response_path, serde_json::to_string_pretty(&response_data).unwrap())
         .map_err(|e| e.to_string())?;
+    if let Ok(mut closed) = GUI_CLOSED_SESSIONS.lock() {
+        closed.insert(session_id.to_string());
+    }
     
     // 标记原始请求文件为已处理
-    let request_path = get_feedback_request_path(&session_id);
     if request_path.exists() {
         match fs::read_to_string(&request_path) {
             Ok(content) => {
@@ -353,11 +645,123 @@ async fn submit_feedback(session_id: String, feedback_content: String) -> Result
     Ok(())
 }
 
+#[tauri::command]
+fn get_attachment_limits() -> AttachmentLimits {
+    attachments::get_attachment_limits()
+}
+
+#[tauri::command]
+async fn search_feedback_history(query: HistoryQuery) -> Result<Vec<HistoryEntry>, String> {
+    tauri::async_runtime::spawn_blocking(move || FeedbackHistory::open_default()?.search(&query))
+        .await
+        .map_err(|e| e.to_string())?
+        .map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+async fn delete_feedback_history(session_id: String) -> Result<bool, String> {
+    tauri::async_runtime::spawn_blocking(move || FeedbackHistory::open_default()?.delete(&session_id))
+        .await
+        .map_err(|e| e.to_string())?
+        .map_err(|e| e.to_string())
+}
+
+// 导出符合条件的全部历史记录，默认为 JSON
+#[tauri::command]
+async fn export_feedback_history(query: HistoryQuery, format: Option<ExportFormat>) -> Result<String, String> {
+    let format = format.unwrap_or(ExportFormat::Json);
+    tauri::async_runtime::spawn_blocking(move || FeedbackHistory::open_default()?.export(&query, format))
+        .await
+        .map_err(|e| e.to_string())?
+        .map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+fn get_reply_templates() -> TemplateLibrary {
+    templates::load_library()
+}
+
+#[tauri::command]
+fn save_reply_template(template: ReplyTemplate) -> Result<TemplateLibrary, String> {
+    let mut library = templates::load_library();
+    library.upsert(template).map_err(|e| e.to_string())?;
+    templates::save_library(&library).map_err(|e| e.to_string())?;
+    Ok(library)
+}
+
+#[tauri::command]
+fn delete_reply_template(template_id: String) -> Result<TemplateLibrary, String> {
+    let mut library = templates::load_library();
+    if library.remove(&template_id) {
+        templates::save_library(&library).map_err(|e| e.to_string())?;
+    }
+    Ok(library)
+}
+
+// template_id 为空时清除该 AI 来源的默认模板
+#[tauri::command]
+fn set_default_reply_template(ai_source: String, template_id: Option<String>) -> Result<TemplateLibrary, String> {
+    let mut library = templates::load_library();
+    library.set_source_default(&ai_source, template_id).map_err(|e| e.to_string())?;
+    templates::save_library(&library).map_err(|e| e.to_string())?;
+    Ok(library)
+}
+
+// 用会话信息 (sessionId、context、aiSource、aiSourceDisplay) 渲染模板
+#[tauri::command]
+fn render_reply_template(template_id: String, session: Value) -> Result<String, String> {
+    let library = templates::load_library();
+    let template = library
+        .get(&template_id)
+        .ok_or_else(|| format!("Template '{}' not found", template_id))?;
+    Ok(templates::render_template(&template.content, &session))
+}
+
+// 保存的反馈超时设置，overriddenBy 为设置了的环境变量 (优先于保存的设置)
+#[tauri::command]
+fn get_feedback_timeout_settings() -> Value {
+    let overridden_by: Vec<&str> = mcp_server::FEEDBACK_TIMEOUT_ENV_VARS
+        .iter()
+        .copied()
+        .filter(|name| std::env::var(name).is_ok_and(|value| !value.trim().is_empty()))
+        .collect();
+    json!({ "settings": mcp_server::load_feedback_timeout_settings(), "overriddenBy": overridden_by })
+}
+
+#[tauri::command]
+fn save_feedback_timeout_settings(settings: FeedbackTimeoutSettings) -> Result<(), String> {
+    mcp_server::save_feedback_timeout_settings(&settings).map_err(|e| e.to_string())
+}
+
+// 模拟匹配自动回复规则，rules 为空时使用规则文件中的规则
+#[tauri::command]
+fn dry_run_auto_reply(request: Value, rules: Option<Vec<AutoReplyRule>>) -> Result<Value, String> {
+    let rules = match rules {
+        Some(rules) => RuleSet::compile(rules),
+        None => RuleSet::load_default(),
+    }
+    .map_err(|e| e.to_string())?;
+    Ok(auto_reply::dry_run(&rules, &request))
+}
+
 #[tauri::command]
 async fn cancel_feedback(session_id: String) -> Result<(), String> {
-    let request_path = get_feedback_request_path(&session_id);
+    cancel_feedback_session(&session_id)
+}
+
+fn cancel_feedback_session(session_id: &str) -> Result<(), String> {
+    forget_feedback_session(session_id);
+    if feedback_ipc::send_cancel(session_id) {
+        return Ok(());
+    }
+
+    let request_path = get_feedback_request_path(session_id);
     
     if request_path.exists() {
+        // 由 GUI 主动取消，无需再通知前端关闭会话
+        if let Ok(mut closed) = GUI_CLOSED_SESSIONS.lock() {
+            closed.insert(session_id.to_string());
+        }
         fs::remove_file(&request_path).map_err(|e| e.to_string())?;
     }
     Ok(())
@@ -512,15 +916,24 @@ async fn apply_window_size(app: AppHandle, window_size: WindowSize) -> Result<()
 #[tauri::command]
 async fn scan_pending_feedback(app: AppHandle) -> Result<String, String> {
     let requests_dir = get_feedback_request_path("").parent().unwrap().to_path_buf();
-    
-    // 确保目录存在
+    eprintln!("🔍 Manual scan for pending feedback requests triggered");
+
+    // 通过 socket 提交的请求没有请求文件，直接重新发送
+    let mut loaded_count = 0;
+    for request_data in feedback_ipc::pending_requests() {
+        if emit_feedback_request(&app, &request_data, false) {
+            loaded_count += 1;
+        }
+    }
+
     if !requests_dir.exists() {
-        return Ok("No pending feedback requests found (directory does not exist)".to_string());
+        return Ok(if loaded_count > 0 {
+            format!("Successfully loaded {} pending feedback requests", loaded_count)
+        } else {
+            "No pending feedback requests found".to_string()
+        });
     }
 
-    eprintln!("🔍 Manual scan for pending feedback requests triggered");
-    
-    let mut loaded_count = 0;
     match fs::read_dir(&requests_dir) {
         Ok(entries) => {
             for entry in entries.flatten() {
@@ -549,6 +962,19 @@ async fn scan_pending_feedback(app: AppHandle) -> Result<String, String> {
     Ok(result_msg)
 }
 
+#[tauri::command]
+fn get_http_config() -> String {
+    let config = json!({
+        "mcpServers": {
+            "cc-mcp": {
+                "type": "http",
+                "url": mcp_http::endpoint_url(mcp_http::get_http_port())
+            }
+        }
+    });
+    serde_json::to_string_pretty(&config).unwrap_or_default()
+}
+
 #[tauri::command]
 fn get_cursor_config() -> String {
     get_config_for_source("cursor")
@@ -603,10 +1029,15 @@ fn get_config_for_source(source: &str) -> String {
 
 #[cfg_attr(mobile, tauri::mobile_entry_point)]
 pub fn run() {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    if let Some(code) = cli::run(&args) {
+        std::process::exit(code);
+    }
+
     if std::env::args().any(|arg| arg == "--mcp-mode") {
         let rt = tokio::runtime::Runtime::new().unwrap();
         rt.block_on(async {
-            let server = LocalMcpServer::new();
+            let server = Arc::new(LocalMcpServer::new());
             if let Err(e) = server.start_stdio_server().await {
                 eprintln!("MCP Server failed to start: {}", e);
                 std::process::exit(1);
@@ -619,6 +1050,7 @@ pub fn run() {
     let app_state = AppState {
         mcp_server: Arc::new(Mutex::new(None)),
         server_running: Arc::new(Mutex::new(false)),
+        http_shutdown: Arc::new(Mutex::new(None)),
         file_watcher_stop: file_watcher_stop.clone(),
     };
 
@@ -631,6 +1063,7 @@ pub fn run() {
             get_server_status,
             list_available_tools,
             get_mcp_config,
+            get_http_config,
             get_cursor_config,
             get_augment_config,
             get_claude_desktop_config,
@@ -638,6 +1071,19 @@ pub fn run() {
             get_custom_config,
             submit_feedback,
             cancel_feedback,
+            get_attachment_limits,
+            search_feedback_history,
+            delete_feedback_history,
+            export_feedback_history,
+            get_reply_templates,
+            save_reply_template,
+            delete_reply_template,
+            set_default_reply_template,
+            render_reply_template,
+            get_feedback_timeout_settings,
+            save_feedback_timeout_settings,
+            dry_run_auto_reply,
+            touch_feedback_session,
             bring_window_to_front,
             play_notification_sound,
             scan_pending_feedback,
@@ -649,7 +1095,26 @@ pub fn run() {
         .setup(|app| {
             let state: State<AppState> = app.state();
             start_file_watcher(app.handle().clone(), state.file_watcher_stop.clone());
+
+            // 启动反馈 socket，失败时 MCP 进程会回退到文件 IPC
+            let ipc_app = app.handle().clone();
+            tauri::async_runtime::spawn(async move {
+                if let Err(e) = feedback_ipc::serve(ipc_app).await {
+                    eprintln!("❌ Feedback socket unavailable, using file IPC only: {}", e);
+                }
+            });
             
+            // 启动时清理超出保留期限的历史记录
+            tauri::async_runtime::spawn_blocking(|| {
+                let removed = FeedbackHistory::open_default()
+                    .and_then(|history| history.apply_retention(history::get_retention_policy()));
+                match removed {
+                    Ok(removed) if removed > 0 => eprintln!("🗑️ Removed {} expired feedback history entries", removed),
+                    Ok(_) => {}
+                    Err(e) => eprintln!("⚠️ Failed to apply feedback history retention: {}", e),
+                }
+            });
+
             // 在应用启动时加载保存的窗口尺寸
             let app_handle = app.handle().clone();
             thread::spawn(move || {
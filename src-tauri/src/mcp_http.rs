@@ -0,0 +1,229 @@
+use anyhow::Result;
+use axum::extract::{Query, Request, State};
+use axum::http::{header, HeaderMap, StatusCode};
+use axum::middleware::{self, Next};
+use axum::response::sse::{Event, KeepAlive, Sse};
+use axum::response::{IntoResponse, Response};
+use axum::routing::{get, post};
+use axum::{Json, Router};
+use rmcp::model::{ClientJsonRpcMessage, ServerJsonRpcMessage};
+use rmcp::service::RoleServer;
+use rmcp::transport::streamable_http_server::session::local::LocalSessionManager;
+use rmcp::transport::streamable_http_server::{StreamableHttpServerConfig, StreamableHttpService};
+use rmcp::transport::Transport;
+use rmcp::ServiceExt;
+use serde::Deserialize;
+use serde_json::json;
+use std::collections::HashMap;
+use std::convert::Infallible;
+use std::future::Future;
+use std::sync::{Arc, Mutex};
+use tokio::net::TcpListener;
+use tokio::sync::mpsc::{UnboundedReceiver, UnboundedSender};
+use tokio_stream::wrappers::UnboundedReceiverStream;
+use tokio_stream::StreamExt;
+use tokio_util::sync::CancellationToken;
+
+use crate::mcp_server::{LocalMcpServer, SUPPORTED_PROTOCOL_VERSIONS};
+
+const PROTOCOL_VERSION_HEADER: &str = "mcp-protocol-version";
+const DEFAULT_HTTP_PORT: u16 = 7823;
+
+// 旧版 HTTP+SSE 传输共享状态
+struct SseState {
+    server: Arc<LocalMcpServer>,
+    shutdown: CancellationToken,
+    // 会话 id -> 向该会话投递客户端消息的通道
+    sessions: Mutex<HashMap<String, UnboundedSender<ClientJsonRpcMessage>>>,
+}
+
+#[derive(Deserialize)]
+struct MessagesQuery {
+    #[serde(rename = "sessionId")]
+    session_id: String,
+}
+
+// 获取 HTTP 传输端口 (MCP_HTTP_PORT 环境变量，默认 7823)
+pub fn get_http_port() -> u16 {
+    std::env::var("MCP_HTTP_PORT")
+        .ok()
+        .and_then(|port| port.trim().parse().ok())
+        .unwrap_or(DEFAULT_HTTP_PORT)
+}
+
+pub fn endpoint_url(port: u16) -> String {
+    format!("http://127.0.0.1:{}/mcp", port)
+}
+
+/// 在已绑定的本地端口上提供 MCP streamable HTTP 传输 (`/mcp`)，并兼容旧版 HTTP+SSE 传输 (`/sse` + `/messages`)
+pub async fn serve(server: Arc<LocalMcpServer>, listener: TcpListener, shutdown: CancellationToken) -> Result<()> {
+    let session_server = server.clone();
+    let streamable_http = StreamableHttpService::new(
+        move || Ok(session_server.with_session(&uuid::Uuid::new_v4().to_string())),
+        Arc::new(LocalSessionManager::default()),
+        StreamableHttpServerConfig { cancellation_token: shutdown.child_token(), ..Default::default() },
+    );
+
+    let state = Arc::new(SseState {
+        server,
+        shutdown: shutdown.clone(),
+        sessions: Mutex::new(HashMap::new()),
+    });
+
+    let router = Router::new()
+        .route_service("/mcp", streamable_http)
+        .route("/sse", get(handle_sse))
+        .route("/messages", post(handle_messages))
+        .with_state(state)
+        .layer(middleware::from_fn(validate_headers));
+
+    eprintln!("🌐 MCP HTTP transport listening on {}", listener.local_addr()?);
+    axum::serve(listener, router)
+        .with_graceful_shutdown(shutdown.cancelled_owned())
+        .await?;
+    eprintln!("🌐 MCP HTTP transport stopped");
+    Ok(())
+}
+
+// 只接受来自本机页面的请求，防止 DNS 重绑定攻击
+fn is_origin_allowed(headers: &HeaderMap) -> bool {
+    let Some(origin) = headers.get(header::ORIGIN).and_then(|v| v.to_str().ok()) else {
+        return true;
+    };
+    let host = origin
+        .split("://")
+        .nth(1)
+        .unwrap_or(origin)
+        .split('/')
+        .next()
+        .unwrap_or("");
+    let host = host.rsplit_once(':').map(|(h, _)| h).unwrap_or(host);
+    matches!(host, "localhost" | "127.0.0.1" | "[::1]" | "tauri.localhost")
+}
+
+fn error_response(status: StatusCode, code: i64, message: &str) -> Response {
+    (status, Json(json!({
+        "jsonrpc": "2.0", "id": null, "error": { "code": code, "message": message }
+    })))
+        .into_response()
+}
+
+// 校验 Origin 和 MCP-Protocol-Version 请求头
+async fn validate_headers(request: Request, next: Next) -> Response {
+    if !is_origin_allowed(request.headers()) {
+        return error_response(StatusCode::FORBIDDEN, -32600, "Origin not allowed");
+    }
+    if let Some(version) = request.headers().get(PROTOCOL_VERSION_HEADER).and_then(|v| v.to_str().ok()) {
+        if !SUPPORTED_PROTOCOL_VERSIONS.contains(&version) {
+            return error_response(StatusCode::BAD_REQUEST, -32600, &format!("Unsupported protocol version: {}", version));
+        }
+    }
+    next.run(request).await
+}
+
+// 旧版 HTTP+SSE 传输：POST /messages 收到的消息经通道交给 rmcp 服务，服务发出的消息经 SSE 流推送
+struct SseTransport {
+    incoming: UnboundedReceiver<ClientJsonRpcMessage>,
+    outgoing: UnboundedSender<ServerJsonRpcMessage>,
+}
+
+impl Transport<RoleServer> for SseTransport {
+    type Error = std::io::Error;
+
+    fn send(&mut self, item: ServerJsonRpcMessage) -> impl Future<Output = Result<(), Self::Error>> + Send + 'static {
+        let result = self
+            .outgoing
+            .send(item)
+            .map_err(|_| std::io::Error::new(std::io::ErrorKind::BrokenPipe, "SSE stream closed"));
+        std::future::ready(result)
+    }
+
+    fn receive(&mut self) -> impl Future<Output = Option<ClientJsonRpcMessage>> + Send {
+        self.incoming.recv()
+    }
+
+    async fn close(&mut self) -> Result<(), Self::Error> {
+        self.incoming.close();
+        Ok(())
+    }
+}
+
+// SSE 流被丢弃 (客户端断开连接) 时移除会话并停止该会话的处理器
+struct SseSessionGuard {
+    state: Arc<SseState>,
+    session_id: String,
+    ct: CancellationToken,
+}
+
+impl Drop for SseSessionGuard {
+    fn drop(&mut self) {
+        if let Ok(mut sessions) = self.state.sessions.lock() {
+            sessions.remove(&self.session_id);
+        }
+        self.ct.cancel();
+    }
+}
+
+// 建立 SSE 流并通过 endpoint 事件告知消息提交地址
+async fn handle_sse(State(state): State<Arc<SseState>>) -> Response {
+    let session_id = uuid::Uuid::new_v4().to_string();
+    let (incoming_tx, incoming) = tokio::sync::mpsc::unbounded_channel();
+    let (outgoing, outgoing_rx) = tokio::sync::mpsc::unbounded_channel::<ServerJsonRpcMessage>();
+    if let Ok(mut sessions) = state.sessions.lock() {
+        sessions.insert(session_id.clone(), incoming_tx);
+    }
+    eprintln!("🌐 New MCP SSE session: {}", session_id);
+
+    let handler = state.server.with_session(&session_id);
+    let transport = SseTransport { incoming, outgoing };
+    let ct = state.shutdown.child_token();
+    let guard = SseSessionGuard { state: state.clone(), session_id: session_id.clone(), ct: ct.clone() };
+    let cleanup_state = state.clone();
+    let cleanup_id = session_id.clone();
+    tokio::spawn(async move {
+        match handler.serve_with_ct(transport, ct.clone()).await {
+            Ok(service) => {
+                let _ = service.waiting().await;
+            }
+            // 初始化完成前客户端就断开了连接
+            Err(_) if ct.is_cancelled() => {}
+            Err(e) => eprintln!("❌ MCP SSE session {} failed to initialize: {}", cleanup_id, e),
+        }
+        if let Ok(mut sessions) = cleanup_state.sessions.lock() {
+            sessions.remove(&cleanup_id);
+        }
+        eprintln!("🌐 MCP SSE session closed: {}", cleanup_id);
+    });
+
+    let endpoint = Event::default()
+        .event("endpoint")
+        .data(format!("/messages?sessionId={}", session_id));
+    let stream = tokio_stream::once(Ok::<_, Infallible>(endpoint)).chain(
+        UnboundedReceiverStream::new(outgoing_rx).map(move |message| {
+            // 流持有 guard，响应结束时随流一起释放
+            let _ = &guard;
+            Ok(Event::default().event("message").data(serde_json::to_string(&message).unwrap_or_default()))
+        }),
+    );
+    Sse::new(stream).keep_alive(KeepAlive::default()).into_response()
+}
+
+async fn handle_messages(
+    State(state): State<Arc<SseState>>,
+    Query(query): Query<MessagesQuery>,
+    Json(message): Json<ClientJsonRpcMessage>,
+) -> Response {
+    let tx = state.sessions.lock().ok().and_then(|sessions| sessions.get(&query.session_id).cloned());
+    let Some(tx) = tx else {
+        return StatusCode::NOT_FOUND.into_response();
+    };
+
+    // 会话已结束 (SSE 流断开)，清理会话
+    if tx.send(message).is_err() {
+        if let Ok(mut sessions) = state.sessions.lock() {
+            sessions.remove(&query.session_id);
+        }
+        return StatusCode::NOT_FOUND.into_response();
+    }
+    StatusCode::ACCEPTED.into_response()
+}
@@ -4,12 +4,25 @@ use std::collections::HashMap;
 use std::sync::{Arc, Mutex};
 use std::fs;
 use std::path::PathBuf;
-use tauri::Emitter;
 
 use chrono;
 use std::time::Duration;
 use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
 use sysinfo::System;
+use tokio_util::sync::CancellationToken;
+use rmcp::model::{self, AnnotateAble};
+use rmcp::service::{NotificationContext, Peer, RequestContext, RoleServer};
+use rmcp::{ErrorData as McpError, ServerHandler, ServiceExt};
+
+use crate::attachments::{self, AttachmentLimits, FeedbackAttachment};
+use crate::feedback_ipc::{self, FeedbackResponse, IpcMessage};
+use crate::file_lister;
+use crate::file_reader;
+use crate::file_searcher;
+use crate::file_writer;
+use crate::history::{self, FeedbackHistory, HistoryEntry};
+use crate::sandbox::{self, Sandbox};
 
 // AI来源信息结构
 #[derive(Debug, Clone, PartialEq)]
@@ -46,11 +59,9 @@ impl AiSource {
     }
 }
 
-// 共享存储路径 (用于 stdio 模式)
+// 共享存储路径 (用于 stdio 模式)，与 GUI 使用同一个目录
 fn get_shared_storage_dir() -> PathBuf {
-    let mut path = std::env::temp_dir();
-    path.push("mcp_manager");
-    path
+    crate::get_shared_storage_dir()
 }
 
 fn get_feedback_request_path(session_id: &str) -> PathBuf {
@@ -69,9 +80,9 @@ fn get_feedback_response_path(session_id: &str) -> PathBuf {
     path
 }
 
-// 写入反馈请求到文件系统
-fn write_feedback_request(session_id: &str, ai_response: &str, context: &str, raw_mcp_source: &str, ai_source: &AiSource) -> Result<()> {
-    let request_data = json!({
+// 构造反馈请求 (socket 消息和请求文件使用相同内容)
+fn build_feedback_request(session_id: &str, ai_response: &str, context: &str, raw_mcp_source: &str, ai_source: &AiSource) -> Value {
+    json!({
         "sessionId": session_id,
         "aiResponse": ai_response,
         "context": context,
@@ -79,27 +90,55 @@ fn write_feedback_request(session_id: &str, ai_response: &str, context: &str, ra
         "status": "pending",
         "aiSource": raw_mcp_source, // 使用原始的 MCP_SOURCE 值
         "aiSourceDisplay": ai_source.to_display_name()
-    });
+    })
+}
 
+// 写入反馈请求到文件系统
+fn write_feedback_request(request_data: &Value) -> Result<()> {
+    let session_id = request_data["sessionId"].as_str().unwrap_or_default();
     let path = get_feedback_request_path(session_id);
-    fs::write(&path, serde_json::to_string_pretty(&request_data)?)?;
+    fs::write(&path, serde_json::to_string_pretty(request_data)?)?;
     Ok(())
 }
 
 // 读取反馈响应
-fn read_feedback_response(session_id: &str) -> Option<String> {
+fn read_feedback_response(session_id: &str) -> Option<FeedbackResponse> {
     let path = get_feedback_response_path(session_id);
     if let Ok(content) = fs::read_to_string(&path) {
-        if let Ok(response_data) = serde_json::from_str::<Value>(&content) {
-            if let Some(feedback) = response_data["feedback"].as_str() {
-                fs::remove_file(&path).ok(); // Clean up response file
-                return Some(feedback.to_string());
-            }
+        if let Ok(response) = serde_json::from_str::<FeedbackResponse>(&content) {
+            fs::remove_file(&path).ok(); // Clean up response file
+            return Some(response);
         }
     }
     None
 }
 
+/// 请求目录中尚未回复的反馈请求 (通过文件 IPC 提交)
+pub fn list_pending_request_files() -> Vec<Value> {
+    let requests_dir = get_feedback_request_path("").parent().unwrap().to_path_buf();
+    let mut requests = Vec::new();
+    if let Ok(entries) = fs::read_dir(&requests_dir) {
+        for entry in entries.flatten() {
+            let path = entry.path();
+            if path.extension().and_then(|s| s.to_str()) != Some("json") {
+                continue;
+            }
+            let Some(request_data) = fs::read_to_string(&path)
+                .ok()
+                .and_then(|content| serde_json::from_str::<Value>(&content).ok())
+            else {
+                continue;
+            };
+            // 已提交反馈的请求不再视为待处理
+            if request_data.get("processed").and_then(|v| v.as_bool()).unwrap_or(false) {
+                continue;
+            }
+            requests.push(request_data);
+        }
+    }
+    requests
+}
+
 // 检查会话是否被取消
 fn is_session_cancelled(session_id: &str) -> bool {
     let path = get_feedback_request_path(session_id);
@@ -164,6 +203,23 @@ async fn start_gui_application() -> Result<()> {
     }
 }
 
+/// 无界面模式 (`--headless` 参数或 MCP_HEADLESS 环境变量)：不启动 GUI，反馈通过命令行回复
+pub fn is_headless() -> bool {
+    std::env::args().any(|arg| arg == "--headless")
+        || std::env::var("MCP_HEADLESS").is_ok_and(|value| matches!(value.trim(), "1" | "true" | "yes"))
+}
+
+// 发起反馈请求前确保 GUI 应用正在运行（仅在 MCP 模式下需要检查，无界面模式下由命令行回复）
+async fn prepare_feedback_gui(app: Option<&tauri::AppHandle>) {
+    if app.is_none() && !is_headless() {
+        eprintln!("🔍 Checking if GUI application is running...");
+        if let Err(e) = ensure_gui_running().await {
+            eprintln!("⚠️ Failed to ensure GUI is running: {}", e);
+            // 继续执行，即使 GUI 启动失败也要写入文件，让文件监听器处理
+        }
+    }
+}
+
 // 确保 GUI 应用正在运行
 async fn ensure_gui_running() -> Result<()> {
     if !is_gui_running() {
@@ -174,8 +230,6 @@ async fn ensure_gui_running() -> Result<()> {
 }
 
 
-use tokio::io::{stdin, stdout};
-
 // 获取 MCP 来源的智能函数，支持多种配置方式
 fn get_mcp_source_smart() -> String {
     // 优先级：
@@ -205,23 +259,475 @@ fn get_mcp_source_smart() -> String {
     "qoder-ai".to_string()
 }
 
+/// MCP 日志级别 (RFC 5424)
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Default)]
+pub enum LogLevel {
+    Debug,
+    #[default]
+    Info,
+    Notice,
+    Warning,
+    Error,
+    Critical,
+    Alert,
+    Emergency,
+}
+
+impl From<model::LoggingLevel> for LogLevel {
+    fn from(level: model::LoggingLevel) -> Self {
+        match level {
+            model::LoggingLevel::Debug => LogLevel::Debug,
+            model::LoggingLevel::Info => LogLevel::Info,
+            model::LoggingLevel::Notice => LogLevel::Notice,
+            model::LoggingLevel::Warning => LogLevel::Warning,
+            model::LoggingLevel::Error => LogLevel::Error,
+            model::LoggingLevel::Critical => LogLevel::Critical,
+            model::LoggingLevel::Alert => LogLevel::Alert,
+            model::LoggingLevel::Emergency => LogLevel::Emergency,
+        }
+    }
+}
+
+impl From<LogLevel> for model::LoggingLevel {
+    fn from(level: LogLevel) -> Self {
+        match level {
+            LogLevel::Debug => model::LoggingLevel::Debug,
+            LogLevel::Info => model::LoggingLevel::Info,
+            LogLevel::Notice => model::LoggingLevel::Notice,
+            LogLevel::Warning => model::LoggingLevel::Warning,
+            LogLevel::Error => model::LoggingLevel::Error,
+            LogLevel::Critical => model::LoggingLevel::Critical,
+            LogLevel::Alert => model::LoggingLevel::Alert,
+            LogLevel::Emergency => model::LoggingLevel::Emergency,
+        }
+    }
+}
+
+/// 支持的 MCP 协议版本，按从新到旧排列
+pub const SUPPORTED_PROTOCOL_VERSIONS: [&str; 3] = ["2025-06-18", "2025-03-26", "2024-11-05"];
+
+// 协商协议版本：客户端请求的版本受支持时使用该版本，否则回复服务器支持的最新版本
+fn negotiate_protocol_version(requested: &model::ProtocolVersion) -> model::ProtocolVersion {
+    match requested.to_string().as_str() {
+        "2025-03-26" => model::ProtocolVersion::V_2025_03_26,
+        "2024-11-05" => model::ProtocolVersion::V_2024_11_05,
+        _ => model::ProtocolVersion::V_2025_06_18,
+    }
+}
+
+/// 客户端会话 (一个 stdio 连接或一个 HTTP 会话)
+pub struct McpSession {
+    pub id: String,
+    log_level: Mutex<LogLevel>,
+    protocol_version: Mutex<String>,
+    client_capabilities: Mutex<model::ClientCapabilities>,
+    /// 客户端通过 `roots/list` 提供的根目录 (获取失败时为空)，首次调用访问文件的工具时获取，收到 `roots/list_changed` 后重新获取
+    client_roots: Mutex<Option<Vec<PathBuf>>>,
+    /// 没有任何根目录时是否使用当前工作目录 (仅 stdio 模式可开启)
+    cwd_fallback: bool,
+}
+
+impl McpSession {
+    pub fn new(id: &str) -> Self {
+        Self {
+            id: id.to_string(),
+            log_level: Mutex::new(LogLevel::default()),
+            // 未经 initialize 协商的会话按最旧的版本处理
+            protocol_version: Mutex::new(SUPPORTED_PROTOCOL_VERSIONS[SUPPORTED_PROTOCOL_VERSIONS.len() - 1].to_string()),
+            client_capabilities: Mutex::new(model::ClientCapabilities::default()),
+            client_roots: Mutex::new(None),
+            cwd_fallback: false,
+        }
+    }
+
+    pub fn with_cwd_fallback(mut self, cwd_fallback: bool) -> Self {
+        self.cwd_fallback = cwd_fallback;
+        self
+    }
+
+    pub fn protocol_version(&self) -> String {
+        self.protocol_version.lock().map(|version| version.clone()).unwrap_or_default()
+    }
+
+    fn set_negotiated(&self, protocol_version: &model::ProtocolVersion, client_capabilities: model::ClientCapabilities) {
+        if let Ok(mut version) = self.protocol_version.lock() {
+            *version = protocol_version.to_string();
+        }
+        if let Ok(mut capabilities) = self.client_capabilities.lock() {
+            *capabilities = client_capabilities;
+        }
+    }
+
+    // 协商的版本不早于 version；按在 SUPPORTED_PROTOCOL_VERSIONS 中的位置比较，不依赖版本字符串的格式
+    fn protocol_at_least(&self, version: &str) -> bool {
+        let index = |version: &str| SUPPORTED_PROTOCOL_VERSIONS.iter().position(|supported| *supported == version);
+        match (index(&self.protocol_version()), index(version)) {
+            (Some(current), Some(required)) => current <= required,
+            _ => false,
+        }
+    }
+
+    /// 工具注解 (annotations) 自 2025-03-26 起支持
+    pub fn supports_tool_annotations(&self) -> bool {
+        self.protocol_at_least("2025-03-26")
+    }
+
+    /// structuredContent / outputSchema 和 resource_link 内容块自 2025-06-18 起支持
+    pub fn supports_structured_output(&self) -> bool {
+        self.protocol_at_least("2025-06-18")
+    }
+
+    /// 客户端是否声明了 elicitation 能力 (2025-06-18 起)
+    pub fn supports_elicitation(&self) -> bool {
+        self.supports_structured_output()
+            && self.client_capabilities.lock().map(|caps| caps.elicitation.is_some()).unwrap_or(false)
+    }
+
+    /// 客户端是否声明了 roots 能力
+    pub fn supports_roots(&self) -> bool {
+        self.client_capabilities.lock().map(|caps| caps.roots.is_some()).unwrap_or(false)
+    }
+
+    /// 当前会话的文件沙箱，客户端支持 roots 时使用客户端提供的根目录
+    pub async fn sandbox(&self, peer: &Peer<RoleServer>) -> Sandbox {
+        if !self.supports_roots() {
+            return Sandbox::for_client(None, self.cwd_fallback);
+        }
+        if let Some(roots) = self.client_roots.lock().ok().and_then(|roots| roots.clone()) {
+            return Sandbox::for_client(Some(roots), self.cwd_fallback);
+        }
+
+        // 获取失败或超时时同样缓存空的根目录 (回退到配置的根目录)，避免之后每次调用都再等待 5 秒，
+        // 客户端发送 roots/list_changed 后重新获取
+        let roots = match tokio::time::timeout(Duration::from_secs(5), peer.list_roots()).await {
+            Ok(Ok(result)) => {
+                let roots: Vec<PathBuf> = result.roots.iter().filter_map(|root| sandbox::file_uri_to_path(&root.uri)).collect();
+                eprintln!("📂 [{}] Client workspace roots: {:?}", self.id, roots);
+                roots
+            }
+            Ok(Err(e)) => {
+                eprintln!("⚠️ [{}] Failed to list client roots: {}", self.id, e);
+                Vec::new()
+            }
+            Err(_) => {
+                eprintln!("⚠️ [{}] Client did not answer roots/list in time", self.id);
+                Vec::new()
+            }
+        };
+        if let Ok(mut cached) = self.client_roots.lock() {
+            *cached = Some(roots.clone());
+        }
+        Sandbox::for_client(Some(roots), self.cwd_fallback)
+    }
+
+    fn clear_client_roots(&self) {
+        if let Ok(mut roots) = self.client_roots.lock() {
+            *roots = None;
+        }
+    }
+
+    pub fn log_level(&self) -> LogLevel {
+        self.log_level.lock().map(|level| *level).unwrap_or_default()
+    }
+
+    pub fn set_log_level(&self, level: LogLevel) {
+        if let Ok(mut log_level) = self.log_level.lock() {
+            *log_level = level;
+        }
+    }
+}
+
+/// 工具执行上下文，每个 `tools/call` 请求一份
+#[derive(Clone, Default)]
+pub struct ToolContext {
+    cancellation_token: CancellationToken,
+    tool_name: String,
+    progress_token: Option<model::ProgressToken>,
+    peer: Option<Peer<RoleServer>>,
+    log_level: LogLevel,
+    session: Option<Arc<McpSession>>,
+    /// 首次解析路径时才获取，不访问文件的工具不会向客户端请求 `roots/list`
+    sandbox: Arc<tokio::sync::OnceCell<Sandbox>>,
+}
+
+impl ToolContext {
+    pub fn new(cancellation_token: CancellationToken) -> Self {
+        Self { cancellation_token, ..Default::default() }
+    }
+
+    /// 绑定客户端连接，之后可以向客户端推送进度和日志
+    pub fn with_peer(mut self, tool_name: &str, peer: Peer<RoleServer>, progress_token: Option<model::ProgressToken>, log_level: LogLevel) -> Self {
+        self.tool_name = tool_name.to_string();
+        self.peer = Some(peer);
+        self.progress_token = progress_token;
+        self.log_level = log_level;
+        self
+    }
+
+    /// 客户端支持 elicitation 时返回用于发送 `elicitation/create` 的 peer
+    pub fn elicitation_peer(&self) -> Option<&Peer<RoleServer>> {
+        self.session.as_ref().filter(|session| session.supports_elicitation())?;
+        self.peer.as_ref()
+    }
+
+    /// 绑定客户端会话，文件沙箱按会话的根目录创建
+    pub fn with_session(mut self, session: Arc<McpSession>) -> Self {
+        self.session = Some(session);
+        self
+    }
+
+    /// 在工作区沙箱中解析工具参数中的路径，越界访问会被记录并作为工具错误返回
+    pub async fn resolve_path(&self, path: &str) -> Result<PathBuf> {
+        match self.sandbox().await.resolve(path) {
+            Ok(resolved) => Ok(resolved),
+            Err(e) => {
+                eprintln!("🚫 Tool '{}' blocked: {}", self.tool_name, e);
+                self.log(LogLevel::Warning, &e.to_string()).await;
+                Err(e.into())
+            }
+        }
+    }
+
+    /// 当前会话的文件沙箱，同一次工具调用中只获取一次
+    pub async fn sandbox(&self) -> &Sandbox {
+        self.sandbox
+            .get_or_init(|| async {
+                match (&self.session, &self.peer) {
+                    (Some(session), Some(peer)) => session.sandbox(peer).await,
+                    _ => Sandbox::default(),
+                }
+            })
+            .await
+    }
+
+    /// 工具结果中显示的路径，位于工作区根目录内时为相对路径
+    pub async fn display_path(&self, path: &std::path::Path) -> String {
+        self.sandbox().await.display_path(path)
+    }
+
+    /// 发送 `notifications/progress`，仅当客户端在 `_meta` 中提供了 progressToken 时生效
+    pub async fn report_progress(&self, progress: f64, total: Option<f64>, message: Option<&str>) {
+        let (Some(peer), Some(progress_token)) = (&self.peer, &self.progress_token) else {
+            return;
+        };
+
+        let params = model::ProgressNotificationParam {
+            progress_token: progress_token.clone(),
+            progress,
+            total,
+            message: message.map(|m| m.to_string()),
+        };
+        if let Err(e) = peer.notify_progress(params).await {
+            eprintln!("⚠️ Failed to send progress notification: {}", e);
+        }
+    }
+
+    /// 发送 `notifications/message` 日志，低于客户端设置级别的日志会被忽略
+    pub async fn log(&self, level: LogLevel, message: &str) {
+        let Some(peer) = &self.peer else {
+            return;
+        };
+        if level < self.log_level {
+            return;
+        }
+
+        let params = model::LoggingMessageNotificationParam {
+            level: level.into(),
+            logger: Some(self.tool_name.clone()),
+            data: json!(message),
+        };
+        if let Err(e) = peer.notify_logging_message(params).await {
+            eprintln!("⚠️ Failed to send log notification: {}", e);
+        }
+    }
+
+    /// 请求的取消令牌，供阻塞任务检查是否已取消
+    pub fn cancellation_token(&self) -> CancellationToken {
+        self.cancellation_token.clone()
+    }
+
+    /// 等待请求被取消
+    pub async fn cancelled(&self) {
+        self.cancellation_token.cancelled().await
+    }
+}
+
+/// 工具调用的协议错误，以 JSON-RPC 错误 (-32602) 返回；其他错误视为工具执行失败，以 `isError` 结果返回
+#[derive(Debug)]
+pub enum ToolError {
+    NotFound(String),
+    InvalidParams(String),
+}
+
+impl std::fmt::Display for ToolError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ToolError::NotFound(name) => write!(f, "Tool '{}' not found", name),
+            ToolError::InvalidParams(message) => write!(f, "Invalid params: {}", message),
+        }
+    }
+}
+
+impl std::error::Error for ToolError {}
+
+/// 工具结果中的内容块
+#[derive(Debug, Clone)]
+pub enum ToolContent {
+    Text(String),
+    Image { data: String, mime_type: String },
+    Resource { uri: String, mime_type: String, text: Option<String>, blob: Option<String> },
+    ResourceLink { uri: String, name: String, description: Option<String>, mime_type: Option<String> },
+}
+
+impl ToolContent {
+    pub fn text(text: impl Into<String>) -> Self {
+        ToolContent::Text(text.into())
+    }
+
+    pub fn image(bytes: &[u8], mime_type: &str) -> Self {
+        use base64::Engine;
+        ToolContent::Image {
+            data: base64::engine::general_purpose::STANDARD.encode(bytes),
+            mime_type: mime_type.to_string(),
+        }
+    }
+
+    /// 指向本地文件的 resource_link，客户端可以按需读取文件而不必把内容嵌入结果
+    pub fn file_link(path: &std::path::Path, name: impl Into<String>, description: Option<String>) -> Self {
+        ToolContent::ResourceLink { uri: sandbox::path_to_file_uri(path), name: name.into(), description, mime_type: None }
+    }
+
+    // 不支持 resource_link 的客户端改为收到链接地址文本
+    fn into_content(self, resource_links: bool) -> model::Content {
+        match self {
+            ToolContent::Text(text) => model::Content::text(text),
+            ToolContent::Image { data, mime_type } => model::Content::image(data, mime_type),
+            ToolContent::Resource { uri, mime_type, text, blob } => model::Content::resource(match blob {
+                Some(blob) => model::ResourceContents::BlobResourceContents { uri, mime_type: Some(mime_type), blob, meta: None },
+                None => model::ResourceContents::TextResourceContents {
+                    uri,
+                    mime_type: Some(mime_type),
+                    text: text.unwrap_or_default(),
+                    meta: None,
+                },
+            }),
+            ToolContent::ResourceLink { uri, .. } if !resource_links => model::Content::text(uri),
+            ToolContent::ResourceLink { uri, name, description, mime_type } => {
+                model::Content::resource_link(model::RawResource { description, mime_type, ..model::RawResource::new(uri, name) })
+            }
+        }
+    }
+}
+
+/// 工具执行结果，可包含多个内容块和结构化输出
+#[derive(Debug, Clone, Default)]
+pub struct ToolResult {
+    pub content: Vec<ToolContent>,
+    pub structured_content: Option<Value>,
+    pub is_error: bool,
+}
+
+impl ToolResult {
+    pub fn text(text: impl Into<String>) -> Self {
+        Self { content: vec![ToolContent::text(text)], ..Default::default() }
+    }
+
+    /// 结构化结果，同时附带序列化后的文本以兼容不支持 structuredContent 的客户端
+    pub fn structured(value: Value) -> Self {
+        let text = serde_json::to_string_pretty(&value).unwrap_or_default();
+        Self { content: vec![ToolContent::Text(text)], structured_content: Some(value), is_error: false }
+    }
+
+    pub fn error(text: impl Into<String>) -> Self {
+        Self { content: vec![ToolContent::text(text)], structured_content: None, is_error: true }
+    }
+
+    pub fn with_content(mut self, content: ToolContent) -> Self {
+        self.content.push(content);
+        self
+    }
+
+    pub fn with_structured(mut self, value: Value) -> Self {
+        self.structured_content = Some(value);
+        self
+    }
+
+    /// 按会话协商的协议版本转换为 `CallToolResult`：2025-06-18 之前的版本不支持 structuredContent 和 resource_link
+    pub fn into_call_tool_result(self, session: &McpSession) -> model::CallToolResult {
+        let structured_output = session.supports_structured_output();
+        model::CallToolResult {
+            content: self.content.into_iter().map(|content| content.into_content(structured_output)).collect(),
+            structured_content: self.structured_content.filter(|_| structured_output),
+            is_error: self.is_error.then_some(true),
+            meta: None,
+        }
+    }
+}
+
 /// MCP 工具特征定义 (异步)
 #[async_trait]
 pub trait McpTool: Send + Sync {
     fn name(&self) -> &str;
     fn description(&self) -> &str;
     fn input_schema(&self) -> Value;
-    async fn execute(&self, params: Value) -> Result<Value>;
-    async fn execute_with_app(&self, params: Value, _app: Option<&tauri::AppHandle>) -> Result<Value> {
-        self.execute(params).await
+    /// 结构化输出的 JSON Schema，返回 structuredContent 的工具应当提供
+    fn output_schema(&self) -> Option<Value> { None }
+    /// 工具注解，如 readOnlyHint、destructiveHint
+    fn annotations(&self) -> Option<Value> { None }
+    async fn execute(&self, params: Value, ctx: &ToolContext) -> Result<ToolResult>;
+    async fn execute_with_app(&self, params: Value, _app: Option<&tauri::AppHandle>, ctx: &ToolContext) -> Result<ToolResult> {
+        self.execute(params, ctx).await
+    }
+}
+
+/// MCP 资源特征定义 (异步)
+#[async_trait]
+pub trait McpResource: Send + Sync {
+    fn uri(&self) -> &str;
+    fn name(&self) -> &str;
+    fn description(&self) -> &str;
+    fn mime_type(&self) -> &str { "text/plain" }
+    async fn read(&self) -> Result<String>;
+}
+
+/// 提示词参数定义
+#[derive(Debug, Clone, Deserialize)]
+pub struct PromptArgument {
+    pub name: String,
+    #[serde(default)]
+    pub description: String,
+    #[serde(default)]
+    pub required: bool,
+}
+
+/// MCP 提示词特征定义 (异步)
+#[async_trait]
+pub trait McpPrompt: Send + Sync {
+    fn name(&self) -> &str;
+    fn description(&self) -> &str;
+    fn arguments(&self) -> Vec<PromptArgument> { Vec::new() }
+    async fn get(&self, arguments: &Value) -> Result<Vec<model::PromptMessage>>;
+}
+
+// JSON Schema 必须是对象，其他值按空对象处理
+fn as_json_object(value: Value) -> model::JsonObject {
+    match value {
+        Value::Object(object) => object,
+        _ => model::JsonObject::new(),
     }
 }
 
-/// 本地 MCP 服务器
+/// 本地 MCP 服务器，实现 rmcp 的 `ServerHandler`
+///
+/// 每个客户端连接使用一份克隆 (见 `with_session`)，工具、资源和提示词注册表在克隆之间共享
+#[derive(Clone)]
 pub struct LocalMcpServer {
-    tools: Arc<Mutex<HashMap<String, Box<dyn McpTool>>>>,
+    tools: Arc<Mutex<HashMap<String, Arc<dyn McpTool>>>>,
+    resources: Arc<Mutex<HashMap<String, Arc<dyn McpResource>>>>,
+    prompts: Arc<Mutex<HashMap<String, Arc<dyn McpPrompt>>>>,
     server_info: ServerInfo,
     app_handle: Option<tauri::AppHandle>,
+    session: Arc<McpSession>,
 }
 
 #[derive(Clone)]
@@ -235,44 +741,86 @@ impl LocalMcpServer {
     pub fn new() -> Self {
         let mut server = Self {
             tools: Arc::new(Mutex::new(HashMap::new())),
+            resources: Arc::new(Mutex::new(HashMap::new())),
+            prompts: Arc::new(Mutex::new(HashMap::new())),
             server_info: ServerInfo {
                 name: "Local MCP Tools".to_string(),
                 version: "1.0.0".to_string(),
                 description: "Local tools for AI assistants".to_string(),
             },
             app_handle: None,
+            session: Arc::new(McpSession::new("local")),
         };
 
         eprintln!("📋 Registering built-in tools...");
         server.register_tool(Box::new(FileReadTool));
+        server.register_tool(Box::new(FileWriteTool));
+        server.register_tool(Box::new(FileEditTool));
+        server.register_tool(Box::new(ListDirectoryTool));
+        server.register_tool(Box::new(SearchFilesTool));
         server.register_tool(Box::new(SystemInfoTool));
         server.register_tool(Box::new(FeedbackTool));
         eprintln!("🎯 All tools registered successfully");
 
+        eprintln!("📋 Registering built-in resources...");
+        server.register_resource(Arc::new(PendingFeedbackResource));
+        for path in get_workspace_resource_files() {
+            server.register_resource(Arc::new(WorkspaceFileResource::new(path)));
+        }
+
+        eprintln!("📋 Registering prompts...");
+        for prompt in TemplatePrompt::built_in() {
+            server.register_prompt(Arc::new(prompt));
+        }
+        // 用户提示词目录中的同名提示词会覆盖内置提示词
+        for prompt in TemplatePrompt::load_from_dir(&get_prompts_dir()) {
+            server.register_prompt(Arc::new(prompt));
+        }
+
         server
     }
 
+    /// 为新的客户端连接创建处理器，共享注册表但拥有独立的会话状态
+    pub fn with_session(&self, session_id: &str) -> Self {
+        Self { session: Arc::new(McpSession::new(session_id)), ..self.clone() }
+    }
+
     pub fn set_app_handle(&mut self, app_handle: tauri::AppHandle) {
         self.app_handle = Some(app_handle);
     }
 
     pub fn register_tool(&mut self, tool: Box<dyn McpTool>) {
         if let Ok(mut tools) = self.tools.lock() {
-            tools.insert(tool.name().to_string(), tool);
+            tools.insert(tool.name().to_string(), Arc::from(tool));
+        }
+    }
+
+    pub fn register_resource(&mut self, resource: Arc<dyn McpResource>) {
+        if let Ok(mut resources) = self.resources.lock() {
+            resources.insert(resource.uri().to_string(), resource);
+        }
+    }
+
+    pub fn register_prompt(&mut self, prompt: Arc<dyn McpPrompt>) {
+        if let Ok(mut prompts) = self.prompts.lock() {
+            prompts.insert(prompt.name().to_string(), prompt);
         }
     }
 
-    pub fn list_tools(&self) -> Vec<Value> {
+    pub fn list_tools(&self) -> Vec<model::Tool> {
         match self.tools.lock() {
             Ok(tools) => {
                 tools
                     .values()
                     .map(|tool| {
-                        json!({
-                            "name": tool.name(),
-                            "description": tool.description(),
-                            "inputSchema": tool.input_schema()
-                        })
+                        let mut definition = model::Tool::new(
+                            tool.name().to_string(),
+                            tool.description().to_string(),
+                            as_json_object(tool.input_schema()),
+                        );
+                        definition.output_schema = tool.output_schema().map(|schema| Arc::new(as_json_object(schema)));
+                        definition.annotations = tool.annotations().and_then(|annotations| serde_json::from_value(annotations).ok());
+                        definition
                     })
                     .collect()
             }
@@ -280,163 +828,834 @@ impl LocalMcpServer {
         }
     }
 
+    // 按会话协商的协议版本去掉客户端不支持的字段
+    fn list_tools_for_session(&self) -> Vec<model::Tool> {
+        let mut tools = self.list_tools();
+        for tool in tools.iter_mut() {
+            if !self.session.supports_structured_output() {
+                tool.output_schema = None;
+            }
+            if !self.session.supports_tool_annotations() {
+                tool.annotations = None;
+            }
+        }
+        tools
+    }
 
-    pub async fn execute_tool(&self, name: &str, params: Value) -> Result<Value> {
-        let tool = {
-            let tools = self.tools.lock().map_err(|_| anyhow::anyhow!("Failed to acquire tools lock"))?;
-            tools.get(name).map(|t| t.name().to_string())
+    pub fn list_resources(&self) -> Vec<model::Resource> {
+        match self.resources.lock() {
+            Ok(resources) => {
+                resources
+                    .values()
+                    .map(|resource| {
+                        model::RawResource {
+                            description: Some(resource.description().to_string()),
+                            mime_type: Some(resource.mime_type().to_string()),
+                            ..model::RawResource::new(resource.uri(), resource.name())
+                        }
+                        .no_annotation()
+                    })
+                    .collect()
+            }
+            Err(_) => Vec::new()
+        }
+    }
+
+    pub async fn read_resource(&self, uri: &str) -> Result<Option<model::ResourceContents>> {
+        let resource = {
+            let resources = self.resources.lock().map_err(|_| anyhow::anyhow!("Failed to acquire resources lock"))?;
+            resources.get(uri).cloned()
         };
 
-        if let Some(tool_name) = tool {
-            let tools = self.tools.lock().unwrap();
-            let tool_instance = tools.get(&tool_name).unwrap();
-            tool_instance.execute_with_app(params, self.app_handle.as_ref()).await
-        } else {
-            Err(anyhow::anyhow!("Tool '{}' not found", name))
+        match resource {
+            Some(resource) => {
+                let text = resource.read().await?;
+                Ok(Some(model::ResourceContents::TextResourceContents {
+                    uri: resource.uri().to_string(),
+                    mime_type: Some(resource.mime_type().to_string()),
+                    text,
+                    meta: None,
+                }))
+            }
+            None => Ok(None),
         }
     }
 
-    pub async fn start_stdio_server(&self) -> Result<()> {
-        eprintln!("Starting MCP server with stdio transport...");
-        self.run_simple_server().await
+    pub fn list_prompts(&self) -> Vec<model::Prompt> {
+        match self.prompts.lock() {
+            Ok(prompts) => {
+                prompts
+                    .values()
+                    .map(|prompt| {
+                        let arguments = prompt
+                            .arguments()
+                            .into_iter()
+                            .map(|arg| model::PromptArgument {
+                                name: arg.name,
+                                title: None,
+                                description: Some(arg.description),
+                                required: Some(arg.required),
+                            })
+                            .collect();
+                        model::Prompt::new(prompt.name(), Some(prompt.description()), Some(arguments))
+                    })
+                    .collect()
+            }
+            Err(_) => Vec::new()
+        }
     }
 
-    async fn run_simple_server(&self) -> Result<()> {
-        use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+    pub async fn get_prompt(&self, name: &str, arguments: &Value) -> Result<Option<model::GetPromptResult>> {
+        let prompt = {
+            let prompts = self.prompts.lock().map_err(|_| anyhow::anyhow!("Failed to acquire prompts lock"))?;
+            prompts.get(name).cloned()
+        };
 
-        eprintln!("MCP Server: Starting stdio transport");
+        match prompt {
+            Some(prompt) => {
+                let messages = prompt.get(arguments).await?;
+                Ok(Some(model::GetPromptResult {
+                    description: Some(prompt.description().to_string()),
+                    messages,
+                }))
+            }
+            None => Ok(None),
+        }
+    }
 
-        let stdin = stdin();
-        let mut stdout = stdout();
-        let mut reader = BufReader::new(stdin);
-        let mut line = String::new();
+    pub async fn execute_tool(&self, name: &str, params: Value, ctx: &ToolContext) -> Result<ToolResult> {
+        // 先克隆出工具实例再执行，避免在等待期间持有锁而阻塞其他请求
+        let tool = {
+            let tools = self.tools.lock().map_err(|_| anyhow::anyhow!("Failed to acquire tools lock"))?;
+            tools.get(name).cloned()
+        };
 
-        loop {
-            line.clear();
-            match reader.read_line(&mut line).await {
-                Ok(0) => {
-                    eprintln!("MCP Server: EOF received, shutting down");
-                    break;
-                }
-                Ok(_) => {
-                    if line.trim().is_empty() {
-                        continue;
-                    }
+        let Some(tool_instance) = tool else {
+            return Err(ToolError::NotFound(name.to_string()).into());
+        };
 
-                    match serde_json::from_str::<Value>(&line) {
-                        Ok(request) => {
-                            let response = self.handle_request(request).await;
-                            if !response.is_null() {
-                                if let Ok(response_str) = serde_json::to_string(&response) {
-                                    let _ = stdout.write_all(response_str.as_bytes()).await;
-                                    let _ = stdout.write_all(b"\n").await;
-                                    let _ = stdout.flush().await;
-                                }
-                            }
-                        }
-                        Err(e) => {
-                            eprintln!("MCP Server: Error parsing JSON: {} - Input: {}", e, line.trim());
-                            let error_response = json!({
-                                "jsonrpc": "2.0",
-                                "id": null,
-                                "error": { 
-                                    "code": -32700, 
-                                    "message": format!("Parse error: {}", e)
-                                }
-                            });
-                            if let Ok(error_str) = serde_json::to_string(&error_response) {
-                                let _ = stdout.write_all(error_str.as_bytes()).await;
-                                let _ = stdout.write_all(b"\n").await;
-                                let _ = stdout.flush().await;
-                            }
-                        }
-                    }
-                }
-                Err(e) => {
-                    eprintln!("MCP Server: Error reading from stdin: {}", e);
-                    break;
+        // 按 inputSchema 校验参数对象和必填字段
+        let params = match params {
+            Value::Null => json!({}),
+            Value::Object(_) => params,
+            _ => return Err(ToolError::InvalidParams("arguments must be an object".to_string()).into()),
+        };
+        if let Some(required) = tool_instance.input_schema()["required"].as_array() {
+            for field in required.iter().filter_map(|f| f.as_str()) {
+                if params.get(field).is_none_or(|v| v.is_null()) {
+                    return Err(ToolError::InvalidParams(format!("missing required argument '{}'", field)).into());
                 }
             }
         }
 
-        eprintln!("MCP Server: Shutting down");
+        tool_instance.execute_with_app(params, self.app_handle.as_ref(), ctx).await
+    }
+
+    pub async fn start_stdio_server(&self) -> Result<()> {
+        eprintln!("Starting MCP server with stdio transport...");
+        if is_headless() {
+            eprintln!("🖥️ Headless mode: the GUI will not be launched, answer feedback with `cc-custom-mcp feedback reply`");
+        }
+        // stdio 服务器由客户端在工作区中启动，可以选择把当前工作目录作为根目录
+        let session = McpSession::new("stdio").with_cwd_fallback(sandbox::is_cwd_fallback_enabled());
+        let handler = Self { session: Arc::new(session), ..self.clone() };
+        let service = handler.serve(rmcp::transport::stdio()).await?;
+        let reason = service.waiting().await?;
+        eprintln!("MCP Server: Shutting down ({:?})", reason);
         Ok(())
     }
+}
 
-    async fn handle_request(&self, request: Value) -> Value {
-        let method = request["method"].as_str().unwrap_or("");
-        let id = request["id"].clone();
+impl ServerHandler for LocalMcpServer {
+    fn get_info(&self) -> model::ServerInfo {
+        model::ServerInfo {
+            protocol_version: model::ProtocolVersion::V_2025_06_18,
+            capabilities: model::ServerCapabilities::builder()
+                .enable_tools()
+                .enable_resources()
+                .enable_prompts()
+                .enable_logging()
+                .build(),
+            server_info: model::Implementation {
+                name: self.server_info.name.clone(),
+                version: self.server_info.version.clone(),
+                ..Default::default()
+            },
+            instructions: Some(self.server_info.description.clone()),
+        }
+    }
 
-        match method {
-            "initialize" => json!({
-                "jsonrpc": "2.0", "id": id, "result": {
-                    "protocolVersion": "2024-11-05",
-                    "capabilities": { "tools": {}, "resources": {}, "prompts": {}, "logging": {} },
-                    "serverInfo": { "name": self.server_info.name, "version": self.server_info.version },
-                    "instructions": self.server_info.description
-                }
-            }),
-            "ping" => json!({"jsonrpc": "2.0", "id": id, "result": {}}),
-            "notifications/initialized" => json!(null),
-            "resources/list" => json!({"jsonrpc": "2.0", "id": id, "result": {"resources": []}}),
-            "prompts/list" => json!({"jsonrpc": "2.0", "id": id, "result": {"prompts": []}}),
-            "logging/setLevel" => json!({"jsonrpc": "2.0", "id": id, "result": {}}),
-            "tools/list" => json!({
-                "jsonrpc": "2.0", "id": id, "result": { "tools": self.list_tools() }
-            }),
-            "tools/call" => {
-                let tool_name = request["params"]["name"].as_str().unwrap_or("");
-                let arguments = request["params"]["arguments"].clone();
-
-                eprintln!("🔧 Executing tool: {} with args: {}", tool_name, arguments);
-                
-                match self.execute_tool(tool_name, arguments).await {
-                    Ok(result) => {
-                        eprintln!("✅ Tool '{}' executed successfully", tool_name);
-                        json!({
-                            "jsonrpc": "2.0", "id": id, "result": {
-                                "content": [{"type": "text", "text": result.to_string()}]
-                            }
-                        })
-                    },
-                    Err(e) => {
-                        eprintln!("❌ Tool '{}' execution failed: {}", tool_name, e);
-                        json!({
-                            "jsonrpc": "2.0", "id": id, "error": {
-                                "code": -32603, 
-                                "message": format!("Tool '{}' execution failed: {}", tool_name, e)
-                            }
-                        })
-                    },
-                }
+    async fn initialize(
+        &self,
+        request: model::InitializeRequestParams,
+        context: RequestContext<RoleServer>,
+    ) -> Result<model::InitializeResult, McpError> {
+        let protocol_version = negotiate_protocol_version(&request.protocol_version);
+        self.session.set_negotiated(&protocol_version, request.capabilities.clone());
+        eprintln!("🤝 [{}] Client {} requested protocol {}, negotiated {} (elicitation: {})",
+                  self.session.id,
+                  request.client_info.name,
+                  request.protocol_version,
+                  protocol_version,
+                  self.session.supports_elicitation());
+
+        if context.peer.peer_info().is_none() {
+            context.peer.set_peer_info(request);
+        }
+        Ok(model::InitializeResult { protocol_version, ..self.get_info() })
+    }
+
+    async fn set_level(
+        &self,
+        request: model::SetLevelRequestParams,
+        _context: RequestContext<RoleServer>,
+    ) -> Result<(), McpError> {
+        self.session.set_log_level(request.level.into());
+        Ok(())
+    }
+
+    async fn list_resources(
+        &self,
+        _request: Option<model::PaginatedRequestParams>,
+        _context: RequestContext<RoleServer>,
+    ) -> Result<model::ListResourcesResult, McpError> {
+        Ok(model::ListResourcesResult { resources: self.list_resources(), ..Default::default() })
+    }
+
+    async fn read_resource(
+        &self,
+        request: model::ReadResourceRequestParams,
+        _context: RequestContext<RoleServer>,
+    ) -> Result<model::ReadResourceResult, McpError> {
+        let uri = request.uri;
+        match LocalMcpServer::read_resource(self, &uri).await {
+            Ok(Some(contents)) => Ok(model::ReadResourceResult { contents: vec![contents] }),
+            Ok(None) => Err(McpError::resource_not_found(format!("Resource '{}' not found", uri), Some(json!({ "uri": uri })))),
+            Err(e) => {
+                eprintln!("❌ Failed to read resource '{}': {}", uri, e);
+                Err(McpError::internal_error(format!("Failed to read resource '{}': {}", uri, e), None))
             }
-            _ => json!({
-                "jsonrpc": "2.0", "id": id, "error": {
-                    "code": -32601, "message": format!("Method '{}' not found", method)
-                }
-            }),
         }
     }
-}
 
-/// 文件读取工具
-pub struct FileReadTool;
+    async fn list_prompts(
+        &self,
+        _request: Option<model::PaginatedRequestParams>,
+        _context: RequestContext<RoleServer>,
+    ) -> Result<model::ListPromptsResult, McpError> {
+        Ok(model::ListPromptsResult { prompts: LocalMcpServer::list_prompts(self), ..Default::default() })
+    }
+
+    async fn get_prompt(
+        &self,
+        request: model::GetPromptRequestParams,
+        _context: RequestContext<RoleServer>,
+    ) -> Result<model::GetPromptResult, McpError> {
+        let arguments = request.arguments.map(Value::Object).unwrap_or(Value::Null);
+        match LocalMcpServer::get_prompt(self, &request.name, &arguments).await {
+            Ok(Some(result)) => Ok(result),
+            Ok(None) => Err(McpError::invalid_params(format!("Prompt '{}' not found", request.name), None)),
+            Err(e) => Err(McpError::invalid_params(format!("Failed to get prompt '{}': {}", request.name, e), None)),
+        }
+    }
+
+    async fn list_tools(
+        &self,
+        _request: Option<model::PaginatedRequestParams>,
+        _context: RequestContext<RoleServer>,
+    ) -> Result<model::ListToolsResult, McpError> {
+        Ok(model::ListToolsResult { tools: self.list_tools_for_session(), ..Default::default() })
+    }
+
+    async fn call_tool(
+        &self,
+        request: model::CallToolRequestParams,
+        context: RequestContext<RoleServer>,
+    ) -> Result<model::CallToolResult, McpError> {
+        let tool_name = request.name.to_string();
+        let arguments = request.arguments.map(Value::Object).unwrap_or(Value::Null);
+
+        eprintln!("🔧 Executing tool: {} with args: {}", tool_name, arguments);
+
+        let ctx = ToolContext::new(context.ct.clone()).with_peer(
+            &tool_name,
+            context.peer.clone(),
+            context.meta.get_progress_token(),
+            self.session.log_level(),
+        )
+        .with_session(self.session.clone());
+        match self.execute_tool(&tool_name, arguments, &ctx).await {
+            Ok(result) => {
+                eprintln!("✅ Tool '{}' executed successfully", tool_name);
+                Ok(result.into_call_tool_result(&self.session))
+            }
+            Err(e) => match e.downcast_ref::<ToolError>() {
+                Some(tool_error) => {
+                    eprintln!("❌ Invalid call to tool '{}': {}", tool_name, tool_error);
+                    Err(McpError::invalid_params(tool_error.to_string(), None))
+                }
+                // 工具执行失败作为结果返回，让模型能够读取错误并自行纠正
+                None => {
+                    eprintln!("❌ Tool '{}' execution failed: {}", tool_name, e);
+                    Ok(model::CallToolResult::error(vec![model::Content::text(format!(
+                        "Tool '{}' execution failed: {}",
+                        tool_name, e
+                    ))]))
+                }
+            },
+        }
+    }
+
+    async fn on_roots_list_changed(&self, _context: NotificationContext<RoleServer>) {
+        eprintln!("📂 [{}] Client workspace roots changed", self.session.id);
+        self.session.clear_client_roots();
+    }
+
+    async fn on_cancelled(&self, notification: model::CancelledNotificationParam, _context: NotificationContext<RoleServer>) {
+        eprintln!("🛑 Request {} cancelled by client: {}", notification.request_id,
+                  notification.reason.as_deref().unwrap_or("no reason given"));
+    }
+}
+
+/// 文件读取工具
+pub struct FileReadTool;
 
 #[async_trait]
 impl McpTool for FileReadTool {
     fn name(&self) -> &str { "file_read" }
-    fn description(&self) -> &str { "Read contents of a file" }
+    fn description(&self) -> &str {
+        "Read a file inside the workspace roots. Text is returned with line numbers in pages of at most `limit` lines \
+         and `max_bytes` bytes; use `offset` to continue. Images are returned as image content, other binary files are not read."
+    }
+    fn annotations(&self) -> Option<Value> { Some(json!({ "title": "Read file", "readOnlyHint": true })) }
+    fn input_schema(&self) -> Value {
+        json!({
+            "type": "object",
+            "properties": {
+                "path": { "type": "string", "description": "Path to the file to read, absolute or relative to the first workspace root" },
+                "offset": { "type": "integer", "minimum": 1, "description": "Line number to start reading from (1-based, default 1)" },
+                "limit": { "type": "integer", "minimum": 1, "description": format!("Maximum number of lines to return (default {})", file_reader::DEFAULT_LINE_LIMIT) },
+                "max_bytes": { "type": "integer", "minimum": 1, "description": format!("Maximum number of bytes of text to return (default and upper bound {})", file_reader::get_max_read_bytes()) },
+                "line_numbers": { "type": "boolean", "description": "Prefix each line with its line number (default true)" }
+            },
+            "required": ["path"]
+        })
+    }
+    async fn execute(&self, params: Value, ctx: &ToolContext) -> Result<ToolResult> {
+        let path = params["path"].as_str().ok_or_else(|| ToolError::InvalidParams("'path' must be a string".to_string()))?;
+        let positive = |name: &str| -> Result<Option<usize>> {
+            match &params[name] {
+                Value::Null => Ok(None),
+                value => value.as_u64().filter(|n| *n > 0).map(|n| Some(n as usize))
+                    .ok_or_else(|| ToolError::InvalidParams(format!("'{}' must be a positive integer", name)).into()),
+            }
+        };
+        let max_read_bytes = file_reader::get_max_read_bytes();
+        let options = file_reader::ReadOptions {
+            offset: positive("offset")?.unwrap_or(1),
+            limit: positive("limit")?.unwrap_or(file_reader::DEFAULT_LINE_LIMIT),
+            max_bytes: positive("max_bytes")?.unwrap_or(max_read_bytes).min(max_read_bytes),
+            line_numbers: params["line_numbers"].as_bool().unwrap_or(true),
+        };
+        let path = ctx.resolve_path(path).await?;
+
+        match file_reader::read_file(&path, &options).await? {
+            file_reader::FileContent::Image { bytes, mime_type } => {
+                Ok(ToolResult { content: vec![ToolContent::image(&bytes, mime_type)], ..Default::default() })
+            }
+            file_reader::FileContent::Binary { size } => Ok(ToolResult::text(format!(
+                "{} is a binary file ({} bytes) and cannot be displayed as text.", path.display(), size
+            ))),
+            file_reader::FileContent::Text(range) => {
+                // 分页和编码说明放在单独的内容块中，第一个内容块始终是文件内容本身
+                let mut notes = Vec::new();
+                if range.encoding != "UTF-8" {
+                    notes.push(format!("[Decoded from {}]", range.encoding));
+                }
+                match (range.next_offset, range.total_lines) {
+                    (Some(next), _) => {
+                        let reason = if range.truncated_by_bytes {
+                            format!("output limited to {} bytes", options.max_bytes)
+                        } else {
+                            format!("output limited to {} lines", options.limit)
+                        };
+                        notes.push(format!(
+                            "[Showing lines {}-{}; {}. Use offset={} to continue.]",
+                            range.first_line, range.last_line, reason, next
+                        ));
+                    }
+                    (None, Some(total)) if range.first_line == 0 && options.offset > 1 => {
+                        notes.push(format!("[Offset {} is past the end of the file ({} lines)]", options.offset, total));
+                    }
+                    _ => {}
+                }
+                let result = ToolResult::text(range.text);
+                Ok(if notes.is_empty() { result } else { result.with_content(ToolContent::text(notes.join("\n"))) })
+            }
+        }
+    }
+}
+
+/// 文件写入工具
+pub struct FileWriteTool;
+
+#[async_trait]
+impl McpTool for FileWriteTool {
+    fn name(&self) -> &str { "file_write" }
+    fn description(&self) -> &str {
+        "Create or overwrite a file inside the workspace roots with the given content. Missing parent directories are created."
+    }
+    fn annotations(&self) -> Option<Value> {
+        Some(json!({ "title": "Write file", "readOnlyHint": false, "destructiveHint": true, "idempotentHint": true }))
+    }
+    fn input_schema(&self) -> Value {
+        json!({
+            "type": "object",
+            "properties": {
+                "path": { "type": "string", "description": "Path to the file to write, absolute or relative to the first workspace root" },
+                "content": { "type": "string", "description": "The complete new content of the file" }
+            },
+            "required": ["path", "content"]
+        })
+    }
+    async fn execute(&self, params: Value, ctx: &ToolContext) -> Result<ToolResult> {
+        self.execute_with_app(params, None, ctx).await
+    }
+    async fn execute_with_app(&self, params: Value, app: Option<&tauri::AppHandle>, ctx: &ToolContext) -> Result<ToolResult> {
+        let path = params["path"].as_str().ok_or_else(|| ToolError::InvalidParams("'path' must be a string".to_string()))?;
+        let content = params["content"].as_str().ok_or_else(|| ToolError::InvalidParams("'content' must be a string".to_string()))?;
+        let path = ctx.resolve_path(path).await?;
+        let label = ctx.display_path(&path).await;
+
+        // 记录修改前的状态，审批后据此检测文件是否被修改
+        let fingerprint = file_writer::fingerprint(&path).await?;
+        let original = file_writer::read_original(&path).await?;
+        let summary = match &original {
+            file_writer::Original::Missing => format!("Created {} ({} bytes)", label, content.len()),
+            file_writer::Original::Text(_) => format!("Wrote {} bytes to {}", content.len(), label),
+            file_writer::Original::Unreadable { size, reason } => {
+                format!("Replaced {} ({} bytes, {}) with {} bytes", label, size, reason, content.len())
+            }
+        };
+        let change = FileChange { path, label, original, fingerprint, modified: content.to_string(), summary };
+        apply_file_change(&change, app, ctx).await
+    }
+}
+
+/// 文件编辑工具
+pub struct FileEditTool;
+
+#[async_trait]
+impl McpTool for FileEditTool {
+    fn name(&self) -> &str { "file_edit" }
+    fn description(&self) -> &str {
+        "Edit a UTF-8 text file inside the workspace roots, either by replacing an exact string (`old_string` must be unique \
+         unless `replace_all` is set) or by applying a unified diff `patch`."
+    }
+    fn annotations(&self) -> Option<Value> {
+        Some(json!({ "title": "Edit file", "readOnlyHint": false, "destructiveHint": true, "idempotentHint": false }))
+    }
     fn input_schema(&self) -> Value {
         json!({
             "type": "object",
-            "properties": { "path": { "type": "string", "description": "Path to the file to read" }},
+            "properties": {
+                "path": { "type": "string", "description": "Path to the file to edit, absolute or relative to the first workspace root" },
+                "old_string": { "type": "string", "description": "Exact text to replace, including whitespace and indentation" },
+                "new_string": { "type": "string", "description": "Replacement text" },
+                "replace_all": { "type": "boolean", "description": "Replace every occurrence of old_string (default false)" },
+                "patch": { "type": "string", "description": "Unified diff to apply instead of old_string/new_string" }
+            },
             "required": ["path"]
         })
     }
-    async fn execute(&self, params: Value) -> Result<Value> {
-        let path = params["path"].as_str().ok_or_else(|| anyhow::anyhow!("Missing 'path' parameter"))?;
-        let content = tokio::fs::read_to_string(path).await?;
-        Ok(json!({ "path": path, "content": content, "size": content.len() }))
+    async fn execute(&self, params: Value, ctx: &ToolContext) -> Result<ToolResult> {
+        self.execute_with_app(params, None, ctx).await
+    }
+    async fn execute_with_app(&self, params: Value, app: Option<&tauri::AppHandle>, ctx: &ToolContext) -> Result<ToolResult> {
+        let path = params["path"].as_str().ok_or_else(|| ToolError::InvalidParams("'path' must be a string".to_string()))?;
+        let edit = match (params["old_string"].as_str(), params["new_string"].as_str(), params["patch"].as_str()) {
+            (Some(old), Some(new), None) => {
+                if old.is_empty() || old == new {
+                    return Err(ToolError::InvalidParams("'old_string' must be non-empty and differ from 'new_string'".to_string()).into());
+                }
+                file_writer::Edit::Replace { old, new, replace_all: params["replace_all"].as_bool().unwrap_or(false) }
+            }
+            (None, None, Some(patch)) => file_writer::Edit::Patch(patch),
+            _ => {
+                return Err(ToolError::InvalidParams("provide either 'old_string' and 'new_string', or 'patch'".to_string()).into());
+            }
+        };
+        let path = ctx.resolve_path(path).await?;
+        let label = ctx.display_path(&path).await;
+
+        let fingerprint = file_writer::fingerprint(&path).await?;
+        let original = match file_writer::read_original(&path).await? {
+            file_writer::Original::Text(original) => original,
+            file_writer::Original::Missing => return Err(anyhow::anyhow!("{} does not exist; use file_write to create it", label)),
+            file_writer::Original::Unreadable { size, reason } => {
+                return Err(anyhow::anyhow!("{} ({} bytes) is {}; use file_write to replace it", label, size, reason))
+            }
+        };
+        let (modified, count) = file_writer::apply_edit(&original, edit)?;
+        let summary = match edit {
+            file_writer::Edit::Replace { .. } => format!("Edited {} ({} replacement{})", label, count, if count == 1 { "" } else { "s" }),
+            file_writer::Edit::Patch(_) => format!("Patched {} ({} hunk{})", label, count, if count == 1 { "" } else { "s" }),
+        };
+        let change = FileChange { path, label, original: file_writer::Original::Text(original), fingerprint, modified, summary };
+        apply_file_change(&change, app, ctx).await
+    }
+}
+
+/// file_write / file_edit 待写入的修改
+struct FileChange {
+    path: PathBuf,
+    /// 显示用的路径
+    label: String,
+    original: file_writer::Original,
+    /// 读取 original 前的文件大小和修改时间
+    fingerprint: Option<file_writer::Fingerprint>,
+    modified: String,
+    summary: String,
+}
+
+// 写入文件修改，开启审批模式时先在反馈界面显示差异，用户确认后才写入
+async fn apply_file_change(change: &FileChange, app: Option<&tauri::AppHandle>, ctx: &ToolContext) -> Result<ToolResult> {
+    let (diff, preview) = match &change.original {
+        file_writer::Original::Text(original) if *original == change.modified => {
+            return Ok(ToolResult::text(format!("{} is already up to date, nothing was written", change.label)));
+        }
+        file_writer::Original::Text(original) => {
+            let diff = file_writer::unified_diff(&change.label, original, &change.modified);
+            let preview = format!("```diff\n{}```", diff);
+            (Some(diff), preview)
+        }
+        file_writer::Original::Missing => {
+            let diff = file_writer::unified_diff(&change.label, "", &change.modified);
+            let preview = format!("```diff\n{}```", diff);
+            (Some(diff), preview)
+        }
+        // 无法显示差异，明确告知用户整个文件将被替换
+        file_writer::Original::Unreadable { size, reason } => {
+            let preview = format!(
+                "The current file ({} bytes) is {} and cannot be shown as a diff. It will be **replaced entirely** with {} bytes of new content:\n\n```\n{}\n```",
+                size, reason, change.modified.len(), change.modified
+            );
+            (None, preview)
+        }
+    };
+
+    if file_writer::requires_approval() {
+        if let Some(reason) = request_change_approval(&change.label, &preview, app, ctx).await? {
+            eprintln!("🙅 Change to {} rejected by the user", change.path.display());
+            return Ok(ToolResult::error(format!("The user rejected the change to {}. {}", change.label, reason)));
+        }
+        // 等待审批期间文件可能已被修改，此时预览已失效
+        let changed = file_writer::fingerprint(&change.path).await? != change.fingerprint
+            || file_writer::read_original(&change.path).await? != change.original;
+        if changed {
+            return Err(anyhow::anyhow!("{} changed while waiting for approval; read it again and retry", change.label));
+        }
+    }
+
+    file_writer::write_atomic(&change.path, &change.modified).await?;
+    eprintln!("✏️ {}", change.summary);
+    ctx.log(LogLevel::Info, &change.summary).await;
+    let result = ToolResult::text(change.summary.clone());
+    Ok(match diff {
+        Some(diff) => result.with_content(ToolContent::text(diff)),
+        None => result,
+    })
+}
+
+// 通过反馈界面请求用户确认文件修改，确认时返回 None，否则返回拒绝说明
+async fn request_change_approval(label: &str, preview: &str, app: Option<&tauri::AppHandle>, ctx: &ToolContext) -> Result<Option<String>> {
+    prepare_feedback_gui(app).await;
+
+    let session_id = uuid::Uuid::new_v4().to_string();
+    let raw_mcp_source = get_mcp_source_smart();
+    let ai_source = AiSource::from_string(&raw_mcp_source);
+    let ai_response = format!("The AI wants to change `{}`:\n\n{}", label, preview);
+    let mut request_data = build_feedback_request(&session_id, &ai_response, "File change approval", &raw_mcp_source, &ai_source);
+    request_data["allowFreeText"] = json!(true);
+    request_data["options"] = json!([
+        { "value": "apply", "label": "Apply", "description": "Write the change to disk" },
+        { "value": "reject", "label": "Reject", "description": "Leave the file unchanged" }
+    ]);
+    request_data["multiSelect"] = json!(false);
+    let timeout = FeedbackTimeout::resolve(&json!({}), &get_global_feedback_timeout())?;
+    if let Some(timeout) = &timeout {
+        request_data["timeoutSeconds"] = json!(timeout.duration.as_secs());
+        request_data["onTimeout"] = json!(timeout.policy.as_str());
+        request_data["expiresAt"] = json!((chrono::Utc::now() + timeout.duration).to_rfc3339());
+    }
+    ctx.log(LogLevel::Info, &format!("Waiting for the user to approve the change to {} (session {})", label, session_id)).await;
+
+    let started_at = std::time::Instant::now();
+    let result = wait_for_feedback(&request_data, &session_id, started_at, timeout.as_ref(), ctx).await?;
+    record_feedback_history(&request_data, &result, started_at).await;
+
+    // 只有明确选择 Apply 才写入，超时、取消和拒绝都保持文件不变
+    let details = result.structured_content.unwrap_or_default();
+    let approved = details["type"] == "feedback_response"
+        && details["selected_options"].as_array().is_some_and(|selected| selected.iter().any(|value| value == "apply"));
+    if approved {
+        return Ok(None);
+    }
+    let feedback = details["user_feedback"].as_str().or(details["message"].as_str()).unwrap_or_default().trim();
+    Ok(Some(if feedback.is_empty() { "No reason was given.".to_string() } else { format!("User feedback: {}", feedback) }))
+}
+
+/// 目录列表工具
+pub struct ListDirectoryTool;
+
+#[async_trait]
+impl McpTool for ListDirectoryTool {
+    fn name(&self) -> &str { "list_directory" }
+    fn description(&self) -> &str {
+        "List a directory inside the workspace roots with file sizes and modification times. Honors .gitignore and skips hidden \
+         files by default. With `pattern`, recursively lists the files matching a glob such as `**/*.rs`."
+    }
+    fn annotations(&self) -> Option<Value> { Some(json!({ "title": "List directory", "readOnlyHint": true })) }
+    fn input_schema(&self) -> Value {
+        json!({
+            "type": "object",
+            "properties": {
+                "path": { "type": "string", "description": "Directory to list, absolute or relative to the first workspace root (default: the first workspace root)" },
+                "depth": { "type": "integer", "minimum": 1, "description": "How many levels to descend, 1 lists direct children only (default 1, unlimited with pattern)" },
+                "pattern": { "type": "string", "description": "Glob matched against paths relative to `path`; only matching files are returned" },
+                "include_hidden": { "type": "boolean", "description": "Include dot files and directories (default false)" },
+                "respect_gitignore": { "type": "boolean", "description": "Skip files ignored by .gitignore and .ignore (default true)" },
+                "max_entries": {
+                    "type": "integer",
+                    "minimum": 1,
+                    "maximum": file_lister::MAX_ENTRIES_LIMIT,
+                    "description": format!("Maximum number of entries to return (default {})", file_lister::DEFAULT_MAX_ENTRIES)
+                }
+            }
+        })
+    }
+    fn output_schema(&self) -> Option<Value> {
+        Some(json!({
+            "type": "object",
+            "properties": {
+                "path": { "type": "string" },
+                "entries": {
+                    "type": "array",
+                    "items": {
+                        "type": "object",
+                        "properties": {
+                            "path": { "type": "string" },
+                            "type": { "type": "string", "enum": ["file", "directory", "symlink"] },
+                            "size": { "type": "integer" },
+                            "modified": { "type": "string", "format": "date-time" }
+                        },
+                        "required": ["path", "type"]
+                    }
+                },
+                "truncated": { "type": "boolean" }
+            },
+            "required": ["path", "entries", "truncated"]
+        }))
+    }
+    async fn execute(&self, params: Value, ctx: &ToolContext) -> Result<ToolResult> {
+        let invalid = |message: &str| -> anyhow::Error { ToolError::InvalidParams(message.to_string()).into() };
+        let positive = |name: &str| -> Result<Option<usize>> {
+            match &params[name] {
+                Value::Null => Ok(None),
+                value => value.as_u64().filter(|n| *n > 0).map(|n| Some(n as usize))
+                    .ok_or_else(|| invalid(&format!("'{}' must be a positive integer", name))),
+            }
+        };
+        let pattern = match &params["pattern"] {
+            Value::Null => None,
+            Value::String(pattern) => Some(file_lister::compile_glob(pattern).map_err(|e| invalid(&format!("invalid 'pattern': {}", e)))?),
+            _ => return Err(invalid("'pattern' must be a string")),
+        };
+        let options = file_lister::ListOptions {
+            depth: positive("depth")?,
+            pattern,
+            include_hidden: params["include_hidden"].as_bool().unwrap_or(false),
+            respect_gitignore: params["respect_gitignore"].as_bool().unwrap_or(true),
+            max_entries: positive("max_entries")?.unwrap_or(file_lister::DEFAULT_MAX_ENTRIES).min(file_lister::MAX_ENTRIES_LIMIT),
+        };
+        let path = ctx.resolve_path(params["path"].as_str().unwrap_or(".")).await?;
+        let label = ctx.display_path(&path).await;
+
+        let sandbox = ctx.sandbox().await.clone();
+        let listing = {
+            let (path, options) = (path.clone(), options.clone());
+            tokio::task::spawn_blocking(move || file_lister::list_directory(&path, &options, &sandbox)).await??
+        };
+
+        let mut text = file_lister::format_listing(&listing);
+        if listing.entries.is_empty() {
+            text = format!("{} has no matching entries\n", label);
+        }
+        if listing.truncated {
+            text.push_str(&format!("[Stopped after {} entries; narrow the path or pattern, or raise max_entries.]\n", options.max_entries));
+        }
+        // 列出的文件同时作为 resource_link 返回，客户端可以直接打开或读取
+        let links: Vec<ToolContent> = listing.entries.iter()
+            .filter(|entry| !entry.is_dir)
+            .map(|entry| ToolContent::file_link(&path.join(&entry.path), entry.path.clone(), None))
+            .collect();
+        let entries: Vec<Value> = listing.entries.iter().map(|entry| {
+            let entry_type = if entry.is_dir { "directory" } else if entry.is_symlink { "symlink" } else { "file" };
+            let mut value = json!({ "path": entry.path, "type": entry_type });
+            if entry_type == "file" {
+                value["size"] = json!(entry.size);
+            }
+            if let Some(modified) = entry.modified {
+                value["modified"] = json!(modified.to_rfc3339());
+            }
+            value
+        }).collect();
+        let result = ToolResult::text(text).with_structured(json!({ "path": label, "entries": entries, "truncated": listing.truncated }));
+        Ok(links.into_iter().fold(result, ToolResult::with_content))
+    }
+}
+
+/// 文件内容搜索工具
+pub struct SearchFilesTool;
+
+#[async_trait]
+impl McpTool for SearchFilesTool {
+    fn name(&self) -> &str { "search_files" }
+    fn description(&self) -> &str {
+        "Search file contents inside the workspace roots for a regular expression or literal string, like grep. Honors .gitignore \
+         and skips hidden and binary files by default. Matches are printed as `path:line:text`, context lines as `path-line-text`."
+    }
+    fn annotations(&self) -> Option<Value> { Some(json!({ "title": "Search files", "readOnlyHint": true })) }
+    fn input_schema(&self) -> Value {
+        let globs = json!({ "anyOf": [{ "type": "string" }, { "type": "array", "items": { "type": "string" } }] });
+        json!({
+            "type": "object",
+            "properties": {
+                "pattern": { "type": "string", "description": "Regular expression (Rust regex syntax) to search for, or a literal string with `literal`" },
+                "path": { "type": "string", "description": "Directory or file to search, absolute or relative to the first workspace root (default: the first workspace root)" },
+                "literal": { "type": "boolean", "description": "Treat pattern as a literal string (default false)" },
+                "case_insensitive": { "type": "boolean", "description": "Ignore case (default false)" },
+                "include": {
+                    "description": "Only search files matching these globs; globs without `/` match file names, e.g. `*.rs`",
+                    "anyOf": globs["anyOf"]
+                },
+                "exclude": { "description": "Skip files matching these globs", "anyOf": globs["anyOf"] },
+                "context_lines": {
+                    "type": "integer",
+                    "minimum": 0,
+                    "maximum": file_searcher::MAX_CONTEXT_LINES,
+                    "description": "Lines of context to show before and after each match (default 0)"
+                },
+                "max_results": {
+                    "type": "integer",
+                    "minimum": 1,
+                    "maximum": file_searcher::MAX_RESULTS_LIMIT,
+                    "description": format!("Maximum number of matching lines to return (default {})", file_searcher::DEFAULT_MAX_RESULTS)
+                },
+                "include_hidden": { "type": "boolean", "description": "Search dot files and directories (default false)" },
+                "respect_gitignore": { "type": "boolean", "description": "Skip files ignored by .gitignore and .ignore (default true)" }
+            },
+            "required": ["pattern"]
+        })
+    }
+    fn output_schema(&self) -> Option<Value> {
+        Some(json!({
+            "type": "object",
+            "properties": {
+                "matches": {
+                    "type": "array",
+                    "items": {
+                        "type": "object",
+                        "properties": {
+                            "path": { "type": "string" },
+                            "line": { "type": "integer" },
+                            "text": { "type": "string" }
+                        },
+                        "required": ["path", "line", "text"]
+                    }
+                },
+                "match_count": { "type": "integer" },
+                "files_searched": { "type": "integer" },
+                "truncated": { "type": "boolean" }
+            },
+            "required": ["matches", "match_count", "files_searched", "truncated"]
+        }))
+    }
+    async fn execute(&self, params: Value, ctx: &ToolContext) -> Result<ToolResult> {
+        let invalid = |message: String| -> anyhow::Error { ToolError::InvalidParams(message).into() };
+        let pattern = params["pattern"].as_str().ok_or_else(|| invalid("'pattern' must be a string".to_string()))?;
+        let regex = file_searcher::build_regex(
+            pattern,
+            params["literal"].as_bool().unwrap_or(false),
+            params["case_insensitive"].as_bool().unwrap_or(false),
+        ).map_err(|e| invalid(format!("invalid 'pattern': {}", e)))?;
+        let globs = |name: &str| -> Result<Option<globset::GlobSet>> {
+            let patterns: Vec<String> = match &params[name] {
+                Value::Null => Vec::new(),
+                Value::String(pattern) => vec![pattern.clone()],
+                Value::Array(patterns) => patterns
+                    .iter()
+                    .map(|pattern| pattern.as_str().map(str::to_string))
+                    .collect::<Option<_>>()
+                    .ok_or_else(|| invalid(format!("'{}' must be a glob or a list of globs", name)))?,
+                _ => return Err(invalid(format!("'{}' must be a glob or a list of globs", name))),
+            };
+            file_searcher::build_globs(&patterns).map_err(|e| invalid(format!("invalid '{}': {}", name, e)))
+        };
+        let count = |name: &str, default: usize, max: usize| -> Result<usize> {
+            match &params[name] {
+                Value::Null => Ok(default),
+                value => value.as_u64().map(|n| (n as usize).min(max))
+                    .ok_or_else(|| invalid(format!("'{}' must be a non-negative integer", name))),
+            }
+        };
+        let options = file_searcher::SearchOptions {
+            regex,
+            include: globs("include")?,
+            exclude: globs("exclude")?,
+            context_lines: count("context_lines", 0, file_searcher::MAX_CONTEXT_LINES)?,
+            max_results: count("max_results", file_searcher::DEFAULT_MAX_RESULTS, file_searcher::MAX_RESULTS_LIMIT)?.max(1),
+            include_hidden: params["include_hidden"].as_bool().unwrap_or(false),
+            respect_gitignore: params["respect_gitignore"].as_bool().unwrap_or(true),
+        };
+        let path = ctx.resolve_path(params["path"].as_str().unwrap_or(".")).await?;
+
+        let sandbox = ctx.sandbox().await.clone();
+        let cancellation_token = ctx.cancellation_token();
+        // 结果中的路径相对于搜索目录，搜索单个文件时相对于其所在目录
+        let root = if path.is_file() { path.parent().unwrap_or(&path).to_path_buf() } else { path.clone() };
+        let results = {
+            let options = options.clone();
+            tokio::task::spawn_blocking(move || file_searcher::search(&path, &options, &sandbox, &cancellation_token)).await??
+        };
+
+        let mut text = file_searcher::format_results(&results);
+        if results.files.is_empty() {
+            text = format!("No matches in {} searched files\n", results.files_searched);
+        }
+        if results.truncated {
+            text.push_str(&format!(
+                "[Stopped after {} matches; narrow the pattern or path, or raise max_results.]\n",
+                options.max_results
+            ));
+        }
+        let matches: Vec<Value> = results.files.iter()
+            .flat_map(|file| file.lines.iter().filter(|line| line.is_match).map(move |line| {
+                json!({ "path": file.path, "line": line.line_number, "text": line.text })
+            }))
+            .collect();
+        let result = ToolResult::text(text).with_structured(json!({
+            "matches": matches,
+            "match_count": results.match_count,
+            "files_searched": results.files_searched,
+            "truncated": results.truncated
+        }));
+        // 每个有匹配的文件附带一个 resource_link
+        Ok(results.files.iter().fold(result, |result, file| {
+            let count = file.lines.iter().filter(|line| line.is_match).count();
+            let description = format!("{} matching line{}", count, if count == 1 { "" } else { "s" });
+            result.with_content(ToolContent::file_link(&root.join(&file.path), file.path.clone(), Some(description)))
+        }))
     }
 }
 
@@ -448,17 +1667,32 @@ impl McpTool for SystemInfoTool {
     fn name(&self) -> &str { "system_info" }
     fn description(&self) -> &str { "Get system information" }
     fn input_schema(&self) -> Value { json!({ "type": "object", "properties": {} }) }
-    async fn execute(&self, _params: Value) -> Result<Value> {
-        Ok(json!({
+    fn annotations(&self) -> Option<Value> {
+        Some(json!({ "title": "System information", "readOnlyHint": true, "openWorldHint": false }))
+    }
+    fn output_schema(&self) -> Option<Value> {
+        Some(json!({
+            "type": "object",
+            "properties": {
+                "os": { "type": "string" },
+                "arch": { "type": "string" },
+                "hostname": { "type": "string" },
+                "timestamp": { "type": "string", "format": "date-time" }
+            },
+            "required": ["os", "arch", "hostname", "timestamp"]
+        }))
+    }
+    async fn execute(&self, _params: Value, _ctx: &ToolContext) -> Result<ToolResult> {
+        Ok(ToolResult::structured(json!({
             "os": std::env::consts::OS,
             "arch": std::env::consts::ARCH,
             "hostname": hostname::get().unwrap_or_default().to_string_lossy().to_string(),
             "timestamp": chrono::Utc::now().to_rfc3339()
-        }))
+        })))
     }
 }
 
-/// 反馈工具 - 文件系统IPC版本
+/// 反馈工具 - 优先通过 GUI 的本地 socket 等待反馈，不可用时回退到文件系统IPC
 pub struct FeedbackTool;
 
 #[async_trait]
@@ -471,22 +1705,90 @@ impl McpTool for FeedbackTool {
             "properties": {
                 "ai_response": { "type": "string", "description": "The AI's response to display" },
                 "context": { "type": "string", "description": "Optional context for the session" },
-                "source": { "type": "string", "description": "Optional AI tool source identifier (IGNORED - MCP_SOURCE env var is used instead)" }
+                "source": { "type": "string", "description": "Optional AI tool source identifier (IGNORED - MCP_SOURCE env var is used instead)" },
+                "timeout_seconds": { "type": "integer", "minimum": 0, "description": "Optional time to wait for the user, 0 waits forever (defaults to MCP_FEEDBACK_TIMEOUT)" },
+                "on_timeout": {
+                    "type": "string",
+                    "enum": ["default_reply", "cancel", "escalate"],
+                    "description": "What to return when the timeout expires: a default reply, a cancelled session, or an error asking for a human (defaults to MCP_FEEDBACK_ON_TIMEOUT, then default_reply)"
+                },
+                "default_reply": { "type": "string", "description": "Reply returned when on_timeout is default_reply (defaults to MCP_FEEDBACK_DEFAULT_REPLY)" },
+                "options": {
+                    "type": "array",
+                    "description": "Optional choices for the user, either plain strings or { value, label, description } objects",
+                    "items": {
+                        "anyOf": [
+                            { "type": "string" },
+                            {
+                                "type": "object",
+                                "properties": {
+                                    "value": { "type": "string" },
+                                    "label": { "type": "string" },
+                                    "description": { "type": "string" }
+                                },
+                                "required": ["value"]
+                            }
+                        ]
+                    }
+                },
+                "multi_select": { "type": "boolean", "description": "Allow selecting more than one option (default false)" },
+                "fields": {
+                    "type": "object",
+                    "description": "Optional form as a flat JSON schema object whose properties are string, number, integer or boolean fields (strings may have an enum)"
+                },
+                "allow_free_text": { "type": "boolean", "description": "Show the free-text reply box alongside options or fields (default true)" }
             },
             "required": ["ai_response"]
         })
     }
 
-    async fn execute(&self, params: Value) -> Result<Value> {
-        self.execute_with_app(params, None).await
+    fn annotations(&self) -> Option<Value> {
+        Some(json!({ "title": "Ask the user for feedback", "readOnlyHint": true, "openWorldHint": false }))
+    }
+
+    fn output_schema(&self) -> Option<Value> {
+        Some(json!({
+            "type": "object",
+            "properties": {
+                "type": { "type": "string", "enum": ["feedback_response", "feedback_cancelled", "feedback_timeout"] },
+                "user_feedback": { "type": "string" },
+                "message": { "type": "string" },
+                "selected_options": { "type": "array", "items": { "type": "string" } },
+                "form_values": { "type": "object" },
+                "attachments": {
+                    "type": "array",
+                    "items": {
+                        "type": "object",
+                        "properties": {
+                            "name": { "type": "string" },
+                            "mime_type": { "type": "string" },
+                            "size": { "type": "integer" },
+                            "uri": { "type": "string" }
+                        }
+                    }
+                },
+                "timed_out": { "type": "boolean" }
+            },
+            "required": ["type"]
+        }))
+    }
+
+    async fn execute(&self, params: Value, ctx: &ToolContext) -> Result<ToolResult> {
+        self.execute_with_app(params, None, ctx).await
     }
 
-    async fn execute_with_app(&self, params: Value, app: Option<&tauri::AppHandle>) -> Result<Value> {
+    async fn execute_with_app(&self, params: Value, app: Option<&tauri::AppHandle>, ctx: &ToolContext) -> Result<ToolResult> {
         let ai_response = params["ai_response"].as_str().unwrap_or("").to_string();
         let context = params["context"].as_str().unwrap_or("Feedback Session").to_string();
+        let timeout = FeedbackTimeout::resolve(&params, &get_global_feedback_timeout())?;
+        let options = parse_feedback_options(&params)?;
+        let fields = parse_feedback_fields(&params)?;
+        let allow_free_text = params["allow_free_text"].as_bool().unwrap_or(true);
+        if !allow_free_text && options.is_none() && fields.is_none() {
+            return Err(ToolError::InvalidParams("'allow_free_text' can only be false when 'options' or 'fields' are given".to_string()).into());
+        }
 
         let session_id = uuid::Uuid::new_v4().to_string();
-        let timestamp = chrono::Utc::now().to_rfc3339();
 
         // 强制使用环境变量，完全忽略 AI 传递的任何 source 参数
         let raw_mcp_source = get_mcp_source_smart();
@@ -494,66 +1796,1690 @@ impl McpTool for FeedbackTool {
         // 用于生成显示名称的 AiSource
         let ai_source = AiSource::from_string(&raw_mcp_source);
 
-        // 确保 GUI 应用正在运行（仅在 MCP 模式下需要检查）
-        if app.is_none() {
-            eprintln!("🔍 Checking if GUI application is running...");
-            if let Err(e) = ensure_gui_running().await {
-                eprintln!("⚠️ Failed to ensure GUI is running: {}", e);
-                // 继续执行，即使 GUI 启动失败也要写入文件，让文件监听器处理
+        let mut request_data = build_feedback_request(&session_id, &ai_response, &context, &raw_mcp_source, &ai_source);
+        let started_at = std::time::Instant::now();
+        request_data["allowFreeText"] = json!(allow_free_text);
+        if let Some(options) = options {
+            request_data["options"] = json!(options);
+            request_data["multiSelect"] = json!(params["multi_select"].as_bool().unwrap_or(false));
+        }
+        if let Some(fields) = fields {
+            request_data["fields"] = fields;
+        }
+        // 超时信息随请求发给 GUI，在会话卡片中显示倒计时
+        if let Some(timeout) = &timeout {
+            request_data["timeoutSeconds"] = json!(timeout.duration.as_secs());
+            request_data["onTimeout"] = json!(timeout.policy.as_str());
+            request_data["expiresAt"] = json!((chrono::Utc::now() + timeout.duration).to_rfc3339());
+        }
+        ctx.log(LogLevel::Info, &format!("Waiting for user feedback (session {})", session_id)).await;
+
+        let result = match elicit_feedback_form(&request_data, started_at, timeout.as_ref(), ctx).await {
+            Some(result) => result,
+            None => {
+                prepare_feedback_gui(app).await;
+                wait_for_feedback(&request_data, &session_id, started_at, timeout.as_ref(), ctx).await?
             }
+        };
+        record_feedback_history(&request_data, &result, started_at).await;
+        Ok(result)
+    }
+}
+
+const DEFAULT_TIMEOUT_REPLY: &str = "The user did not reply in time. Continue with your best judgement.";
+
+/// 反馈超时后的处理策略
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum TimeoutPolicy {
+    /// 返回默认回复
+    DefaultReply,
+    /// 按用户取消处理
+    Cancel,
+    /// 返回错误结果，要求 AI 停下来等待人工处理
+    Escalate,
+}
+
+impl TimeoutPolicy {
+    pub fn from_string(policy: &str) -> Option<Self> {
+        match policy.trim().to_lowercase().as_str() {
+            "default_reply" | "default-reply" | "default" => Some(TimeoutPolicy::DefaultReply),
+            "cancel" | "cancelled" => Some(TimeoutPolicy::Cancel),
+            "escalate" => Some(TimeoutPolicy::Escalate),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            TimeoutPolicy::DefaultReply => "default_reply",
+            TimeoutPolicy::Cancel => "cancel",
+            TimeoutPolicy::Escalate => "escalate",
         }
+    }
+}
+
+/// 全局反馈超时设置，在设置页面中修改并保存到共享存储目录，环境变量优先
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct FeedbackTimeoutSettings {
+    /// 超时秒数，0 或未设置表示无限等待
+    #[serde(default)]
+    pub timeout_seconds: Option<u64>,
+    #[serde(default)]
+    pub on_timeout: Option<String>,
+    #[serde(default)]
+    pub default_reply: Option<String>,
+}
+
+/// 覆盖保存的设置的环境变量
+pub const FEEDBACK_TIMEOUT_ENV_VARS: [&str; 3] = ["MCP_FEEDBACK_TIMEOUT", "MCP_FEEDBACK_ON_TIMEOUT", "MCP_FEEDBACK_DEFAULT_REPLY"];
+
+fn get_feedback_timeout_settings_path() -> PathBuf {
+    let mut path = get_shared_storage_dir();
+    fs::create_dir_all(&path).ok();
+    path.push("feedback_timeout.json");
+    path
+}
+
+pub fn load_feedback_timeout_settings() -> FeedbackTimeoutSettings {
+    match fs::read_to_string(get_feedback_timeout_settings_path()) {
+        Ok(content) => serde_json::from_str(&content).unwrap_or_else(|e| {
+            eprintln!("❌ Failed to parse feedback timeout settings: {}, using defaults", e);
+            FeedbackTimeoutSettings::default()
+        }),
+        Err(_) => FeedbackTimeoutSettings::default(),
+    }
+}
+
+/// 校验并保存超时设置
+pub fn save_feedback_timeout_settings(settings: &FeedbackTimeoutSettings) -> Result<()> {
+    if let Some(policy) = &settings.on_timeout {
+        TimeoutPolicy::from_string(policy).ok_or_else(|| anyhow::anyhow!("Unknown on_timeout policy '{}'", policy))?;
+    }
+    fs::write(get_feedback_timeout_settings_path(), serde_json::to_string_pretty(settings)?)?;
+    Ok(())
+}
+
+// 全局反馈超时设置：保存的设置，可被 MCP_FEEDBACK_TIMEOUT (秒数)、MCP_FEEDBACK_ON_TIMEOUT、MCP_FEEDBACK_DEFAULT_REPLY 环境变量逐项覆盖
+fn get_global_feedback_timeout() -> Value {
+    let env = |name: &str| std::env::var(name).ok().filter(|value| !value.trim().is_empty());
+    merge_feedback_timeout_settings(load_feedback_timeout_settings(), env)
+}
+
+fn merge_feedback_timeout_settings(saved: FeedbackTimeoutSettings, env: impl Fn(&str) -> Option<String>) -> Value {
+    json!({
+        "timeout_seconds": env("MCP_FEEDBACK_TIMEOUT").and_then(|value| value.trim().parse::<u64>().ok()).or(saved.timeout_seconds),
+        "on_timeout": env("MCP_FEEDBACK_ON_TIMEOUT").or(saved.on_timeout),
+        "default_reply": env("MCP_FEEDBACK_DEFAULT_REPLY").or(saved.default_reply)
+    })
+}
+
+/// 反馈等待超时设置
+#[derive(Debug, Clone, PartialEq)]
+pub struct FeedbackTimeout {
+    pub duration: Duration,
+    pub policy: TimeoutPolicy,
+    pub default_reply: String,
+}
+
+impl FeedbackTimeout {
+    /// 工具参数优先于全局设置，超时为 0 或未设置时无限等待
+    pub fn resolve(params: &Value, global: &Value) -> Result<Option<Self>> {
+        let setting = |name: &str| params.get(name).filter(|v| !v.is_null()).or_else(|| global.get(name).filter(|v| !v.is_null()));
 
-        // 写入请求文件
-        if let Err(e) = write_feedback_request(&session_id, &ai_response, &context, &raw_mcp_source, &ai_source) {
-            eprintln!("❌ Failed to write feedback request: {}", e);
-            return Err(anyhow::anyhow!("Failed to write feedback request: {}", e));
+        let seconds = match setting("timeout_seconds") {
+            Some(value) => value.as_u64().ok_or_else(|| {
+                ToolError::InvalidParams("'timeout_seconds' must be a non-negative integer".to_string())
+            })?,
+            None => 0,
+        };
+        if seconds == 0 {
+            return Ok(None);
         }
-        eprintln!("📝 Feedback request written successfully for session: {}", session_id);
 
-        if let Some(app_handle) = app {
-            let feedback_data = json!({
-                "sessionId": session_id,
-                "aiResponse": ai_response,
-                "context": context,
-                "timestamp": timestamp,
-                "aiSource": raw_mcp_source,
-                "aiSourceDisplay": ai_source.to_display_name()
-            });
+        let policy = match setting("on_timeout").and_then(|v| v.as_str()) {
+            Some(policy) => TimeoutPolicy::from_string(policy).ok_or_else(|| {
+                ToolError::InvalidParams(format!("unknown on_timeout policy '{}'", policy))
+            })?,
+            None => TimeoutPolicy::DefaultReply,
+        };
+        let default_reply = setting("default_reply")
+            .and_then(|v| v.as_str())
+            .unwrap_or(DEFAULT_TIMEOUT_REPLY)
+            .to_string();
 
-            if let Err(e) = app_handle.emit("feedback-request", &feedback_data) {
-                eprintln!("❌ Failed to emit feedback-request event: {}", e);
-            } else {
-                eprintln!("📡 Feedback request event emitted successfully");
+        Ok(Some(Self { duration: Duration::from_secs(seconds), policy, default_reply }))
+    }
+
+    fn into_result(self) -> ToolResult {
+        let message = format!("No reply from the user within {} seconds.", self.duration.as_secs());
+        match self.policy {
+            TimeoutPolicy::DefaultReply => ToolResult::text(self.default_reply.clone()).with_structured(json!({
+                "type": "feedback_response",
+                "user_feedback": self.default_reply,
+                "timed_out": true
+            })),
+            TimeoutPolicy::Cancel => ToolResult::structured(json!({
+                "type": "feedback_cancelled",
+                "message": message,
+                "timed_out": true
+            })),
+            TimeoutPolicy::Escalate => {
+                let message = format!("{} Stop and wait for a human before continuing.", message);
+                ToolResult::error(message.clone()).with_structured(json!({
+                    "type": "feedback_timeout",
+                    "message": message,
+                    "timed_out": true
+                }))
             }
-            
-            tokio::spawn(async {
-                if let Err(e) = crate::system_sound::play_notification_sound_async().await {
-                    eprintln!("🔔 Failed to play notification sound: {}", e);
-                }
-            });
         }
-        // 移除超时限制，无限等待用户反馈
-        loop {
-            // 检查响应
-            if let Some(feedback_content) = read_feedback_response(&session_id) {
-                // 清理请求文件
-                fs::remove_file(get_feedback_request_path(&session_id)).ok();
-                return Ok(json!({
-                    "type": "feedback_response",
-                    "user_feedback": feedback_content
-                }));
+    }
+}
+
+// 等待到超时时间，未设置超时时永不返回
+async fn wait_until_timeout(started_at: std::time::Instant, timeout: Option<&FeedbackTimeout>) {
+    match timeout {
+        Some(timeout) => tokio::time::sleep_until(tokio::time::Instant::from_std(started_at + timeout.duration)).await,
+        None => std::future::pending().await,
+    }
+}
+
+const FORM_FIELD_TYPES: [&str; 4] = ["string", "number", "integer", "boolean"];
+
+// 规范化反馈选项：字符串或 { value, label, description } 对象，统一转换为对象
+fn parse_feedback_options(params: &Value) -> Result<Option<Vec<Value>>> {
+    let Some(options) = params.get("options").filter(|v| !v.is_null()) else {
+        return Ok(None);
+    };
+    let options = options
+        .as_array()
+        .filter(|options| !options.is_empty())
+        .ok_or_else(|| ToolError::InvalidParams("'options' must be a non-empty array".to_string()))?;
+
+    let mut normalized: Vec<Value> = Vec::new();
+    for option in options {
+        let (value, label, description) = match option {
+            Value::String(value) => (value.as_str(), value.as_str(), None),
+            Value::Object(_) => {
+                let value = option["value"].as_str().ok_or_else(|| {
+                    ToolError::InvalidParams("each option object needs a string 'value'".to_string())
+                })?;
+                (value, option["label"].as_str().unwrap_or(value), option["description"].as_str())
             }
+            _ => return Err(ToolError::InvalidParams("options must be strings or objects".to_string()).into()),
+        };
+        if normalized.iter().any(|existing| existing["value"] == value) {
+            return Err(ToolError::InvalidParams(format!("duplicate option '{}'", value)).into());
+        }
+        normalized.push(json!({ "value": value, "label": label, "description": description }));
+    }
+    Ok(Some(normalized))
+}
+
+// 校验反馈表单：只支持扁平的 string / number / integer / boolean 字段
+fn parse_feedback_fields(params: &Value) -> Result<Option<Value>> {
+    let Some(fields) = params.get("fields").filter(|v| !v.is_null()) else {
+        return Ok(None);
+    };
+    let invalid = |message: String| -> anyhow::Error { ToolError::InvalidParams(message).into() };
 
-            // 检查取消
-            if is_session_cancelled(&session_id) {
-                return Ok(json!({
-                    "type": "feedback_cancelled",
-                    "message": "Feedback session was cancelled by the user."
-                }));
+    let properties = fields["properties"]
+        .as_object()
+        .filter(|properties| !properties.is_empty())
+        .ok_or_else(|| invalid("'fields' must have a non-empty 'properties' object".to_string()))?;
+    for (name, property) in properties {
+        let field_type = property["type"].as_str().unwrap_or("");
+        if !FORM_FIELD_TYPES.contains(&field_type) {
+            return Err(invalid(format!("field '{}' must have type string, number, integer or boolean", name)));
+        }
+        if let Some(choices) = property.get("enum") {
+            let valid = field_type == "string"
+                && choices.as_array().is_some_and(|choices| !choices.is_empty() && choices.iter().all(Value::is_string));
+            if !valid {
+                return Err(invalid(format!("field '{}' enum must be a non-empty list of strings", name)));
             }
+        }
+    }
+
+    let required = match fields.get("required") {
+        Some(required) => required
+            .as_array()
+            .ok_or_else(|| invalid("'fields.required' must be an array".to_string()))?
+            .clone(),
+        None => Vec::new(),
+    };
+    for name in &required {
+        if !name.as_str().is_some_and(|name| properties.contains_key(name)) {
+            return Err(invalid(format!("required field {} is not defined in 'fields.properties'", name)));
+        }
+    }
+
+    Ok(Some(json!({ "type": "object", "properties": properties, "required": required })))
+}
 
-            // 不再检查超时，允许无限等待
-            tokio::time::sleep(Duration::from_millis(500)).await;
+/// 校验用户提交的反馈是否符合请求中的选项和表单定义
+pub fn validate_feedback_response(request_data: &Value, response: &FeedbackResponse) -> std::result::Result<(), String> {
+    let has_answer = response.selected_options.as_ref().is_some_and(|selected| !selected.is_empty())
+        || response.form_values.is_some();
+    if !request_data["allowFreeText"].as_bool().unwrap_or(true) && !has_answer {
+        return Err("Please choose an option or fill in the form".to_string());
+    }
+
+    if let Some(selected) = &response.selected_options {
+        let options = request_data["options"].as_array().ok_or("This request has no options")?;
+        if selected.len() > 1 && !request_data["multiSelect"].as_bool().unwrap_or(false) {
+            return Err("Only one option can be selected".to_string());
+        }
+        for value in selected {
+            if !options.iter().any(|option| option["value"] == value.as_str()) {
+                return Err(format!("Unknown option '{}'", value));
+            }
         }
     }
+
+    let fields = &request_data["fields"];
+    if let Some(properties) = fields["properties"].as_object() {
+        let empty = serde_json::Map::new();
+        let values = match &response.form_values {
+            Some(values) => values.as_object().ok_or("Form values must be an object")?,
+            None => &empty,
+        };
+        for name in fields["required"].as_array().into_iter().flatten().filter_map(Value::as_str) {
+            let missing = match values.get(name) {
+                None | Some(Value::Null) => true,
+                Some(Value::String(text)) => text.trim().is_empty(),
+                Some(_) => false,
+            };
+            if missing {
+                return Err(format!("Field '{}' is required", name));
+            }
+        }
+        for (name, value) in values {
+            let property = properties.get(name).ok_or_else(|| format!("Unknown field '{}'", name))?;
+            let valid = match property["type"].as_str() {
+                _ if value.is_null() => true,
+                Some("string") => value.as_str().is_some_and(|text| {
+                    property["enum"].as_array().is_none_or(|choices| choices.iter().any(|choice| choice == text))
+                }),
+                Some("number") => value.is_number(),
+                Some("integer") => value.is_i64() || value.is_u64(),
+                Some("boolean") => value.is_boolean(),
+                _ => false,
+            };
+            if !valid {
+                return Err(format!("Invalid value for field '{}'", name));
+            }
+        }
+    } else if response.form_values.is_some() {
+        return Err("This request has no form".to_string());
+    }
+
+    Ok(())
+}
+
+// 把附件转换为内容块：图片作为 image，文本文件作为嵌入的文本资源，其他文件作为 base64 资源
+fn attachment_content(attachment: &FeedbackAttachment, limits: AttachmentLimits) -> Result<ToolContent> {
+    use base64::Engine;
+
+    let bytes = attachments::read_attachment(&attachments::get_attachments_root(), attachment, limits)?;
+    if attachment.mime_type.starts_with("image/") {
+        return Ok(ToolContent::image(&bytes, &attachment.mime_type));
+    }
+
+    let uri = sandbox::path_to_file_uri(std::path::Path::new(&attachment.path));
+    match (attachments::text_mime_type(attachment), String::from_utf8(bytes)) {
+        (Some(mime_type), Ok(text)) => Ok(ToolContent::Resource { uri, mime_type, text: Some(text), blob: None }),
+        (_, other) => {
+            let bytes = other.map(String::into_bytes).unwrap_or_else(|e| e.into_bytes());
+            Ok(ToolContent::Resource {
+                uri,
+                mime_type: attachment.mime_type.clone(),
+                text: None,
+                blob: Some(base64::engine::general_purpose::STANDARD.encode(bytes)),
+            })
+        }
+    }
+}
+
+fn feedback_response_result(response: FeedbackResponse) -> ToolResult {
+    let mut structured = json!({
+        "type": "feedback_response",
+        "user_feedback": response.feedback
+    });
+    let has_structured_answer = response.selected_options.is_some() || response.form_values.is_some();
+    if let Some(selected) = response.selected_options {
+        structured["selected_options"] = json!(selected);
+    }
+    if let Some(form_values) = response.form_values {
+        structured["form_values"] = form_values;
+    }
+    if !response.attachments.is_empty() {
+        structured["attachments"] = json!(response.attachments.iter().map(|attachment| json!({
+            "name": attachment.name,
+            "mime_type": attachment.mime_type,
+            "size": attachment.size,
+            "uri": sandbox::path_to_file_uri(std::path::Path::new(&attachment.path))
+        })).collect::<Vec<_>>());
+    }
+
+    // 选项和表单结果只能通过 JSON 表达，文本内容也使用 JSON 以兼容不支持 structuredContent 的客户端
+    let mut result = if has_structured_answer {
+        ToolResult::structured(structured)
+    } else {
+        ToolResult::text(response.feedback).with_structured(structured)
+    };
+
+    let limits = attachments::get_attachment_limits();
+    for attachment in &response.attachments {
+        match attachment_content(attachment, limits) {
+            Ok(content) => result = result.with_content(content),
+            Err(e) => {
+                eprintln!("⚠️ Failed to attach {}: {}", attachment.name, e);
+                result = result.with_content(ToolContent::text(format!("[Attachment '{}' unavailable: {}]", attachment.name, e)));
+            }
+        }
+    }
+    result
+}
+
+// 优先通过 socket 等待回复，socket 不可用时回退到文件 IPC
+async fn wait_for_feedback(
+    request_data: &Value,
+    session_id: &str,
+    started_at: std::time::Instant,
+    timeout: Option<&FeedbackTimeout>,
+    ctx: &ToolContext,
+) -> Result<ToolResult> {
+    // 开始等待时先报告一次进度，客户端无需等满一分钟即可显示等待状态
+    report_waiting_progress(ctx, started_at).await;
+
+    // GUI 刚启动时 socket 可能尚未就绪，最多等待 5 秒；无界面模式下不会启动 GUI，无需等待
+    let attempts = if is_headless() { 1 } else { 10 };
+    match feedback_ipc::connect_with_retry(attempts).await {
+        Ok(stream) => {
+            if let Some(result) = wait_feedback_over_socket(stream, request_data, session_id, started_at, timeout, ctx).await {
+                return Ok(result);
+            }
+        }
+        Err(e) => eprintln!("⚠️ Feedback socket unavailable ({}), falling back to file IPC", e),
+    }
+
+    wait_feedback_over_files(request_data, session_id, started_at, timeout, ctx).await
+}
+
+// 根据工具结果构造历史记录，请求和响应文件删除后仍可在历史中查询
+fn build_history_entry(request_data: &Value, result: &ToolResult, duration: Duration) -> HistoryEntry {
+    let mut details = result.structured_content.clone().unwrap_or_else(|| json!({}));
+    let timed_out = details["timed_out"].as_bool().unwrap_or(false);
+    let status = match details["type"].as_str() {
+        _ if timed_out => "timed_out",
+        Some("feedback_response") => "answered",
+        Some("feedback_timeout") => "timed_out",
+        _ => "cancelled",
+    };
+    let user_feedback = details["user_feedback"].as_str().map(str::to_string);
+    let message = details["message"].as_str().map(str::to_string);
+    if let Some(details) = details.as_object_mut() {
+        for key in ["type", "user_feedback", "message", "timed_out"] {
+            details.remove(key);
+        }
+    }
+    let text = |key: &str| request_data[key].as_str().unwrap_or_default().to_string();
+
+    HistoryEntry {
+        session_id: text("sessionId"),
+        ai_response: text("aiResponse"),
+        context: text("context"),
+        ai_source: text("aiSource"),
+        ai_source_display: text("aiSourceDisplay"),
+        status: status.to_string(),
+        // 取消和超时没有用户回复，记录原因便于搜索
+        user_feedback: user_feedback.or(message),
+        details: details.as_object().filter(|details| !details.is_empty()).map(|_| details.clone()),
+        requested_at: text("timestamp"),
+        finished_at: chrono::Utc::now().to_rfc3339(),
+        duration_ms: duration.as_millis() as i64,
+    }
+}
+
+// 写入历史数据库并执行保留策略，然后清理会话的附件；失败只记录日志，不影响工具结果
+async fn record_feedback_history(request_data: &Value, result: &ToolResult, started_at: std::time::Instant) {
+    let entry = build_history_entry(request_data, result, started_at.elapsed());
+    let recorded = tokio::task::spawn_blocking(move || -> Result<usize> {
+        let mut history = FeedbackHistory::open_default()?;
+        history.record(&entry)?;
+        history.apply_retention(history::get_retention_policy())
+    }).await;
+
+    match recorded {
+        Ok(Ok(removed)) if removed > 0 => eprintln!("🗑️ Removed {} expired feedback history entries", removed),
+        Ok(Ok(_)) => {}
+        Ok(Err(e)) => eprintln!("⚠️ Failed to record feedback history: {}", e),
+        Err(e) => eprintln!("⚠️ Feedback history task failed: {}", e),
+    }
+
+    // 附件内容已嵌入结果，历史记录只保存文本，会话结束后删除附件目录
+    let session_id = request_data["sessionId"].as_str().unwrap_or_default();
+    if !session_id.is_empty() {
+        if let Err(e) = attachments::remove_session_attachments(&attachments::get_attachments_root(), session_id) {
+            eprintln!("⚠️ Failed to remove attachments of session {}: {}", session_id, e);
+        }
+    }
+}
+
+fn feedback_cancelled_result(message: &str) -> ToolResult {
+    ToolResult::structured(json!({
+        "type": "feedback_cancelled",
+        "message": message
+    }))
+}
+
+// 客户端支持 elicitation 时，只有表单 (没有选项) 的反馈请求直接由客户端向用户显示；
+// 客户端不支持、请求失败或返回的表单无效时返回 None，回退到 GUI
+async fn elicit_feedback_form(
+    request_data: &Value,
+    started_at: std::time::Instant,
+    timeout: Option<&FeedbackTimeout>,
+    ctx: &ToolContext,
+) -> Option<ToolResult> {
+    if request_data.get("options").is_some() {
+        return None;
+    }
+    let fields = request_data.get("fields")?;
+    let peer = ctx.elicitation_peer()?;
+    let session_id = request_data["sessionId"].as_str().unwrap_or_default();
+    let requested_schema: model::ElicitationSchema = match serde_json::from_value(fields.clone()) {
+        Ok(schema) => schema,
+        Err(e) => {
+            eprintln!("⚠️ Form for session {} cannot be sent as an elicitation ({}), showing it in the GUI", session_id, e);
+            return None;
+        }
+    };
+    let message = request_data["aiResponse"]
+        .as_str()
+        .filter(|message| !message.trim().is_empty())
+        .or(request_data["context"].as_str())
+        .unwrap_or_default()
+        .to_string();
+    let params = model::CreateElicitationRequestParams::FormElicitationParams { meta: None, message, requested_schema };
+    eprintln!("📋 Asking the client to show the form for session {}", session_id);
+
+    let result = tokio::select! {
+        result = peer.create_elicitation(params) => result,
+        _ = ctx.cancelled() => {
+            eprintln!("🛑 Feedback session {} cancelled by client", session_id);
+            return Some(feedback_cancelled_result("Feedback session was cancelled by the client."));
+        }
+        _ = wait_until_timeout(started_at, timeout) => {
+            eprintln!("⏰ Feedback session {} timed out", session_id);
+            return timeout.cloned().map(FeedbackTimeout::into_result);
+        }
+    };
+    let result = match result {
+        Ok(result) => result,
+        Err(e) => {
+            eprintln!("⚠️ Elicitation for session {} failed ({}), showing the form in the GUI", session_id, e);
+            return None;
+        }
+    };
+    match result.action {
+        model::ElicitationAction::Accept => {
+            let response = FeedbackResponse { form_values: result.content, ..Default::default() };
+            if let Err(e) = validate_feedback_response(request_data, &response) {
+                eprintln!("⚠️ Elicitation for session {} returned an invalid form ({}), showing it in the GUI", session_id, e);
+                return None;
+            }
+            Some(feedback_response_result(response))
+        }
+        model::ElicitationAction::Decline => Some(feedback_cancelled_result("The user declined to fill in the form.")),
+        model::ElicitationAction::Cancel => Some(feedback_cancelled_result("Feedback session was cancelled by the user.")),
+    }
+}
+
+// 向客户端报告等待进度 (开始等待时和之后每分钟一次)
+async fn report_waiting_progress(ctx: &ToolContext, started_at: std::time::Instant) {
+    let elapsed = started_at.elapsed();
+    ctx.report_progress(
+        elapsed.as_secs() as f64,
+        None,
+        Some(&format!("Waiting for user, {} min elapsed", elapsed.as_secs() / 60)),
+    ).await;
+}
+
+// 通过 socket 等待反馈，连接中断时返回 None 以回退到文件 IPC
+async fn wait_feedback_over_socket<S>(
+    stream: S,
+    request_data: &Value,
+    session_id: &str,
+    started_at: std::time::Instant,
+    timeout: Option<&FeedbackTimeout>,
+    ctx: &ToolContext,
+) -> Option<ToolResult>
+where
+    S: tokio::io::AsyncRead + tokio::io::AsyncWrite + Send + 'static,
+{
+    let (mut reader, mut writer) = tokio::io::split(stream);
+    let request = IpcMessage::Request { request: request_data.clone() };
+    if let Err(e) = feedback_ipc::write_frame(&mut writer, &request).await {
+        eprintln!("⚠️ Failed to send feedback request over socket ({}), falling back to file IPC", e);
+        return None;
+    }
+    eprintln!("🔌 Feedback request sent over socket for session: {}", session_id);
+
+    // 在单独的任务中读取消息，避免 select! 中断读取到一半的帧
+    let (incoming_tx, mut incoming) = tokio::sync::mpsc::unbounded_channel();
+    let reader_task = tokio::spawn(async move {
+        while let Ok(Some(message)) = feedback_ipc::read_frame(&mut reader).await {
+            if incoming_tx.send(message).is_err() {
+                break;
+            }
+        }
+    });
+
+    let mut progress = tokio::time::interval_at(
+        tokio::time::Instant::now() + Duration::from_secs(60),
+        Duration::from_secs(60),
+    );
+    // 未设置超时时无限等待用户反馈
+    let result = loop {
+        tokio::select! {
+            message = incoming.recv() => match message {
+                Some(IpcMessage::Response { response, .. }) => break Some(feedback_response_result(response)),
+                Some(IpcMessage::Cancel { .. }) => {
+                    break Some(feedback_cancelled_result("Feedback session was cancelled by the user."));
+                }
+                Some(message) => eprintln!("⚠️ Unexpected message on feedback socket: {:?}", message),
+                None => {
+                    eprintln!("⚠️ Lost connection to GUI for session {}, falling back to file IPC", session_id);
+                    break None;
+                }
+            },
+            _ = ctx.cancelled() => {
+                let cancel = IpcMessage::Cancel { session_id: session_id.to_string() };
+                feedback_ipc::write_frame(&mut writer, &cancel).await.ok();
+                eprintln!("🛑 Feedback session {} cancelled by client", session_id);
+                break Some(feedback_cancelled_result("Feedback session was cancelled by the client."));
+            }
+            _ = wait_until_timeout(started_at, timeout) => {
+                // 通知 GUI 关闭已超时的会话
+                let cancel = IpcMessage::Cancel { session_id: session_id.to_string() };
+                feedback_ipc::write_frame(&mut writer, &cancel).await.ok();
+                eprintln!("⏰ Feedback session {} timed out", session_id);
+                break timeout.cloned().map(FeedbackTimeout::into_result);
+            }
+            _ = progress.tick() => report_waiting_progress(ctx, started_at).await,
+        }
+    };
+    reader_task.abort();
+    result
+}
+
+// 通过请求/响应文件等待反馈 (GUI socket 不可用时的回退通道)
+async fn wait_feedback_over_files(
+    request_data: &Value,
+    session_id: &str,
+    started_at: std::time::Instant,
+    timeout: Option<&FeedbackTimeout>,
+    ctx: &ToolContext,
+) -> Result<ToolResult> {
+    // 写入请求文件
+    if let Err(e) = write_feedback_request(request_data) {
+        eprintln!("❌ Failed to write feedback request: {}", e);
+        return Err(anyhow::anyhow!("Failed to write feedback request: {}", e));
+    }
+    eprintln!("📝 Feedback request written successfully for session: {}", session_id);
+
+    let mut last_report_minutes = started_at.elapsed().as_secs() / 60;
+    loop {
+        // 检查响应
+        if let Some(response) = read_feedback_response(session_id) {
+            // 清理请求文件
+            fs::remove_file(get_feedback_request_path(session_id)).ok();
+            return Ok(feedback_response_result(response));
+        }
+
+        // 检查取消
+        if is_session_cancelled(session_id) {
+            return Ok(feedback_cancelled_result("Feedback session was cancelled by the user."));
+        }
+
+        let elapsed_minutes = started_at.elapsed().as_secs() / 60;
+        if elapsed_minutes > last_report_minutes {
+            last_report_minutes = elapsed_minutes;
+            report_waiting_progress(ctx, started_at).await;
+        }
+
+        // 客户端取消或等待超时时删除请求文件，GUI 随之关闭会话
+        tokio::select! {
+            _ = ctx.cancelled() => {
+                fs::remove_file(get_feedback_request_path(session_id)).ok();
+                eprintln!("🛑 Feedback session {} cancelled by client", session_id);
+                return Ok(feedback_cancelled_result("Feedback session was cancelled by the client."));
+            }
+            _ = wait_until_timeout(started_at, timeout) => {
+                fs::remove_file(get_feedback_request_path(session_id)).ok();
+                eprintln!("⏰ Feedback session {} timed out", session_id);
+                if let Some(timeout) = timeout {
+                    return Ok(timeout.clone().into_result());
+                }
+            }
+            _ = tokio::time::sleep(Duration::from_millis(500)) => {}
+        }
+    }
+}
+
+// 获取需要作为资源暴露的工作区文件 (MCP_RESOURCE_FILES 环境变量，按系统路径分隔符分隔)
+fn get_workspace_resource_files() -> Vec<PathBuf> {
+    match std::env::var_os("MCP_RESOURCE_FILES") {
+        Some(value) => std::env::split_paths(&value)
+            .filter(|path| !path.as_os_str().is_empty())
+            .map(|path| fs::canonicalize(&path).unwrap_or(path))
+            .collect(),
+        None => Vec::new(),
+    }
+}
+
+// 获取用户提示词目录 (MCP_PROMPTS_DIR 环境变量，默认为共享存储目录下的 prompts)
+fn get_prompts_dir() -> PathBuf {
+    if let Some(dir) = std::env::var_os("MCP_PROMPTS_DIR") {
+        if !dir.is_empty() {
+            return PathBuf::from(dir);
+        }
+    }
+    let mut path = get_shared_storage_dir();
+    path.push("prompts");
+    path
+}
+
+/// 模板提示词 - 使用 `{参数名}` 占位符，可从 JSON 文件加载
+#[derive(Debug, Clone, Deserialize)]
+pub struct TemplatePrompt {
+    pub name: String,
+    #[serde(default)]
+    pub description: String,
+    #[serde(default)]
+    pub arguments: Vec<PromptArgument>,
+    pub template: String,
+}
+
+impl TemplatePrompt {
+    pub fn built_in() -> Vec<Self> {
+        vec![
+            TemplatePrompt {
+                name: "feedback_before_finishing".to_string(),
+                description: "Ask the user for feedback via the feedback tool before finishing a task".to_string(),
+                arguments: vec![PromptArgument {
+                    name: "task".to_string(),
+                    description: "The task that is being worked on".to_string(),
+                    required: true,
+                }],
+                template: "You are working on the following task: {task}\n\n\
+                    Before you consider the task finished, call the `feedback` tool with a summary of what you did \
+                    and wait for the user's reply. Follow the instructions in the reply, and keep calling the \
+                    `feedback` tool after each round of changes until the user confirms that the task is done."
+                    .to_string(),
+            },
+            TemplatePrompt {
+                name: "summarize_changes".to_string(),
+                description: "Summarize the current changes for code review".to_string(),
+                arguments: vec![PromptArgument {
+                    name: "changes".to_string(),
+                    description: "Diff or description of the changes".to_string(),
+                    required: true,
+                }],
+                template: "Summarize the following changes for code review. \
+                    Start with one or two sentences describing what changed and why, then list the notable \
+                    changes per file, and finish with anything the reviewer should check carefully.\n\n{changes}"
+                    .to_string(),
+            },
+        ]
+    }
+
+    // 从目录加载 *.json 提示词文件，解析失败的文件会被跳过
+    pub fn load_from_dir(dir: &std::path::Path) -> Vec<Self> {
+        let mut prompts = Vec::new();
+        let Ok(entries) = fs::read_dir(dir) else {
+            return prompts;
+        };
+
+        for entry in entries.flatten() {
+            let path = entry.path();
+            if path.extension().and_then(|s| s.to_str()) != Some("json") {
+                continue;
+            }
+            match fs::read_to_string(&path)
+                .map_err(anyhow::Error::from)
+                .and_then(|content| Ok(serde_json::from_str::<TemplatePrompt>(&content)?))
+            {
+                Ok(prompt) => {
+                    eprintln!("📝 Loaded prompt '{}' from {:?}", prompt.name, path);
+                    prompts.push(prompt);
+                }
+                Err(e) => eprintln!("⚠️ Failed to load prompt from {:?}: {}", path, e),
+            }
+        }
+        prompts
+    }
+}
+
+#[async_trait]
+impl McpPrompt for TemplatePrompt {
+    fn name(&self) -> &str { &self.name }
+    fn description(&self) -> &str { &self.description }
+    fn arguments(&self) -> Vec<PromptArgument> { self.arguments.clone() }
+    async fn get(&self, arguments: &Value) -> Result<Vec<model::PromptMessage>> {
+        let mut values = HashMap::new();
+        for arg in &self.arguments {
+            let value = match &arguments[&arg.name] {
+                Value::String(value) => value.clone(),
+                Value::Null if arg.required => {
+                    return Err(anyhow::anyhow!("Missing required argument '{}'", arg.name));
+                }
+                Value::Null => String::new(),
+                other => other.to_string(),
+            };
+            values.insert(arg.name.as_str(), value);
+        }
+
+        let text = substitute_placeholders(&self.template, |name| values.get(name).cloned());
+        Ok(vec![model::PromptMessage::new_text(model::PromptMessageRole::User, text)])
+    }
+}
+
+/// 一次扫描替换模板中的 `{名称}` 占位符，lookup 返回 None 的占位符保持原样。
+/// 替换进来的值不会再被扫描，值中包含的占位符不会被展开
+pub fn substitute_placeholders(template: &str, lookup: impl Fn(&str) -> Option<String>) -> String {
+    let mut result = String::with_capacity(template.len());
+    let mut rest = template;
+    while let Some(start) = rest.find('{') {
+        result.push_str(&rest[..start]);
+        let after = &rest[start + 1..];
+        match after.find(['{', '}']) {
+            Some(end) if after.as_bytes()[end] == b'}' => {
+                let name = &after[..end];
+                match lookup(name) {
+                    Some(value) => result.push_str(&value),
+                    None => {
+                        result.push('{');
+                        result.push_str(name);
+                        result.push('}');
+                    }
+                }
+                rest = &after[end + 1..];
+            }
+            // 没有配对的 `}`，或者在 `}` 之前又出现了 `{`
+            _ => {
+                result.push('{');
+                rest = after;
+            }
+        }
+    }
+    result.push_str(rest);
+    result
+}
+
+/// 待处理反馈会话资源
+pub struct PendingFeedbackResource;
+
+#[async_trait]
+impl McpResource for PendingFeedbackResource {
+    fn uri(&self) -> &str { "feedback://sessions/pending" }
+    fn name(&self) -> &str { "Pending feedback sessions" }
+    fn description(&self) -> &str { "Feedback sessions that are still waiting for a user reply" }
+    fn mime_type(&self) -> &str { "application/json" }
+    async fn read(&self) -> Result<String> {
+        // 通过 socket 提交的请求只保存在 GUI 中
+        let mut requests = feedback_ipc::list_pending().await.unwrap_or_default();
+        requests.extend(list_pending_request_files());
+
+        let mut sessions: Vec<Value> = requests
+            .iter()
+            .map(|request_data| json!({
+                "sessionId": request_data["sessionId"],
+                "aiResponse": request_data["aiResponse"],
+                "context": request_data["context"],
+                "timestamp": request_data["timestamp"],
+                "aiSource": request_data["aiSource"],
+                "aiSourceDisplay": request_data["aiSourceDisplay"]
+            }))
+            .collect();
+
+        sessions.sort_by(|a, b| a["timestamp"].as_str().cmp(&b["timestamp"].as_str()));
+        Ok(serde_json::to_string_pretty(&json!({ "sessions": sessions }))?)
+    }
+}
+
+/// 工作区文件资源
+pub struct WorkspaceFileResource {
+    path: PathBuf,
+    uri: String,
+    name: String,
+}
+
+impl WorkspaceFileResource {
+    pub fn new(path: PathBuf) -> Self {
+        let uri = sandbox::path_to_file_uri(&path);
+        let name = path
+            .file_name()
+            .map(|n| n.to_string_lossy().to_string())
+            .unwrap_or_else(|| uri.clone());
+        Self { path, uri, name }
+    }
+}
+
+#[async_trait]
+impl McpResource for WorkspaceFileResource {
+    fn uri(&self) -> &str { &self.uri }
+    fn name(&self) -> &str { &self.name }
+    fn description(&self) -> &str { "Workspace file configured via MCP_RESOURCE_FILES" }
+    fn mime_type(&self) -> &str {
+        match self.path.extension().and_then(|s| s.to_str()) {
+            Some("md") | Some("markdown") => "text/markdown",
+            Some("json") => "application/json",
+            Some("html") | Some("htm") => "text/html",
+            _ => "text/plain",
+        }
+    }
+    async fn read(&self) -> Result<String> {
+        // 与 file_read 相同的字节数上限和编码识别，资源无法分页读取，超出部分被截断
+        let options = file_reader::ReadOptions {
+            offset: 1,
+            limit: usize::MAX,
+            max_bytes: file_reader::get_max_read_bytes(),
+            line_numbers: false,
+        };
+        match file_reader::read_file(&self.path, &options).await? {
+            file_reader::FileContent::Text(range) => match range.next_offset {
+                Some(next) => Ok(format!(
+                    "{}\n[Truncated after line {} at {} bytes; use the file_read tool with offset={} to read the rest]",
+                    range.text.trim_end_matches('\n'), range.last_line, options.max_bytes, next
+                )),
+                None => Ok(range.text),
+            },
+            file_reader::FileContent::Image { .. } | file_reader::FileContent::Binary { .. } => {
+                Err(anyhow::anyhow!("{} is not a text file", self.path.display()))
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader, DuplexStream, Lines, ReadHalf, WriteHalf};
+
+    const LATEST: &str = SUPPORTED_PROTOCOL_VERSIONS[0];
+
+    // 通过内存管道与服务器交换 JSON-RPC 消息的测试客户端
+    struct TestClient {
+        server: LocalMcpServer,
+        reader: Lines<BufReader<ReadHalf<DuplexStream>>>,
+        writer: WriteHalf<DuplexStream>,
+        next_id: i64,
+        /// 回复服务器 `roots/list` 请求的根目录，为 null 时返回错误
+        roots: Value,
+        /// 收到的 `roots/list` 请求数
+        roots_requests: usize,
+        /// 回复服务器 `elicitation/create` 请求的结果，为 null 时返回错误
+        elicitation: Value,
+        /// 收到的 `elicitation/create` 请求参数
+        elicitations: Vec<Value>,
+        /// 收到的通知
+        notifications: Vec<Value>,
+        /// 等待其他请求时先到达的响应
+        responses: Vec<Value>,
+    }
+
+    impl TestClient {
+        async fn connect(protocol_version: &str, capabilities: Value) -> (Self, Value) {
+            let (client_io, server_io) = tokio::io::duplex(1 << 16);
+            let server = LocalMcpServer::new().with_session("test");
+            let handler = server.clone();
+            tokio::spawn(async move {
+                if let Ok(service) = handler.serve(server_io).await {
+                    let _ = service.waiting().await;
+                }
+            });
+
+            let (reader, writer) = tokio::io::split(client_io);
+            let mut client = Self {
+                server,
+                reader: BufReader::new(reader).lines(),
+                writer,
+                next_id: 0,
+                roots: json!([]),
+                roots_requests: 0,
+                elicitation: Value::Null,
+                elicitations: Vec::new(),
+                notifications: Vec::new(),
+                responses: Vec::new(),
+            };
+            let response = client.request("initialize", json!({
+                "protocolVersion": protocol_version,
+                "capabilities": capabilities,
+                "clientInfo": { "name": "test", "version": "0.0.0" }
+            })).await;
+            client.send(json!({ "jsonrpc": "2.0", "method": "notifications/initialized" })).await;
+            (client, response)
+        }
+
+        async fn send(&mut self, message: Value) {
+            let line = format!("{}\n", message);
+            self.writer.write_all(line.as_bytes()).await.unwrap();
+        }
+
+        async fn request(&mut self, method: &str, params: Value) -> Value {
+            let id = self.send_request(method, params).await;
+            self.response(id).await
+        }
+
+        // 发送请求但不等待响应，返回请求 id
+        async fn send_request(&mut self, method: &str, params: Value) -> i64 {
+            self.next_id += 1;
+            let id = self.next_id;
+            self.send(json!({ "jsonrpc": "2.0", "id": id, "method": method, "params": params })).await;
+            id
+        }
+
+        // 等待指定请求的响应，期间处理服务器发来的其他消息
+        async fn response(&mut self, id: i64) -> Value {
+            loop {
+                if let Some(index) = self.responses.iter().position(|response| response["id"] == id) {
+                    return self.responses.remove(index);
+                }
+                let line = self.reader.next_line().await.unwrap().expect("server closed the connection");
+                self.handle_message(&line).await;
+            }
+        }
+
+        // 处理服务器发来的消息，直到 idle 时间内没有新消息
+        async fn process_messages(&mut self, idle: Duration) {
+            while let Ok(line) = tokio::time::timeout(idle, self.reader.next_line()).await {
+                let line = line.unwrap().expect("server closed the connection");
+                self.handle_message(&line).await;
+            }
+        }
+
+        // 回复服务器的 roots/list 和 elicitation/create 请求，记录响应和通知
+        async fn handle_message(&mut self, line: &str) {
+            let message: Value = serde_json::from_str(line).unwrap();
+            if message["method"] == "roots/list" {
+                self.roots_requests += 1;
+                let reply = if self.roots.is_null() {
+                    json!({ "jsonrpc": "2.0", "id": message["id"], "error": { "code": -32603, "message": "no roots" } })
+                } else {
+                    json!({ "jsonrpc": "2.0", "id": message["id"], "result": { "roots": self.roots } })
+                };
+                self.send(reply).await;
+            } else if message["method"] == "elicitation/create" {
+                self.elicitations.push(message["params"].clone());
+                let reply = if self.elicitation.is_null() {
+                    json!({ "jsonrpc": "2.0", "id": message["id"], "error": { "code": -32603, "message": "no elicitation" } })
+                } else {
+                    json!({ "jsonrpc": "2.0", "id": message["id"], "result": self.elicitation })
+                };
+                self.send(reply).await;
+            } else if message.get("id").is_some() {
+                self.responses.push(message);
+            } else {
+                self.notifications.push(message);
+            }
+        }
+    }
+
+    async fn call_tool(name: &str, arguments: Value) -> Value {
+        let (mut client, _) = TestClient::connect(LATEST, json!({})).await;
+        client.request("tools/call", json!({ "name": name, "arguments": arguments })).await
+    }
+
+    #[tokio::test]
+    async fn test_unknown_tool_is_protocol_error() {
+        let response = call_tool("no_such_tool", json!({})).await;
+        assert_eq!(response["error"]["code"], -32602);
+        assert!(response.get("result").is_none());
+    }
+
+    #[tokio::test]
+    async fn test_missing_required_argument_is_protocol_error() {
+        let response = call_tool("file_read", json!({})).await;
+        assert_eq!(response["error"]["code"], -32602);
+        assert!(response["error"]["message"].as_str().unwrap().contains("path"));
+    }
+
+    #[tokio::test]
+    async fn test_unknown_method_is_method_not_found() {
+        let (mut client, _) = TestClient::connect(LATEST, json!({})).await;
+        let response = client.request("no/such/method", json!({})).await;
+        assert_eq!(response["error"]["code"], -32601);
+        let response = client.request("ping", json!({})).await;
+        assert!(response.get("result").is_some());
+    }
+
+    #[tokio::test]
+    async fn test_execution_failure_is_error_result() {
+        let response = call_tool("file_read", json!({ "path": "/definitely/not/a/real/file.txt" })).await;
+        assert!(response.get("error").is_none());
+        assert_eq!(response["result"]["isError"], true);
+        assert!(response["result"]["content"][0]["text"].as_str().unwrap().contains("file_read"));
+    }
+
+    #[tokio::test]
+    async fn test_successful_call_is_not_error_result() {
+        let response = call_tool("system_info", json!({})).await;
+        assert!(response.get("error").is_none());
+        assert!(response["result"].get("isError").is_none());
+    }
+
+    #[tokio::test]
+    async fn test_structured_result_includes_text_fallback() {
+        let response = call_tool("system_info", json!({})).await;
+        let structured = &response["result"]["structuredContent"];
+        assert_eq!(structured["os"], std::env::consts::OS);
+        let text: Value = serde_json::from_str(response["result"]["content"][0]["text"].as_str().unwrap()).unwrap();
+        assert_eq!(&text, structured);
+    }
+
+    // 以临时目录作为客户端根目录的会话
+    async fn connect_with_workspace() -> (TestClient, PathBuf) {
+        let workspace = std::env::temp_dir().join(format!("mcp_workspace_{}", uuid::Uuid::new_v4()));
+        fs::create_dir_all(&workspace).unwrap();
+        let (mut client, _) = TestClient::connect(LATEST, json!({ "roots": { "listChanged": true } })).await;
+        client.roots = json!([{ "uri": format!("file://{}", workspace.to_string_lossy().replace(' ', "%20")), "name": "workspace" }]);
+        (client, workspace)
+    }
+
+    #[tokio::test]
+    async fn test_file_read_returns_raw_content() {
+        let (mut client, workspace) = connect_with_workspace().await;
+        let path = workspace.join("file.txt");
+        fs::write(&path, "line \"one\"\nline two\n").unwrap();
+        let arguments = json!({ "path": path.to_string_lossy(), "line_numbers": false });
+        let response = client.request("tools/call", json!({ "name": "file_read", "arguments": arguments })).await;
+        fs::remove_dir_all(&workspace).ok();
+        assert_eq!(response["result"]["content"][0]["text"], "line \"one\"\nline two\n");
+        assert_eq!(response["result"]["content"].as_array().unwrap().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_file_read_pages_through_large_files() {
+        let (mut client, workspace) = connect_with_workspace().await;
+        let path = workspace.join("big.txt");
+        fs::write(&path, (1..=10).map(|n| format!("line {}\n", n)).collect::<String>()).unwrap();
+        fs::write(workspace.join("blob.bin"), [0u8, 1, 2, 3]).unwrap();
+
+        let arguments = json!({ "path": path.to_string_lossy(), "offset": 3, "limit": 2 });
+        let response = client.request("tools/call", json!({ "name": "file_read", "arguments": arguments })).await;
+        assert_eq!(response["result"]["content"][0]["text"], "     3\tline 3\n     4\tline 4\n");
+        assert_eq!(response["result"]["content"][1]["text"], "[Showing lines 3-4; output limited to 2 lines. Use offset=5 to continue.]");
+
+        let arguments = json!({ "path": path.to_string_lossy(), "offset": 20 });
+        let response = client.request("tools/call", json!({ "name": "file_read", "arguments": arguments })).await;
+        assert_eq!(response["result"]["content"][1]["text"], "[Offset 20 is past the end of the file (10 lines)]");
+
+        let response = client.request("tools/call", json!({ "name": "file_read", "arguments": { "path": "blob.bin" } })).await;
+        assert!(response["result"]["content"][0]["text"].as_str().unwrap().contains("binary file (4 bytes)"));
+
+        let response = client.request("tools/call", json!({ "name": "file_read", "arguments": { "path": "big.txt", "limit": 0 } })).await;
+        assert_eq!(response["error"]["code"], -32602);
+        fs::remove_dir_all(&workspace).ok();
+    }
+
+    #[tokio::test]
+    async fn test_file_write_and_edit() {
+        let (mut client, workspace) = connect_with_workspace().await;
+        async fn call(client: &mut TestClient, name: &str, arguments: Value) -> Value {
+            client.request("tools/call", json!({ "name": name, "arguments": arguments })).await["result"].clone()
+        }
+
+        let result = call(&mut client, "file_write", json!({ "path": "src/lib.rs", "content": "fn a() {}\nfn b() {}\n" })).await;
+        assert_eq!(result["content"][0]["text"], "Created src/lib.rs (20 bytes)");
+        assert!(result["content"][1]["text"].as_str().unwrap().contains("+fn b() {}"));
+
+        let result = call(&mut client, "file_edit", json!({ "path": "src/lib.rs", "old_string": "fn b() {}", "new_string": "fn c() {}" })).await;
+        assert_eq!(result["content"][0]["text"], "Edited src/lib.rs (1 replacement)");
+        assert!(result["content"][1]["text"].as_str().unwrap().contains("-fn b() {}\n+fn c() {}"));
+
+        let patch = "--- a/src/lib.rs\n+++ b/src/lib.rs\n@@ -1,2 +1,2 @@\n-fn a() {}\n+fn a() -> u8 { 1 }\n fn c() {}\n";
+        let result = call(&mut client, "file_edit", json!({ "path": "src/lib.rs", "patch": patch })).await;
+        assert_eq!(result["content"][0]["text"], "Patched src/lib.rs (1 hunk)");
+        assert_eq!(fs::read_to_string(workspace.join("src/lib.rs")).unwrap(), "fn a() -> u8 { 1 }\nfn c() {}\n");
+
+        // 修改失败时文件保持不变
+        let result = call(&mut client, "file_edit", json!({ "path": "src/lib.rs", "patch": patch })).await;
+        assert_eq!(result["isError"], true);
+        let result = call(&mut client, "file_edit", json!({ "path": "missing.rs", "old_string": "a", "new_string": "b" })).await;
+        assert!(result["content"][0]["text"].as_str().unwrap().contains("use file_write to create it"));
+        let result = call(&mut client, "file_write", json!({ "path": "/etc/cc-custom-mcp-test", "content": "x" })).await;
+        assert!(result["content"][0]["text"].as_str().unwrap().contains("outside the workspace roots"));
+        assert_eq!(fs::read_to_string(workspace.join("src/lib.rs")).unwrap(), "fn a() -> u8 { 1 }\nfn c() {}\n");
+
+        // 二进制文件不能编辑，但可以整体覆盖 (包括覆盖为空文件)
+        fs::write(workspace.join("data.bin"), b"\xff\xfe\x00\x01").unwrap();
+        let result = call(&mut client, "file_edit", json!({ "path": "data.bin", "old_string": "a", "new_string": "b" })).await;
+        assert!(result["content"][0]["text"].as_str().unwrap().contains("not UTF-8 text"));
+        let result = call(&mut client, "file_write", json!({ "path": "data.bin", "content": "" })).await;
+        assert_eq!(result["content"][0]["text"], "Replaced data.bin (4 bytes, not UTF-8 text) with 0 bytes");
+        assert_eq!(fs::read(workspace.join("data.bin")).unwrap(), b"");
+        fs::remove_dir_all(&workspace).ok();
+    }
+
+    #[tokio::test]
+    async fn test_list_directory() {
+        let (mut client, workspace) = connect_with_workspace().await;
+        fs::create_dir_all(workspace.join("src")).unwrap();
+        fs::write(workspace.join("src/lib.rs"), "fn main() {}").unwrap();
+        fs::write(workspace.join("notes.md"), "notes").unwrap();
+
+        let response = client.request("tools/call", json!({ "name": "list_directory", "arguments": {} })).await;
+        let result = &response["result"];
+        let text = result["content"][0]["text"].as_str().unwrap();
+        assert!(text.starts_with("notes.md") && text.ends_with("src/\n"), "{}", text);
+        let entry = &result["structuredContent"]["entries"][0];
+        assert_eq!((&entry["path"], &entry["type"], &entry["size"]), (&json!("notes.md"), &json!("file"), &json!(5)));
+        assert!(entry["modified"].is_string());
+
+        let arguments = json!({ "pattern": "**/*.rs" });
+        let response = client.request("tools/call", json!({ "name": "list_directory", "arguments": arguments })).await;
+        assert_eq!(response["result"]["structuredContent"]["entries"][0]["path"], "src/lib.rs");
+        assert_eq!(response["result"]["structuredContent"]["entries"].as_array().unwrap().len(), 1);
+        assert_eq!(response["result"]["content"][1]["type"], "resource_link");
+        assert_eq!(response["result"]["content"][1]["name"], "src/lib.rs");
+
+        let response = client.request("tools/call", json!({ "name": "list_directory", "arguments": { "path": "/etc" } })).await;
+        assert_eq!(response["result"]["isError"], true);
+        fs::remove_dir_all(&workspace).ok();
+    }
+
+    #[tokio::test]
+    async fn test_search_files() {
+        let (mut client, workspace) = connect_with_workspace().await;
+        fs::create_dir_all(workspace.join("src")).unwrap();
+        fs::write(workspace.join("src/lib.rs"), "fn parse() {}\nfn render() {}\n").unwrap();
+        fs::write(workspace.join("notes.md"), "TODO: parse dates\n").unwrap();
+
+        let arguments = json!({ "pattern": "fn \\w+", "include": "*.rs" });
+        let response = client.request("tools/call", json!({ "name": "search_files", "arguments": arguments })).await;
+        assert_eq!(response["result"]["content"][0]["text"], "src/lib.rs:1:fn parse() {}\nsrc/lib.rs:2:fn render() {}\n");
+        assert_eq!(response["result"]["structuredContent"]["matches"][1], json!({ "path": "src/lib.rs", "line": 2, "text": "fn render() {}" }));
+        let link = &response["result"]["content"][1];
+        assert_eq!((&link["type"], &link["name"], &link["description"]), (&json!("resource_link"), &json!("src/lib.rs"), &json!("2 matching lines")));
+        assert_eq!(sandbox::file_uri_to_path(link["uri"].as_str().unwrap()), Some(fs::canonicalize(&workspace).unwrap().join("src/lib.rs")));
+
+        let arguments = json!({ "pattern": "todo:", "case_insensitive": true, "literal": true, "exclude": ["src/**"] });
+        let response = client.request("tools/call", json!({ "name": "search_files", "arguments": arguments })).await;
+        assert_eq!(response["result"]["structuredContent"]["matches"][0]["path"], "notes.md");
+        assert_eq!(response["result"]["structuredContent"]["files_searched"], 1);
+
+        let response = client.request("tools/call", json!({ "name": "search_files", "arguments": { "pattern": "(" } })).await;
+        assert_eq!(response["error"]["code"], -32602);
+        let response = client.request("tools/call", json!({ "name": "search_files", "arguments": { "pattern": "root", "path": "/etc" } })).await;
+        assert_eq!(response["result"]["isError"], true);
+        fs::remove_dir_all(&workspace).ok();
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_file_read_is_confined_to_workspace_roots() {
+        let (mut client, workspace) = connect_with_workspace().await;
+        fs::write(workspace.join("notes.txt"), "inside").unwrap();
+        std::os::unix::fs::symlink("/etc/hostname", workspace.join("hostname")).unwrap();
+        async fn read(client: &mut TestClient, path: &str) -> String {
+            let arguments = json!({ "path": path, "line_numbers": false });
+            let response = client.request("tools/call", json!({ "name": "file_read", "arguments": arguments })).await;
+            response["result"]["content"][0]["text"].as_str().unwrap_or_default().to_string()
+        }
+
+        // 相对路径相对于客户端根目录
+        assert_eq!(read(&mut client, "notes.txt").await, "inside");
+        assert!(read(&mut client, "hostname").await.contains("outside the workspace roots"));
+        assert!(read(&mut client, "/etc/passwd").await.contains("outside the workspace roots"));
+        assert!(read(&mut client, "~/.ssh/id_rsa").await.contains("sensitive file pattern"));
+
+        // 客户端根目录变化后重新获取，没有任何根目录时拒绝所有路径
+        client.roots = json!([]);
+        client.send(json!({ "jsonrpc": "2.0", "method": "notifications/roots/list_changed" })).await;
+        let notes = workspace.join("notes.txt");
+        assert!(read(&mut client, &notes.to_string_lossy()).await.contains("No workspace roots configured"));
+
+        // 获取失败的结果同样被缓存，直到根目录再次变化
+        client.roots = Value::Null;
+        client.send(json!({ "jsonrpc": "2.0", "method": "notifications/roots/list_changed" })).await;
+        let requests = client.roots_requests;
+        for _ in 0..2 {
+            assert!(read(&mut client, &notes.to_string_lossy()).await.contains("No workspace roots configured"));
+        }
+        assert_eq!(client.roots_requests, requests + 1);
+        fs::remove_dir_all(&workspace).ok();
+    }
+
+    #[tokio::test]
+    async fn test_roots_are_requested_only_by_file_tools() {
+        let (mut client, workspace) = connect_with_workspace().await;
+        fs::write(workspace.join("notes.txt"), "inside").unwrap();
+
+        client.request("tools/call", json!({ "name": "system_info", "arguments": {} })).await;
+        client.request("tools/call", json!({ "name": "no_such_tool", "arguments": {} })).await;
+        assert_eq!(client.roots_requests, 0);
+
+        let response = client.request("tools/call", json!({ "name": "file_read", "arguments": { "path": "notes.txt" } })).await;
+        assert!(response["result"]["content"][0]["text"].as_str().unwrap().contains("inside"));
+        assert_eq!(client.roots_requests, 1);
+        fs::remove_dir_all(&workspace).ok();
+    }
+
+    // 反馈测试使用无界面模式、独立的共享目录和不存在的 socket，不会启动或连接正在运行的 GUI，只通过请求文件交互
+    fn use_feedback_test_env() -> PathBuf {
+        static SHARED_DIR: std::sync::OnceLock<PathBuf> = std::sync::OnceLock::new();
+        SHARED_DIR
+            .get_or_init(|| {
+                let dir = std::env::temp_dir().join(format!("mcp_feedback_test_{}", uuid::Uuid::new_v4()));
+                fs::create_dir_all(&dir).unwrap();
+                std::env::set_var("MCP_HEADLESS", "1");
+                std::env::set_var("MCP_SHARED_DIR", &dir);
+                std::env::set_var("MCP_HISTORY_DB", dir.join("history.db"));
+                std::env::set_var("MCP_IPC_ENDPOINT", dir.join("no-gui.sock"));
+                dir
+            })
+            .clone()
+    }
+
+    // 发起 feedback 调用 (可附带其他参数和 `_meta`) 并等待请求文件出现，返回请求 id 和请求文件路径
+    async fn start_feedback_call(client: &mut TestClient, mut arguments: Value, meta: Value) -> (i64, PathBuf) {
+        let requests_dir = use_feedback_test_env().join("feedback_requests");
+        let ai_response = format!("test {}", uuid::Uuid::new_v4());
+        arguments["ai_response"] = json!(ai_response);
+        let params = json!({ "name": "feedback", "arguments": arguments, "_meta": meta });
+        let id = client.send_request("tools/call", params).await;
+        for _ in 0..100 {
+            for entry in fs::read_dir(&requests_dir).into_iter().flatten().flatten() {
+                let request: Value = fs::read_to_string(entry.path()).ok().and_then(|c| serde_json::from_str(&c).ok()).unwrap_or_default();
+                if request["aiResponse"] == ai_response.as_str() {
+                    return (id, entry.path());
+                }
+            }
+            client.process_messages(Duration::from_millis(100)).await;
+        }
+        panic!("feedback request file was not written");
+    }
+
+    #[tokio::test]
+    async fn test_client_cancels_pending_feedback() {
+        let (mut client, _) = TestClient::connect(LATEST, json!({})).await;
+        let (id, request_path) = start_feedback_call(&mut client, json!({}), json!({})).await;
+
+        let cancelled = json!({ "requestId": id, "reason": "user pressed stop" });
+        client.send(json!({ "jsonrpc": "2.0", "method": "notifications/cancelled", "params": cancelled })).await;
+        let response = tokio::time::timeout(Duration::from_secs(5), client.response(id)).await.expect("feedback call was not cancelled");
+        assert_eq!(response["result"]["structuredContent"], json!({
+            "type": "feedback_cancelled",
+            "message": "Feedback session was cancelled by the client."
+        }));
+        // 删除请求文件后 GUI 随之关闭会话
+        assert!(!request_path.exists());
+    }
+
+    #[tokio::test]
+    async fn test_pending_feedback_does_not_block_ping() {
+        let (mut client, _) = TestClient::connect(LATEST, json!({})).await;
+        let (feedback_id, _) = start_feedback_call(&mut client, json!({}), json!({})).await;
+
+        let ping_id = client.send_request("ping", json!({})).await;
+        let ping = tokio::time::timeout(Duration::from_secs(5), client.response(ping_id)).await.expect("ping blocked by pending feedback");
+        assert_eq!(ping["result"], json!({}));
+        // ping 响应先于 feedback 响应到达
+        assert!(client.responses.iter().all(|response| response["id"] != feedback_id));
+
+        client.send(json!({ "jsonrpc": "2.0", "method": "notifications/cancelled", "params": { "requestId": feedback_id } })).await;
+        client.response(feedback_id).await;
+    }
+
+    #[tokio::test]
+    async fn test_feedback_reports_progress_with_client_token() {
+        let (mut client, _) = TestClient::connect(LATEST, json!({})).await;
+        let (id, _) = start_feedback_call(&mut client, json!({}), json!({ "progressToken": "feedback-progress" })).await;
+        // 取消后等待响应，此前发出的通知都已收到
+        client.send(json!({ "jsonrpc": "2.0", "method": "notifications/cancelled", "params": { "requestId": id } })).await;
+        client.response(id).await;
+
+        let progress: Vec<&Value> = client.notifications.iter().filter(|n| n["method"] == "notifications/progress").collect();
+        assert_eq!(progress.len(), 1);
+        assert_eq!(progress[0]["params"]["progressToken"], "feedback-progress");
+        assert!(progress[0]["params"]["message"].as_str().unwrap().starts_with("Waiting for user"));
+    }
+
+    #[tokio::test]
+    async fn test_set_level_filters_log_messages() {
+        let (mut client, workspace) = connect_with_workspace().await;
+        let blocked_read = json!({ "name": "file_read", "arguments": { "path": "../outside.txt" } });
+
+        client.request("logging/setLevel", json!({ "level": "error" })).await;
+        client.request("tools/call", blocked_read.clone()).await;
+        assert!(client.notifications.iter().all(|n| n["method"] != "notifications/message"));
+
+        client.request("logging/setLevel", json!({ "level": "warning" })).await;
+        client.request("tools/call", blocked_read).await;
+        let messages: Vec<&Value> = client.notifications.iter().filter(|n| n["method"] == "notifications/message").collect();
+        assert_eq!(messages.len(), 1);
+        assert_eq!(messages[0]["params"]["level"], "warning");
+        assert_eq!(messages[0]["params"]["logger"], "file_read");
+        fs::remove_dir_all(&workspace).ok();
+    }
+
+    #[tokio::test]
+    async fn test_feedback_form_uses_client_elicitation() {
+        use_feedback_test_env();
+        let form = json!({
+            "ai_response": "Which branch?",
+            "fields": { "properties": { "branch": { "type": "string" } }, "required": ["branch"] }
+        });
+        let (mut client, _) = TestClient::connect(LATEST, json!({ "elicitation": {} })).await;
+
+        client.elicitation = json!({ "action": "accept", "content": { "branch": "main" } });
+        let response = client.request("tools/call", json!({ "name": "feedback", "arguments": form })).await;
+        assert_eq!(client.elicitations[0]["message"], "Which branch?");
+        assert_eq!(client.elicitations[0]["requestedSchema"]["properties"]["branch"]["type"], "string");
+        let structured = &response["result"]["structuredContent"];
+        assert_eq!(structured["type"], "feedback_response");
+        assert_eq!(structured["form_values"], json!({ "branch": "main" }));
+
+        for action in ["decline", "cancel"] {
+            client.elicitation = json!({ "action": action });
+            let response = client.request("tools/call", json!({ "name": "feedback", "arguments": form })).await;
+            assert_eq!(response["result"]["structuredContent"]["type"], "feedback_cancelled");
+        }
+        assert_eq!(client.elicitations.len(), 3);
+    }
+
+    #[tokio::test]
+    async fn test_feedback_form_falls_back_to_gui_when_elicitation_fails() {
+        let form = json!({ "fields": { "properties": { "branch": { "type": "string" } } } });
+        let (mut client, _) = TestClient::connect(LATEST, json!({ "elicitation": {} })).await;
+        let (id, request_path) = start_feedback_call(&mut client, form.clone(), json!({})).await;
+        assert_eq!(client.elicitations.len(), 1);
+        client.send(json!({ "jsonrpc": "2.0", "method": "notifications/cancelled", "params": { "requestId": id } })).await;
+        client.response(id).await;
+        assert!(!request_path.exists());
+
+        // 不支持 elicitation 的客户端直接使用 GUI
+        let (mut client, _) = TestClient::connect(LATEST, json!({})).await;
+        client.elicitation = json!({ "action": "accept", "content": { "branch": "main" } });
+        let (id, _) = start_feedback_call(&mut client, form, json!({})).await;
+        assert!(client.elicitations.is_empty());
+        client.send(json!({ "jsonrpc": "2.0", "method": "notifications/cancelled", "params": { "requestId": id } })).await;
+        client.response(id).await;
+    }
+
+    #[tokio::test]
+    async fn test_cancelled_socket_feedback_notifies_gui() {
+        let (mcp_io, mut gui_io) = tokio::io::duplex(1 << 16);
+        let token = CancellationToken::new();
+        let ctx = ToolContext::new(token.clone());
+        let request = build_feedback_request("s1", "done?", "Review", "cursor", &AiSource::from_string("cursor"));
+
+        let wait = wait_feedback_over_socket(mcp_io, &request, "s1", std::time::Instant::now(), None, &ctx);
+        let gui = async {
+            let received = feedback_ipc::read_frame(&mut gui_io).await.unwrap();
+            assert_eq!(received, Some(IpcMessage::Request { request: request.clone() }));
+            token.cancel();
+            feedback_ipc::read_frame(&mut gui_io).await.unwrap()
+        };
+        let (result, message) = tokio::join!(wait, gui);
+        assert_eq!(message, Some(IpcMessage::Cancel { session_id: "s1".to_string() }));
+        assert_eq!(result.unwrap().structured_content.unwrap()["type"], "feedback_cancelled");
+    }
+
+    #[tokio::test]
+    async fn test_protocol_version_negotiation() {
+        for version in SUPPORTED_PROTOCOL_VERSIONS {
+            let (client, response) = TestClient::connect(version, json!({})).await;
+            assert_eq!(response["result"]["protocolVersion"], version);
+            assert_eq!(client.server.session.protocol_version(), version);
+        }
+
+        let (client, response) = TestClient::connect("2999-01-01", json!({ "elicitation": {} })).await;
+        assert_eq!(response["result"]["protocolVersion"], LATEST);
+        assert!(client.server.session.supports_structured_output());
+        assert!(client.server.session.supports_elicitation());
+
+        // elicitation 自 2025-06-18 起才可用
+        let (client, _) = TestClient::connect("2025-03-26", json!({ "elicitation": {} })).await;
+        assert!(!client.server.session.supports_elicitation());
+
+        let session = McpSession::new("test");
+        assert!(!session.supports_tool_annotations());
+        session.set_negotiated(&model::ProtocolVersion::V_2025_03_26, model::ClientCapabilities::default());
+        assert!(session.supports_tool_annotations() && !session.supports_structured_output());
+    }
+
+    #[tokio::test]
+    async fn test_features_follow_negotiated_version() {
+        let (mut client, _) = TestClient::connect("2024-11-05", json!({})).await;
+        assert!(!client.server.session.supports_tool_annotations());
+
+        let response = client.request("tools/list", json!({})).await;
+        let system_info = response["result"]["tools"].as_array().unwrap().iter().find(|t| t["name"] == "system_info").unwrap();
+        assert!(system_info.get("outputSchema").is_none());
+        assert!(system_info.get("annotations").is_none());
+
+        let response = client.request("tools/call", json!({ "name": "system_info", "arguments": {} })).await;
+        assert!(response["result"].get("structuredContent").is_none());
+    }
+
+    #[test]
+    fn test_content_block_serialization() {
+        let session = McpSession::new("test");
+        session.set_negotiated(&model::ProtocolVersion::V_2025_06_18, model::ClientCapabilities::default());
+        let result = ToolResult::text("hello")
+            .with_content(ToolContent::image(b"abc", "image/png"))
+            .with_content(ToolContent::Resource {
+                uri: "file:///tmp/a.bin".to_string(),
+                mime_type: "application/octet-stream".to_string(),
+                text: None,
+                blob: Some("AAE=".to_string()),
+            })
+            .into_call_tool_result(&session);
+        let result = serde_json::to_value(result).unwrap();
+        assert_eq!(result["content"][1], json!({ "type": "image", "data": "YWJj", "mimeType": "image/png" }));
+        assert_eq!(result["content"][2]["type"], "resource");
+        assert_eq!(result["content"][2]["resource"]["blob"], "AAE=");
+        assert!(result["content"][2]["resource"].get("text").is_none());
+    }
+
+    #[test]
+    fn test_resource_link_serialization() {
+        let link = || {
+            ToolResult::text("found").with_content(ToolContent::ResourceLink {
+                uri: "file:///tmp/a.txt".to_string(),
+                name: "a.txt".to_string(),
+                description: Some("2 matching lines".to_string()),
+                mime_type: None,
+            })
+        };
+        let session = McpSession::new("test");
+        session.set_negotiated(&model::ProtocolVersion::V_2025_06_18, model::ClientCapabilities::default());
+        let result = serde_json::to_value(link().into_call_tool_result(&session)).unwrap();
+        assert_eq!(result["content"][1], json!({
+            "type": "resource_link",
+            "uri": "file:///tmp/a.txt",
+            "name": "a.txt",
+            "description": "2 matching lines"
+        }));
+
+        // 2025-06-18 之前的客户端收到链接地址文本
+        session.set_negotiated(&model::ProtocolVersion::V_2025_03_26, model::ClientCapabilities::default());
+        let result = serde_json::to_value(link().into_call_tool_result(&session)).unwrap();
+        assert_eq!(result["content"][1], json!({ "type": "text", "text": "file:///tmp/a.txt" }));
+    }
+
+    #[tokio::test]
+    async fn test_template_prompt_does_not_expand_argument_values() {
+        let prompt = TemplatePrompt {
+            name: "review".to_string(),
+            description: String::new(),
+            arguments: ["a", "b"].iter().map(|name| PromptArgument { name: name.to_string(), description: String::new(), required: false }).collect(),
+            template: "{a} / {b} / {unknown} / {{a}".to_string(),
+        };
+        let messages = prompt.get(&json!({ "a": "uses {b}", "b": "{a}" })).await.unwrap();
+        let message = serde_json::to_value(&messages[0]).unwrap();
+        assert_eq!(message["content"]["text"], "uses {b} / {a} / {unknown} / {uses {b}");
+        assert_eq!(substitute_placeholders("{x}}{", |_| Some("y".to_string())), "y}{");
+    }
+
+    #[tokio::test]
+    async fn test_workspace_file_resource() {
+        let dir = std::env::temp_dir().join(format!("mcp resource {}", uuid::Uuid::new_v4()));
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(dir.join("notes.md"), b"caf\xe9 cr\xe8me br\xfbl\xe9e\n").unwrap();
+        fs::write(dir.join("data.bin"), b"\x00\x01\x02").unwrap();
+
+        let resource = WorkspaceFileResource::new(dir.join("notes.md"));
+        assert!(resource.uri().starts_with("file:///") && resource.uri().contains("/mcp%20resource%20"), "{}", resource.uri());
+        assert_eq!(sandbox::file_uri_to_path(resource.uri()), Some(dir.join("notes.md")));
+        assert_eq!(resource.read().await.unwrap(), "café crème brûlée\n");
+        assert!(WorkspaceFileResource::new(dir.join("data.bin")).read().await.is_err());
+        fs::remove_dir_all(&dir).ok();
+    }
+
+    #[test]
+    fn test_environment_overrides_saved_timeout_settings() {
+        let saved = FeedbackTimeoutSettings {
+            timeout_seconds: Some(300),
+            on_timeout: Some("escalate".to_string()),
+            default_reply: Some("carry on".to_string()),
+        };
+        let merged = merge_feedback_timeout_settings(saved.clone(), |_| None);
+        assert_eq!(merged, json!({ "timeout_seconds": 300, "on_timeout": "escalate", "default_reply": "carry on" }));
+
+        let env = |name: &str| (name == "MCP_FEEDBACK_TIMEOUT").then(|| "60".to_string());
+        let timeout = FeedbackTimeout::resolve(&json!({}), &merge_feedback_timeout_settings(saved, env)).unwrap().unwrap();
+        assert_eq!((timeout.duration.as_secs(), timeout.policy), (60, TimeoutPolicy::Escalate));
+    }
+
+    #[test]
+    fn test_feedback_timeout_resolution() {
+        let global = json!({ "timeout_seconds": 600, "on_timeout": "cancel", "default_reply": null });
+        assert_eq!(FeedbackTimeout::resolve(&json!({}), &json!({})).unwrap(), None);
+
+        // 未指定参数时使用全局设置
+        let timeout = FeedbackTimeout::resolve(&json!({}), &global).unwrap().unwrap();
+        assert_eq!(timeout.duration, Duration::from_secs(600));
+        assert_eq!(timeout.policy, TimeoutPolicy::Cancel);
+        assert_eq!(timeout.default_reply, DEFAULT_TIMEOUT_REPLY);
+
+        // 工具参数优先，0 表示无限等待
+        let params = json!({ "timeout_seconds": 5, "on_timeout": "default_reply", "default_reply": "ship it" });
+        let timeout = FeedbackTimeout::resolve(&params, &global).unwrap().unwrap();
+        assert_eq!((timeout.duration.as_secs(), timeout.policy), (5, TimeoutPolicy::DefaultReply));
+        assert_eq!(timeout.default_reply, "ship it");
+        assert_eq!(FeedbackTimeout::resolve(&json!({ "timeout_seconds": 0 }), &global).unwrap(), None);
+
+        assert!(FeedbackTimeout::resolve(&json!({ "timeout_seconds": -1 }), &global).is_err());
+        assert!(FeedbackTimeout::resolve(&json!({ "on_timeout": "panic" }), &global).is_err());
+    }
+
+    #[test]
+    fn test_timeout_policy_results() {
+        let session = McpSession::new("test");
+        session.set_negotiated(&model::ProtocolVersion::V_2025_06_18, model::ClientCapabilities::default());
+        let timeout = |policy| FeedbackTimeout { duration: Duration::from_secs(30), policy, default_reply: "ok".to_string() };
+
+        let result = serde_json::to_value(timeout(TimeoutPolicy::DefaultReply).into_result().into_call_tool_result(&session)).unwrap();
+        assert_eq!(result["structuredContent"], json!({ "type": "feedback_response", "user_feedback": "ok", "timed_out": true }));
+        assert!(result.get("isError").is_none());
+
+        let result = serde_json::to_value(timeout(TimeoutPolicy::Cancel).into_result().into_call_tool_result(&session)).unwrap();
+        assert_eq!(result["structuredContent"]["type"], "feedback_cancelled");
+
+        let result = serde_json::to_value(timeout(TimeoutPolicy::Escalate).into_result().into_call_tool_result(&session)).unwrap();
+        assert_eq!(result["structuredContent"]["type"], "feedback_timeout");
+        assert_eq!(result["isError"], true);
+    }
+
+    #[test]
+    fn test_feedback_options_and_fields_parsing() {
+        let options = parse_feedback_options(&json!({ "options": ["a", { "value": "b", "label": "Option B" }] })).unwrap().unwrap();
+        assert_eq!(options[0], json!({ "value": "a", "label": "a", "description": null }));
+        assert_eq!(options[1]["label"], "Option B");
+        assert!(parse_feedback_options(&json!({})).unwrap().is_none());
+        assert!(parse_feedback_options(&json!({ "options": [] })).is_err());
+        assert!(parse_feedback_options(&json!({ "options": ["a", "a"] })).is_err());
+        assert!(parse_feedback_options(&json!({ "options": [1] })).is_err());
+
+        let fields = parse_feedback_fields(&json!({ "fields": {
+            "properties": { "name": { "type": "string" }, "level": { "type": "string", "enum": ["low", "high"] } },
+            "required": ["name"]
+        }})).unwrap().unwrap();
+        assert_eq!(fields["required"], json!(["name"]));
+        assert!(parse_feedback_fields(&json!({ "fields": { "properties": { "x": { "type": "array" } } } })).is_err());
+        assert!(parse_feedback_fields(&json!({ "fields": { "properties": { "x": { "type": "integer", "enum": ["1"] } } } })).is_err());
+        assert!(parse_feedback_fields(&json!({ "fields": { "properties": { "x": { "type": "string" } }, "required": ["y"] } })).is_err());
+    }
+
+    #[test]
+    fn test_validate_feedback_response() {
+        let request = json!({
+            "allowFreeText": false,
+            "options": [{ "value": "a" }, { "value": "b" }],
+            "multiSelect": false,
+            "fields": {
+                "type": "object",
+                "properties": { "name": { "type": "string" }, "count": { "type": "integer" }, "level": { "type": "string", "enum": ["low", "high"] } },
+                "required": ["name"]
+            }
+        });
+        let response = |selected: Option<Vec<&str>>, form: Option<Value>| FeedbackResponse {
+            feedback: String::new(),
+            selected_options: selected.map(|values| values.into_iter().map(String::from).collect()),
+            form_values: form,
+            attachments: Vec::new(),
+        };
+
+        assert!(validate_feedback_response(&request, &response(Some(vec!["a"]), Some(json!({ "name": "x", "count": 2 })))).is_ok());
+        assert!(validate_feedback_response(&request, &response(None, None)).is_err());
+        assert!(validate_feedback_response(&request, &response(Some(vec!["a", "b"]), Some(json!({ "name": "x" })))).is_err());
+        assert!(validate_feedback_response(&request, &response(Some(vec!["c"]), Some(json!({ "name": "x" })))).is_err());
+        assert!(validate_feedback_response(&request, &response(Some(vec!["a"]), Some(json!({ "name": " " })))).is_err());
+        assert!(validate_feedback_response(&request, &response(Some(vec!["a"]), Some(json!({ "name": "x", "count": 1.5 })))).is_err());
+        assert!(validate_feedback_response(&request, &response(Some(vec!["a"]), Some(json!({ "name": "x", "level": "mid" })))).is_err());
+        assert!(validate_feedback_response(&request, &response(Some(vec!["a"]), Some(json!({ "name": "x", "other": 1 })))).is_err());
+
+        // 普通文本反馈请求不接受选项和表单
+        assert!(validate_feedback_response(&json!({}), &response(None, None)).is_ok());
+        assert!(validate_feedback_response(&json!({}), &response(Some(vec!["a"]), None)).is_err());
+    }
+
+    #[test]
+    fn test_structured_feedback_result() {
+        let result = feedback_response_result(FeedbackResponse {
+            feedback: "go".to_string(),
+            selected_options: Some(vec!["b".to_string()]),
+            form_values: Some(json!({ "name": "x" })),
+            attachments: Vec::new(),
+        });
+        let structured = result.structured_content.clone().unwrap();
+        assert_eq!(structured, json!({ "type": "feedback_response", "user_feedback": "go", "selected_options": ["b"], "form_values": { "name": "x" } }));
+        let ToolContent::Text(text) = &result.content[0] else { panic!("expected text content") };
+        assert_eq!(serde_json::from_str::<Value>(text).unwrap(), structured);
+    }
+
+    #[test]
+    fn test_build_history_entry() {
+        let request = build_feedback_request("s1", "done?", "Review", "cursor", &AiSource::from_string("cursor"));
+        let answered = feedback_response_result(FeedbackResponse {
+            feedback: "go".to_string(),
+            selected_options: Some(vec!["b".to_string()]),
+            ..Default::default()
+        });
+        let entry = build_history_entry(&request, &answered, Duration::from_millis(1500));
+        assert_eq!(entry.session_id, "s1");
+        assert_eq!(entry.status, "answered");
+        assert_eq!(entry.user_feedback.as_deref(), Some("go"));
+        assert_eq!(entry.details, Some(json!({ "selected_options": ["b"] })));
+        assert_eq!(entry.duration_ms, 1500);
+
+        let timeout = FeedbackTimeout { duration: Duration::from_secs(5), policy: TimeoutPolicy::DefaultReply, default_reply: "ok".to_string() };
+        assert_eq!(build_history_entry(&request, &timeout.into_result(), Duration::ZERO).status, "timed_out");
+        let cancelled = build_history_entry(&request, &feedback_cancelled_result("closed"), Duration::ZERO);
+        assert_eq!((cancelled.status.as_str(), cancelled.user_feedback.as_deref(), cancelled.details), ("cancelled", Some("closed"), None));
+    }
 }
@@ -0,0 +1,376 @@
+use globset::{GlobBuilder, GlobSet, GlobSetBuilder};
+use std::fs;
+use std::path::{Component, Path, PathBuf};
+
+// 文件工具的工作区沙箱：只允许访问工作区根目录内的路径，并拒绝敏感文件
+//
+// 根目录来源 (按优先级)：
+// 1. 客户端通过 `roots/list` 提供的根目录 (配置了 MCP_WORKSPACE_ROOTS 时，只接受位于配置根目录之内的部分)
+// 2. MCP_WORKSPACE_ROOTS 环境变量 (按系统路径分隔符分隔)
+// 3. 仅 stdio 模式且设置了 MCP_WORKSPACE_FROM_CWD 时：服务器进程的当前工作目录
+//
+// 都没有时沙箱没有根目录，所有路径都会被拒绝
+
+/// 默认拒绝访问的敏感文件，额外的规则通过 MCP_DENY_GLOBS 环境变量添加 (逗号分隔)
+const DEFAULT_DENY_GLOBS: [&str; 22] = [
+    "**/.ssh/**",
+    "**/.gnupg/**",
+    "**/.aws/**",
+    "**/.azure/**",
+    "**/.kube/**",
+    "**/.config/gcloud/**",
+    "**/.docker/config.json",
+    "**/.netrc",
+    "**/.npmrc",
+    "**/.pypirc",
+    "**/.git-credentials",
+    "**/.env",
+    "**/.env.*",
+    "**/*.pem",
+    "**/*.key",
+    "**/*.p12",
+    "**/*.pfx",
+    "**/id_rsa*",
+    "**/id_ed25519*",
+    "**/id_ecdsa*",
+    "/etc/shadow",
+    "/etc/sudoers",
+];
+
+/// 路径访问被拒绝的原因，作为工具错误返回给模型
+#[derive(Debug, Clone, PartialEq)]
+pub enum SandboxError {
+    NoRoots,
+    OutsideRoots { path: PathBuf, roots: Vec<PathBuf> },
+    Denied { path: PathBuf, pattern: String },
+    InvalidPath { path: String, reason: String },
+}
+
+impl std::fmt::Display for SandboxError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            SandboxError::NoRoots => {
+                write!(f, "No workspace roots configured; set MCP_WORKSPACE_ROOTS or expose roots from the client")
+            }
+            SandboxError::OutsideRoots { path, roots } => {
+                let roots: Vec<String> = roots.iter().map(|root| root.display().to_string()).collect();
+                write!(f, "Access denied: {} is outside the workspace roots ({})", path.display(), roots.join(", "))
+            }
+            SandboxError::Denied { path, pattern } => {
+                write!(f, "Access denied: {} matches the sensitive file pattern '{}'", path.display(), pattern)
+            }
+            SandboxError::InvalidPath { path, reason } => write!(f, "Invalid path '{}': {}", path, reason),
+        }
+    }
+}
+
+impl std::error::Error for SandboxError {}
+
+#[derive(Debug, Clone)]
+pub struct Sandbox {
+    roots: Vec<PathBuf>,
+    deny_patterns: Vec<String>,
+    deny: GlobSet,
+}
+
+impl Default for Sandbox {
+    fn default() -> Self {
+        Self::for_client(None, false)
+    }
+}
+
+impl Sandbox {
+    /// 根目录会被规范化 (解析符号链接)，不存在的根目录会被忽略
+    pub fn new(roots: Vec<PathBuf>, extra_deny_globs: &[String]) -> Self {
+        let roots = roots.into_iter().filter_map(|root| fs::canonicalize(root).ok()).collect();
+
+        let mut deny_patterns = Vec::new();
+        let mut builder = GlobSetBuilder::new();
+        let patterns = DEFAULT_DENY_GLOBS.iter().map(|pattern| pattern.to_string()).chain(extra_deny_globs.iter().cloned());
+        for pattern in patterns {
+            // macOS 和 Windows 的文件系统通常不区分大小写
+            match GlobBuilder::new(&pattern).case_insensitive(cfg!(any(windows, target_os = "macos"))).build() {
+                Ok(glob) => {
+                    builder.add(glob);
+                    deny_patterns.push(pattern);
+                }
+                Err(e) => eprintln!("⚠️ Ignoring invalid deny glob '{}': {}", pattern, e),
+            }
+        }
+        let deny = builder.build().unwrap_or_else(|_| GlobSet::empty());
+        Self { roots, deny_patterns, deny }
+    }
+
+    /// 按环境变量配置和客户端提供的根目录创建沙箱，cwd_fallback 为 true 时都没有提供则使用当前工作目录
+    pub fn for_client(client_roots: Option<Vec<PathBuf>>, cwd_fallback: bool) -> Self {
+        let configured = Self::new(get_configured_roots(), &get_extra_deny_globs());
+        let client = client_roots.filter(|roots| !roots.is_empty()).map(|roots| Self::new(roots, &[]).roots);
+
+        let roots = match client {
+            Some(client) if configured.roots.is_empty() => client,
+            Some(client) => {
+                let accepted: Vec<PathBuf> = client.into_iter().filter(|root| configured.contains(root)).collect();
+                if accepted.is_empty() {
+                    eprintln!("⚠️ Client roots are all outside MCP_WORKSPACE_ROOTS, using the configured roots");
+                    configured.roots.clone()
+                } else {
+                    accepted
+                }
+            }
+            None if configured.roots.is_empty() && cwd_fallback => Self::new(std::env::current_dir().into_iter().collect(), &[]).roots,
+            None => configured.roots.clone(),
+        };
+        Self { roots, ..configured }
+    }
+
+    fn contains(&self, path: &Path) -> bool {
+        self.roots.iter().any(|root| path.starts_with(root))
+    }
+
+    /// 用于显示的路径：位于根目录内时为相对路径
+    pub fn display_path(&self, path: &Path) -> String {
+        self.roots
+            .iter()
+            .find_map(|root| path.strip_prefix(root).ok())
+            .filter(|relative| !relative.as_os_str().is_empty())
+            .unwrap_or(path)
+            .display()
+            .to_string()
+    }
+
+    /// 返回匹配的敏感文件规则
+    pub fn denied_pattern(&self, path: &Path) -> Option<&str> {
+        self.deny.matches(path).first().map(|index| self.deny_patterns[*index].as_str())
+    }
+
+    /// 解析工具参数中的路径：相对路径相对于第一个根目录，`~` 展开为用户主目录，
+    /// 符号链接解析后必须仍在根目录之内，且不能匹配敏感文件规则。目标文件可以不存在 (用于写入)
+    pub fn resolve(&self, path: &str) -> Result<PathBuf, SandboxError> {
+        let invalid = |reason: &str| SandboxError::InvalidPath { path: path.to_string(), reason: reason.to_string() };
+        if path.trim().is_empty() {
+            return Err(invalid("path is empty"));
+        }
+        let Some(first_root) = self.roots.first() else {
+            return Err(SandboxError::NoRoots);
+        };
+
+        let requested = expand_home(path);
+        let absolute = if requested.is_absolute() { requested } else { first_root.join(requested) };
+        let resolved = resolve_symlinks(&absolute).map_err(invalid)?;
+
+        // 同时检查请求的路径和解析后的路径，指向敏感文件的符号链接和名为敏感文件的链接都会被拒绝
+        for candidate in [&absolute, &resolved] {
+            if let Some(pattern) = self.denied_pattern(candidate) {
+                return Err(SandboxError::Denied { path: candidate.clone(), pattern: pattern.to_string() });
+            }
+        }
+        if !self.contains(&resolved) {
+            return Err(SandboxError::OutsideRoots { path: resolved, roots: self.roots.clone() });
+        }
+        Ok(resolved)
+    }
+}
+
+/// stdio 模式下是否在没有配置根目录时使用当前工作目录 (MCP_WORKSPACE_FROM_CWD 环境变量)。
+/// GUI 中的 HTTP 服务器的工作目录与客户端的工作区无关，不使用此设置
+pub fn is_cwd_fallback_enabled() -> bool {
+    std::env::var("MCP_WORKSPACE_FROM_CWD").is_ok_and(|value| matches!(value.trim(), "1" | "true" | "yes"))
+}
+
+/// MCP_WORKSPACE_ROOTS 环境变量中配置的根目录
+fn get_configured_roots() -> Vec<PathBuf> {
+    match std::env::var_os("MCP_WORKSPACE_ROOTS") {
+        Some(value) => std::env::split_paths(&value).filter(|path| !path.as_os_str().is_empty()).collect(),
+        None => Vec::new(),
+    }
+}
+
+fn get_extra_deny_globs() -> Vec<String> {
+    std::env::var("MCP_DENY_GLOBS")
+        .map(|value| value.split(',').map(str::trim).filter(|s| !s.is_empty()).map(str::to_string).collect())
+        .unwrap_or_default()
+}
+
+fn expand_home(path: &str) -> PathBuf {
+    let home = || std::env::var_os(if cfg!(windows) { "USERPROFILE" } else { "HOME" }).map(PathBuf::from);
+    if path == "~" {
+        if let Some(home) = home() {
+            return home;
+        }
+    }
+    if let Some(rest) = path.strip_prefix("~/").or_else(|| path.strip_prefix("~\\")) {
+        if let Some(home) = home() {
+            return home.join(rest);
+        }
+    }
+    PathBuf::from(path)
+}
+
+// 规范化路径中已存在的部分 (解析符号链接和 `..`)，再拼接不存在的部分。
+// 不存在的部分中出现 `..` 时无法确定最终位置；悬空的符号链接在写入时会指向根目录之外，两者都会被拒绝
+fn resolve_symlinks(path: &Path) -> Result<PathBuf, &'static str> {
+    let mut existing = path;
+    let mut missing = Vec::new();
+    loop {
+        if let Ok(canonical) = fs::canonicalize(existing) {
+            let mut resolved = canonical;
+            for component in missing.iter().rev() {
+                match component {
+                    Component::Normal(name) => resolved.push(name),
+                    Component::CurDir => {}
+                    _ => return Err("'..' cannot follow a directory that does not exist"),
+                }
+            }
+            return Ok(resolved);
+        }
+        if fs::symlink_metadata(existing).is_ok() {
+            return Err("the path contains a dangling symbolic link");
+        }
+        let mut components = existing.components();
+        missing.push(components.next_back().ok_or("the path does not exist")?);
+        existing = components.as_path();
+    }
+}
+
+/// 把客户端 `roots/list` 返回的 `file://` URI 转换为本地路径
+pub fn file_uri_to_path(uri: &str) -> Option<PathBuf> {
+    let rest = uri.strip_prefix("file://")?;
+    let rest = rest.strip_prefix("localhost").unwrap_or(rest);
+    let bytes = rest.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        if bytes[i] == b'%' && i + 2 < bytes.len() {
+            if let Some(byte) = std::str::from_utf8(&bytes[i + 1..i + 3]).ok().and_then(|hex| u8::from_str_radix(hex, 16).ok()) {
+                decoded.push(byte);
+                i += 3;
+                continue;
+            }
+        }
+        decoded.push(bytes[i]);
+        i += 1;
+    }
+    let path = String::from_utf8(decoded).ok()?;
+    // Windows: file:///C:/work -> C:/work
+    let path = match path.as_bytes() {
+        [b'/', drive, b':', ..] if cfg!(windows) && drive.is_ascii_alphabetic() => path[1..].to_string(),
+        _ => path,
+    };
+    Some(PathBuf::from(path))
+}
+
+/// 把本地路径转换为 `file://` URI：Windows 路径转换为 `file:///C:/...` 或 `file://server/share/...`，
+/// 路径中的空格、`#`、`%` 和非 ASCII 字符按 UTF-8 百分号编码
+pub fn path_to_file_uri(path: &Path) -> String {
+    let path = path.to_string_lossy();
+    if cfg!(windows) {
+        file_uri_from_slashed(&path.replace('\\', "/"))
+    } else {
+        file_uri_from_slashed(&path)
+    }
+}
+
+// 以 `/` 分隔的路径转换为 URI，Windows 路径需先把 `\` 替换为 `/`
+fn file_uri_from_slashed(path: &str) -> String {
+    // Windows 的 canonicalize 返回 \\?\C:\... 或 \\?\UNC\server\share\... 形式的路径
+    let path = match path.strip_prefix("//?/UNC/") {
+        Some(unc) => format!("//{}", unc),
+        None => path.strip_prefix("//?/").unwrap_or(path).to_string(),
+    };
+    // UNC 路径的服务器名作为 URI 的 authority
+    let (authority, path) = match path.strip_prefix("//") {
+        Some(unc) => match unc.find('/') {
+            Some(end) => (unc[..end].to_string(), unc[end..].to_string()),
+            None => (unc.to_string(), "/".to_string()),
+        },
+        None => (String::new(), path),
+    };
+    let mut uri = format!("file://{}", authority);
+    if !path.starts_with('/') {
+        uri.push('/');
+    }
+    for byte in path.bytes() {
+        match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' | b'/' | b':' => uri.push(byte as char),
+            _ => uri.push_str(&format!("%{:02X}", byte)),
+        }
+    }
+    uri
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn workspace() -> (PathBuf, Sandbox) {
+        let root = std::env::temp_dir().join(format!("mcp_sandbox_{}", uuid::Uuid::new_v4()));
+        fs::create_dir_all(root.join("src")).unwrap();
+        fs::write(root.join("src/main.rs"), "fn main() {}").unwrap();
+        let sandbox = Sandbox::new(vec![root.clone()], &["**/secrets/**".to_string()]);
+        (fs::canonicalize(&root).unwrap(), sandbox)
+    }
+
+    #[test]
+    fn test_resolve_within_roots() {
+        let (root, sandbox) = workspace();
+        assert_eq!(sandbox.resolve("src/main.rs").unwrap(), root.join("src/main.rs"));
+        assert_eq!(sandbox.resolve(&root.join("src/../src/main.rs").to_string_lossy()).unwrap(), root.join("src/main.rs"));
+        // 不存在的文件 (写入目标) 也可以解析
+        assert_eq!(sandbox.resolve("src/new/file.rs").unwrap(), root.join("src/new/file.rs"));
+        assert!(matches!(sandbox.resolve("src/new/../../../x"), Err(SandboxError::InvalidPath { .. })));
+
+        assert!(matches!(sandbox.resolve("../outside.txt"), Err(SandboxError::OutsideRoots { .. })));
+        assert!(matches!(sandbox.resolve("/etc/hostname"), Err(SandboxError::OutsideRoots { .. })));
+        assert!(matches!(sandbox.resolve("~/.ssh/id_rsa"), Err(SandboxError::Denied { .. })));
+        assert!(matches!(sandbox.resolve(".env"), Err(SandboxError::Denied { .. })));
+        assert!(matches!(sandbox.resolve("config/secrets/token.txt"), Err(SandboxError::Denied { .. })));
+        assert_eq!(sandbox.display_path(&root.join("src/main.rs")), Path::new("src/main.rs").display().to_string());
+        fs::remove_dir_all(&root).ok();
+    }
+
+    #[test]
+    fn test_no_roots_denies_every_path() {
+        let (root, _) = workspace();
+        // 不存在的根目录被忽略
+        let sandbox = Sandbox::new(vec![root.join("missing")], &[]);
+        for path in ["src/main.rs", &root.join("src/main.rs").to_string_lossy(), "~/notes.txt"] {
+            assert_eq!(sandbox.resolve(path), Err(SandboxError::NoRoots));
+        }
+        assert!(SandboxError::NoRoots.to_string().contains("set MCP_WORKSPACE_ROOTS or expose roots from the client"));
+        fs::remove_dir_all(&root).ok();
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_symlinks_cannot_escape_roots() {
+        let (root, sandbox) = workspace();
+        std::os::unix::fs::symlink("/etc", root.join("etc")).unwrap();
+        std::os::unix::fs::symlink(root.join("src/main.rs"), root.join("link.rs")).unwrap();
+        std::os::unix::fs::symlink(root.join("src/main.rs"), root.join("server.key")).unwrap();
+
+        let error = sandbox.resolve("etc/hostname").unwrap_err();
+        assert!(error.to_string().contains("outside the workspace roots"), "{}", error);
+        assert_eq!(sandbox.resolve("link.rs").unwrap(), root.join("src/main.rs"));
+        assert!(matches!(sandbox.resolve("server.key"), Err(SandboxError::Denied { .. })));
+        std::os::unix::fs::symlink("/tmp/mcp_sandbox_missing_target", root.join("dangling")).unwrap();
+        assert!(matches!(sandbox.resolve("dangling"), Err(SandboxError::InvalidPath { .. })));
+        fs::remove_dir_all(&root).ok();
+    }
+
+    #[test]
+    fn test_file_uri_to_path() {
+        assert_eq!(file_uri_to_path("file:///home/me/my%20project"), Some(PathBuf::from("/home/me/my project")));
+        assert_eq!(file_uri_to_path("file://localhost/srv/app"), Some(PathBuf::from("/srv/app")));
+        assert_eq!(file_uri_to_path("https://example.com"), None);
+    }
+
+    #[test]
+    fn test_path_to_file_uri() {
+        let path = Path::new("/home/me/my project/#1 résumé.md");
+        assert_eq!(path_to_file_uri(path), "file:///home/me/my%20project/%231%20r%C3%A9sum%C3%A9.md");
+        assert_eq!(file_uri_to_path(&path_to_file_uri(path)), Some(path.to_path_buf()));
+        assert_eq!(file_uri_from_slashed("C:/Users/me/a b.txt"), "file:///C:/Users/me/a%20b.txt");
+        assert_eq!(file_uri_from_slashed("//?/C:/work/x.rs"), "file:///C:/work/x.rs");
+        assert_eq!(file_uri_from_slashed("//?/UNC/server/share/x.rs"), "file://server/share/x.rs");
+    }
+}
@@ -0,0 +1,228 @@
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::HashMap;
+use std::fs;
+use std::path::PathBuf;
+
+// 快捷回复模板：与窗口配置一起保存在共享存储目录，GUI 通过 Tauri 命令管理
+
+const SHORTCUT_MODIFIERS: [&str; 4] = ["Ctrl", "Alt", "Shift", "Meta"];
+
+/// 快捷回复模板，content 中可以使用 `{context}`、`{source}` 等变量
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ReplyTemplate {
+    #[serde(default)]
+    pub id: String,
+    pub name: String,
+    pub content: String,
+    /// 快捷键，如 `Alt+1`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub shortcut: Option<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TemplateLibrary {
+    #[serde(default)]
+    pub templates: Vec<ReplyTemplate>,
+    /// AI 来源 (MCP_SOURCE) 对应的默认模板 id，打开会话时预填到输入框
+    #[serde(default)]
+    pub source_defaults: HashMap<String, String>,
+}
+
+impl Default for TemplateLibrary {
+    fn default() -> Self {
+        let template = |id: &str, name: &str, content: &str, shortcut: &str| ReplyTemplate {
+            id: id.to_string(),
+            name: name.to_string(),
+            content: content.to_string(),
+            shortcut: Some(shortcut.to_string()),
+        };
+        TemplateLibrary {
+            templates: vec![
+                template("continue", "Continue", "continue", "Alt+1"),
+                template("run-tests", "Run the tests first", "Run the tests first and fix any failures before continuing.", "Alt+2"),
+                template("summarize", "Stop and summarize", "Stop here and summarize what you changed in {context}.", "Alt+3"),
+            ],
+            source_defaults: HashMap::new(),
+        }
+    }
+}
+
+impl TemplateLibrary {
+    /// 新增或更新模板，id 为空时生成新 id
+    pub fn upsert(&mut self, mut template: ReplyTemplate) -> Result<ReplyTemplate> {
+        template.name = template.name.trim().to_string();
+        if template.name.is_empty() {
+            return Err(anyhow::anyhow!("Template name cannot be empty"));
+        }
+        if template.content.trim().is_empty() {
+            return Err(anyhow::anyhow!("Template content cannot be empty"));
+        }
+        template.shortcut = match template.shortcut.as_deref().map(str::trim).filter(|s| !s.is_empty()) {
+            Some(shortcut) => Some(normalize_shortcut(shortcut)?),
+            None => None,
+        };
+        if template.id.trim().is_empty() {
+            template.id = uuid::Uuid::new_v4().to_string();
+        }
+
+        if let Some(shortcut) = &template.shortcut {
+            if let Some(other) = self.templates.iter().find(|t| t.id != template.id && t.shortcut.as_ref() == Some(shortcut)) {
+                return Err(anyhow::anyhow!("Shortcut {} is already used by '{}'", shortcut, other.name));
+            }
+        }
+
+        match self.templates.iter_mut().find(|t| t.id == template.id) {
+            Some(existing) => *existing = template.clone(),
+            None => self.templates.push(template.clone()),
+        }
+        Ok(template)
+    }
+
+    /// 删除模板，同时清除引用它的来源默认设置
+    pub fn remove(&mut self, id: &str) -> bool {
+        let before = self.templates.len();
+        self.templates.retain(|t| t.id != id);
+        self.source_defaults.retain(|_, template_id| template_id != id);
+        self.templates.len() != before
+    }
+
+    pub fn set_source_default(&mut self, ai_source: &str, template_id: Option<String>) -> Result<()> {
+        match template_id.filter(|id| !id.is_empty()) {
+            Some(id) => {
+                if !self.templates.iter().any(|t| t.id == id) {
+                    return Err(anyhow::anyhow!("Template '{}' not found", id));
+                }
+                self.source_defaults.insert(ai_source.to_string(), id);
+            }
+            None => {
+                self.source_defaults.remove(ai_source);
+            }
+        }
+        Ok(())
+    }
+
+    pub fn get(&self, id: &str) -> Option<&ReplyTemplate> {
+        self.templates.iter().find(|t| t.id == id)
+    }
+}
+
+pub fn get_templates_path() -> PathBuf {
+    let mut path = crate::get_shared_storage_dir();
+    fs::create_dir_all(&path).ok();
+    path.push("reply_templates.json");
+    path
+}
+
+pub fn load_library() -> TemplateLibrary {
+    match fs::read_to_string(get_templates_path()) {
+        Ok(content) => serde_json::from_str(&content).unwrap_or_else(|e| {
+            eprintln!("❌ Failed to parse reply templates: {}, using defaults", e);
+            TemplateLibrary::default()
+        }),
+        Err(_) => TemplateLibrary::default(),
+    }
+}
+
+pub fn save_library(library: &TemplateLibrary) -> Result<()> {
+    fs::write(get_templates_path(), serde_json::to_string_pretty(library)?)?;
+    Ok(())
+}
+
+// 统一快捷键格式：修饰键按 Ctrl/Alt/Shift/Meta 顺序排列，单字符按键大写
+fn normalize_shortcut(shortcut: &str) -> Result<String> {
+    let mut modifiers = Vec::new();
+    let mut key = None;
+    for part in shortcut.split('+').map(str::trim) {
+        let modifier = match part.to_lowercase().as_str() {
+            "ctrl" | "control" => Some("Ctrl"),
+            "alt" | "option" => Some("Alt"),
+            "shift" => Some("Shift"),
+            "meta" | "cmd" | "command" => Some("Meta"),
+            _ => None,
+        };
+        match modifier {
+            Some(modifier) if !modifiers.contains(&modifier) => modifiers.push(modifier),
+            Some(_) => return Err(anyhow::anyhow!("Duplicate modifier in shortcut '{}'", shortcut)),
+            None if key.is_none() && !part.is_empty() => key = Some(part),
+            None => return Err(anyhow::anyhow!("Invalid shortcut '{}'", shortcut)),
+        }
+    }
+
+    let key = key.ok_or_else(|| anyhow::anyhow!("Shortcut '{}' has no key", shortcut))?;
+    // 不带修饰键的快捷键会和正常输入冲突，Shift+Enter 已用于发送
+    if modifiers.iter().all(|m| *m == "Shift") {
+        return Err(anyhow::anyhow!("Shortcut '{}' needs Ctrl, Alt or Meta", shortcut));
+    }
+    modifiers.sort_by_key(|m| SHORTCUT_MODIFIERS.iter().position(|x| x == m));
+    let key = if key.chars().count() == 1 { key.to_uppercase() } else { key.to_string() };
+    Ok(format!("{}+{}", modifiers.join("+"), key))
+}
+
+/// 用会话信息替换模板变量，未知变量保持原样；变量值 (如 AI 提供的 context) 中的占位符不会被展开
+pub fn render_template(content: &str, session: &Value) -> String {
+    let now = chrono::Local::now();
+    let text = |key: &str| session[key].as_str().unwrap_or_default().to_string();
+    let variables = [
+        ("context", text("context")),
+        ("source", text("aiSource")),
+        ("source_display", text("aiSourceDisplay")),
+        ("session_id", text("sessionId")),
+        ("date", now.format("%Y-%m-%d").to_string()),
+        ("time", now.format("%H:%M").to_string()),
+    ];
+
+    crate::mcp_server::substitute_placeholders(content, |name| {
+        variables.iter().find(|(variable, _)| *variable == name).map(|(_, value)| value.clone())
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn template(id: &str, shortcut: Option<&str>) -> ReplyTemplate {
+        ReplyTemplate {
+            id: id.to_string(),
+            name: format!("Reply {}", id),
+            content: "text".to_string(),
+            shortcut: shortcut.map(str::to_string),
+        }
+    }
+
+    #[test]
+    fn test_template_library_editing() {
+        let mut library = TemplateLibrary::default();
+        let saved = library.upsert(template("", Some("ctrl + shift + r"))).unwrap();
+        assert!(!saved.id.is_empty());
+        assert_eq!(saved.shortcut.as_deref(), Some("Ctrl+Shift+R"));
+
+        // 快捷键冲突、缺少修饰键和空名称都会被拒绝
+        assert!(library.upsert(template("other", Some("Shift+Ctrl+r"))).is_err());
+        assert!(library.upsert(template("other", Some("r"))).is_err());
+        assert!(library.upsert(template("other", Some("Shift+R"))).is_err());
+        assert!(library.upsert(ReplyTemplate { name: " ".to_string(), ..template("other", None) }).is_err());
+
+        library.set_source_default("cursor", Some(saved.id.clone())).unwrap();
+        assert!(library.set_source_default("augment", Some("missing".to_string())).is_err());
+        assert!(library.remove(&saved.id));
+        assert!(library.source_defaults.is_empty());
+    }
+
+    #[test]
+    fn test_render_template() {
+        let session = json!({ "context": "Refactor", "aiSource": "cursor", "aiSourceDisplay": "Cursor AI" });
+        assert_eq!(
+            render_template("Summarize {context} for {source_display} ({source}) {unknown}", &session),
+            "Summarize Refactor for Cursor AI (cursor) {unknown}"
+        );
+
+        // AI 提供的 context 中的占位符保持原样
+        let session = json!({ "context": "leak {session_id}", "sessionId": "secret" });
+        assert_eq!(render_template("{context} [{session_id}]", &session), "leak {session_id} [secret]");
+    }
+}
@@ -0,0 +1,577 @@
+use anyhow::Result;
+use pulldown_cmark::{Event, Options, Parser, Tag, TagEnd};
+use ratatui::crossterm::event::{self, Event as TermEvent, KeyCode, KeyEvent, KeyEventKind, KeyModifiers};
+use ratatui::layout::{Constraint, Layout, Position, Rect};
+use ratatui::style::{Color, Style, Stylize};
+use ratatui::text::{Line, Span, Text};
+use ratatui::widgets::{Block, List, ListItem, ListState, Paragraph, Wrap};
+use ratatui::{DefaultTerminal, Frame};
+use serde_json::Value;
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
+
+use crate::feedback_ipc::FeedbackResponse;
+use crate::feedback_store::{self, PendingSession};
+
+// 终端界面 (cc-custom-mcp tui)：在无法运行 GUI 的远程机器上 (如 tmux 中) 查看和回复反馈请求
+
+const REFRESH_INTERVAL: Duration = Duration::from_secs(2);
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Mode {
+    Browse,
+    Compose,
+    ConfirmCancel,
+}
+
+/// 每个会话的回复草稿，切换会话时保留
+#[derive(Debug, Default)]
+struct Draft {
+    text: String,
+    selected_options: Vec<String>,
+}
+
+struct App {
+    rt: tokio::runtime::Runtime,
+    sessions: Vec<PendingSession>,
+    list_state: ListState,
+    drafts: HashMap<String, Draft>,
+    mode: Mode,
+    scroll: u16,
+    status: String,
+    last_refresh: Instant,
+    quit: bool,
+}
+
+pub fn run() -> Result<()> {
+    let mut app = App {
+        rt: tokio::runtime::Runtime::new()?,
+        sessions: Vec::new(),
+        list_state: ListState::default(),
+        drafts: HashMap::new(),
+        mode: Mode::Browse,
+        scroll: 0,
+        status: String::new(),
+        last_refresh: Instant::now(),
+        quit: false,
+    };
+    app.refresh();
+    app.status.clear();
+
+    let mut terminal = ratatui::try_init()?;
+    let result = app.event_loop(&mut terminal);
+    ratatui::restore();
+    result
+}
+
+impl App {
+    fn event_loop(&mut self, terminal: &mut DefaultTerminal) -> Result<()> {
+        while !self.quit {
+            terminal.draw(|frame| self.draw(frame))?;
+            if event::poll(Duration::from_millis(250))? {
+                if let TermEvent::Key(key) = event::read()? {
+                    if key.kind == KeyEventKind::Press {
+                        self.handle_key(key);
+                    }
+                }
+            }
+            if self.last_refresh.elapsed() >= REFRESH_INTERVAL {
+                self.refresh();
+            }
+        }
+        Ok(())
+    }
+
+    fn current(&self) -> Option<&PendingSession> {
+        self.list_state.selected().and_then(|index| self.sessions.get(index))
+    }
+
+    fn current_id(&self) -> Option<String> {
+        self.current().map(|session| session.session_id().to_string())
+    }
+
+    fn draft(&mut self) -> Option<&mut Draft> {
+        let session_id = self.current_id()?;
+        Some(self.drafts.entry(session_id).or_default())
+    }
+
+    // 重新加载待处理的请求，保持当前选中的会话
+    fn refresh(&mut self) {
+        let selected = self.current_id();
+        let previous = self.sessions.len();
+        self.sessions = self.rt.block_on(feedback_store::list_pending_sessions());
+        self.last_refresh = Instant::now();
+
+        let ids: Vec<&str> = self.sessions.iter().map(PendingSession::session_id).collect();
+        self.drafts.retain(|session_id, _| ids.contains(&session_id.as_str()));
+        let index = selected
+            .and_then(|selected| ids.iter().position(|id| *id == selected))
+            .or_else(|| (!ids.is_empty()).then(|| self.list_state.selected().unwrap_or(0).min(ids.len() - 1)));
+        if index != self.list_state.selected() {
+            self.scroll = 0;
+        }
+        self.list_state.select(index);
+
+        if self.sessions.len() > previous {
+            self.status = format!("🔔 {} pending feedback request(s)", self.sessions.len());
+        }
+        // 正在回复的会话已在别处处理
+        if self.current().is_none() && self.mode != Mode::Browse {
+            self.mode = Mode::Browse;
+        }
+    }
+
+    fn select(&mut self, offset: isize) {
+        if self.sessions.is_empty() {
+            return;
+        }
+        let index = self.list_state.selected().unwrap_or(0) as isize + offset;
+        self.list_state.select(Some(index.clamp(0, self.sessions.len() as isize - 1) as usize));
+        self.scroll = 0;
+    }
+
+    fn handle_key(&mut self, key: KeyEvent) {
+        if key.modifiers.contains(KeyModifiers::CONTROL) && key.code == KeyCode::Char('c') {
+            self.quit = true;
+            return;
+        }
+        match self.mode {
+            Mode::Browse => self.handle_browse_key(key),
+            Mode::Compose => self.handle_compose_key(key),
+            Mode::ConfirmCancel => {
+                self.mode = Mode::Browse;
+                if key.code == KeyCode::Char('y') {
+                    self.cancel_current();
+                } else {
+                    self.status = "Cancel aborted".to_string();
+                }
+            }
+        }
+    }
+
+    fn handle_browse_key(&mut self, key: KeyEvent) {
+        match key.code {
+            KeyCode::Char('q') | KeyCode::Esc => self.quit = true,
+            KeyCode::Up | KeyCode::Char('k') => self.select(-1),
+            KeyCode::Down | KeyCode::Char('j') => self.select(1),
+            KeyCode::PageUp => self.scroll = self.scroll.saturating_sub(10),
+            KeyCode::PageDown => self.scroll = self.scroll.saturating_add(10),
+            KeyCode::Char('r') => {
+                self.refresh();
+                self.status = "Refreshed".to_string();
+            }
+            KeyCode::Enter | KeyCode::Char('i') if self.current().is_some() => self.mode = Mode::Compose,
+            KeyCode::Char('s') if self.current().is_some() => self.submit_current(),
+            KeyCode::Char('c') if self.current().is_some() => {
+                self.mode = Mode::ConfirmCancel;
+                self.status = "Cancel this feedback request? (y/N)".to_string();
+            }
+            KeyCode::Char(digit @ '1'..='9') => self.toggle_option(digit as usize - '1' as usize),
+            _ => {}
+        }
+    }
+
+    fn handle_compose_key(&mut self, key: KeyEvent) {
+        let Some(draft) = self.draft() else {
+            return;
+        };
+        match key.code {
+            KeyCode::Esc => self.mode = Mode::Browse,
+            // Alt+Enter 或 Ctrl+J 换行，Enter 发送
+            KeyCode::Enter if key.modifiers.contains(KeyModifiers::ALT) => draft.text.push('\n'),
+            KeyCode::Char('j') if key.modifiers.contains(KeyModifiers::CONTROL) => draft.text.push('\n'),
+            KeyCode::Enter => self.submit_current(),
+            KeyCode::Backspace => {
+                draft.text.pop();
+            }
+            KeyCode::Char(c) if !key.modifiers.contains(KeyModifiers::CONTROL) => draft.text.push(c),
+            _ => {}
+        }
+    }
+
+    fn toggle_option(&mut self, index: usize) {
+        let Some(session) = self.current() else {
+            return;
+        };
+        let Some(value) = session.request["options"][index]["value"].as_str().map(str::to_string) else {
+            return;
+        };
+        let multi_select = session.request["multiSelect"].as_bool().unwrap_or(false);
+        if let Some(draft) = self.draft() {
+            if draft.selected_options.contains(&value) {
+                draft.selected_options.retain(|selected| *selected != value);
+            } else if multi_select {
+                draft.selected_options.push(value);
+            } else {
+                draft.selected_options = vec![value];
+            }
+        }
+    }
+
+    fn submit_current(&mut self) {
+        let Some(session) = self.current().cloned() else {
+            return;
+        };
+        let draft = self.drafts.get(session.session_id());
+        let response = match build_response(&session.request, draft) {
+            Ok(response) => response,
+            Err(e) => {
+                self.status = format!("❌ {}", e);
+                return;
+            }
+        };
+        match self.rt.block_on(feedback_store::reply(&session, response)) {
+            Ok(()) => {
+                self.status = format!("✅ Feedback sent to {}", session_title(&session.request));
+                self.mode = Mode::Browse;
+                self.refresh();
+            }
+            Err(e) => self.status = format!("❌ {}", e),
+        }
+    }
+
+    fn cancel_current(&mut self) {
+        let Some(session) = self.current().cloned() else {
+            return;
+        };
+        match self.rt.block_on(feedback_store::cancel(&session)) {
+            Ok(()) => {
+                self.status = format!("🛑 Cancelled {}", session_title(&session.request));
+                self.refresh();
+            }
+            Err(e) => self.status = format!("❌ {}", e),
+        }
+    }
+
+    fn draw(&mut self, frame: &mut Frame) {
+        let [main, status] = Layout::vertical([Constraint::Fill(1), Constraint::Length(1)]).areas(frame.area());
+        let [queue, detail] = Layout::horizontal([Constraint::Percentage(30), Constraint::Fill(1)]).areas(main);
+
+        let items: Vec<ListItem> = self
+            .sessions
+            .iter()
+            .map(|session| {
+                let request = &session.request;
+                ListItem::new(vec![
+                    Line::from(session_title(request)).bold(),
+                    Line::from(format!("{}  {}", format_time(request), text(request, "aiSourceDisplay"))).dark_gray(),
+                ])
+            })
+            .collect();
+        let list = List::new(items)
+            .block(Block::bordered().title(format!(" Pending ({}) ", self.sessions.len())))
+            .highlight_style(Style::new().bg(Color::DarkGray))
+            .highlight_symbol("▶ ");
+        frame.render_stateful_widget(list, queue, &mut self.list_state);
+
+        match self.current() {
+            Some(session) => self.draw_session(frame, detail, session),
+            None => frame.render_widget(
+                Paragraph::new("No pending feedback requests. New requests appear here automatically.")
+                    .block(Block::bordered())
+                    .dark_gray(),
+                detail,
+            ),
+        }
+
+        let hints = match self.mode {
+            Mode::Browse => "↑↓ select  Enter reply  1-9 option  s send  c cancel  PgUp/PgDn scroll  r refresh  q quit",
+            Mode::Compose => "Enter send  Alt+Enter/Ctrl+J newline  Esc back",
+            Mode::ConfirmCancel => "y confirm  any other key abort",
+        };
+        let status_line = if self.status.is_empty() {
+            Line::from(hints.dark_gray())
+        } else {
+            Line::from(vec![Span::raw(&self.status), Span::raw("  "), hints.dark_gray()])
+        };
+        frame.render_widget(Paragraph::new(status_line), status);
+    }
+
+    fn draw_session(&self, frame: &mut Frame, area: Rect, session: &PendingSession) {
+        let request = &session.request;
+        let draft = self.drafts.get(session.session_id());
+        let options = request["options"].as_array().cloned().unwrap_or_default();
+        let option_height = if options.is_empty() { 0 } else { options.len() as u16 + 2 };
+        let [message, choices, input] = Layout::vertical([
+            Constraint::Fill(1),
+            Constraint::Length(option_height),
+            Constraint::Length(7),
+        ])
+        .areas(area);
+
+        let title = format!(" {} · {} · {} ", text(request, "aiSourceDisplay"), session_title(request), format_time(request));
+        let paragraph = Paragraph::new(Text::from(render_markdown(&text(request, "aiResponse"))))
+            .block(Block::bordered().title(title))
+            .wrap(Wrap { trim: false })
+            .scroll((self.scroll, 0));
+        frame.render_widget(paragraph, message);
+
+        if !options.is_empty() {
+            let selected = draft.map(|draft| draft.selected_options.as_slice()).unwrap_or_default();
+            let lines: Vec<Line> = options
+                .iter()
+                .enumerate()
+                .map(|(index, option)| {
+                    let value = option["value"].as_str().unwrap_or_default();
+                    let checked = selected.iter().any(|selected| selected == value);
+                    let label = option["label"].as_str().unwrap_or(value);
+                    let line = Line::from(format!("[{}] {}. {}", if checked { "x" } else { " " }, index + 1, label));
+                    if checked { line.green() } else { line }
+                })
+                .collect();
+            let title = if request["multiSelect"].as_bool().unwrap_or(false) { " Options (any) " } else { " Options (one) " };
+            frame.render_widget(Paragraph::new(lines).block(Block::bordered().title(title)), choices);
+        }
+
+        // 带表单的请求以 JSON 对象回复表单字段
+        let fields: Vec<&String> = request["fields"]["properties"].as_object().map(|p| p.keys().collect()).unwrap_or_default();
+        let title = if fields.is_empty() {
+            " Reply ".to_string()
+        } else {
+            format!(" Reply with a JSON object: {} ", fields.iter().map(|f| f.as_str()).collect::<Vec<_>>().join(", "))
+        };
+        let border = if self.mode == Mode::Compose { Style::new().yellow() } else { Style::new() };
+        let draft_text = draft.map(|draft| draft.text.as_str()).unwrap_or_default();
+        let visible = input.height.saturating_sub(2) as usize;
+        let lines: Vec<&str> = draft_text.split('\n').collect();
+        let shown: Vec<Line> = lines[lines.len().saturating_sub(visible)..].iter().map(|line| Line::from(*line)).collect();
+        if self.mode == Mode::Compose {
+            let last = shown.last().map(Line::width).unwrap_or(0) as u16;
+            let row = shown.len().max(1) as u16;
+            frame.set_cursor_position(Position::new(
+                (input.x + 1 + last).min(input.right().saturating_sub(2)),
+                input.y + row,
+            ));
+        }
+        frame.render_widget(Paragraph::new(shown).block(Block::bordered().title(title).border_style(border)), input);
+    }
+}
+
+fn text(request: &Value, key: &str) -> String {
+    request[key].as_str().unwrap_or_default().to_string()
+}
+
+fn session_title(request: &Value) -> String {
+    Some(text(request, "context"))
+        .filter(|context| !context.is_empty())
+        .unwrap_or_else(|| "Feedback".to_string())
+}
+
+fn format_time(request: &Value) -> String {
+    chrono::DateTime::parse_from_rfc3339(&text(request, "timestamp"))
+        .map(|time| time.with_timezone(&chrono::Local).format("%H:%M").to_string())
+        .unwrap_or_default()
+}
+
+// 根据请求类型组装回复：有表单字段时草稿需为 JSON 对象，其余为自由文本
+fn build_response(request: &Value, draft: Option<&Draft>) -> Result<FeedbackResponse> {
+    let text = draft.map(|draft| draft.text.trim().to_string()).unwrap_or_default();
+    let selected = draft.map(|draft| draft.selected_options.clone()).unwrap_or_default();
+    let mut response = FeedbackResponse {
+        selected_options: (!selected.is_empty()).then_some(selected),
+        ..Default::default()
+    };
+    if request["fields"]["properties"].is_object() && !text.is_empty() {
+        let values: Value = serde_json::from_str(&text).map_err(|e| anyhow::anyhow!("Form reply must be a JSON object: {}", e))?;
+        response.form_values = Some(values);
+    } else {
+        response.feedback = text;
+    }
+    if response.feedback.is_empty() && response.selected_options.is_none() && response.form_values.is_none() {
+        return Err(anyhow::anyhow!("Type a reply or choose an option first"));
+    }
+    Ok(response)
+}
+
+/// 把 Markdown 渲染为终端中的带样式文本
+fn render_markdown(markdown: &str) -> Vec<Line<'static>> {
+    let mut renderer = MarkdownRenderer::default();
+    for event in Parser::new_ext(markdown, Options::ENABLE_STRIKETHROUGH | Options::ENABLE_TASKLISTS) {
+        renderer.push(event);
+    }
+    renderer.flush();
+    while renderer.lines.last().is_some_and(|line| line.spans.is_empty()) {
+        renderer.lines.pop();
+    }
+    renderer.lines
+}
+
+#[derive(Default)]
+struct MarkdownRenderer {
+    lines: Vec<Line<'static>>,
+    current: Vec<Span<'static>>,
+    styles: Vec<Style>,
+    /// 嵌套列表，Some 为有序列表的下一个序号
+    lists: Vec<Option<u64>>,
+    quote_depth: usize,
+    in_code_block: bool,
+    link: Option<String>,
+}
+
+impl MarkdownRenderer {
+    fn style(&self) -> Style {
+        self.styles.last().copied().unwrap_or_default()
+    }
+
+    fn push_style(&mut self, style: Style) {
+        self.styles.push(self.style().patch(style));
+    }
+
+    fn prefix(&self) -> Vec<Span<'static>> {
+        let mut prefix = vec![Span::styled("│ ".repeat(self.quote_depth), Style::new().dark_gray())];
+        prefix.push(Span::raw("  ".repeat(self.lists.len().saturating_sub(1))));
+        prefix
+    }
+
+    fn flush(&mut self) {
+        if !self.current.is_empty() {
+            let mut spans = self.prefix();
+            spans.append(&mut self.current);
+            self.lines.push(Line::from(spans));
+        }
+    }
+
+    fn blank_line(&mut self) {
+        self.flush();
+        if self.lines.last().is_some_and(|line| !line.spans.is_empty()) {
+            self.lines.push(Line::default());
+        }
+    }
+
+    fn push(&mut self, event: Event) {
+        match event {
+            Event::Start(Tag::Heading { level, .. }) => {
+                self.blank_line();
+                let style = Style::new().bold().cyan();
+                self.push_style(if level == pulldown_cmark::HeadingLevel::H1 { style.underlined() } else { style });
+            }
+            Event::End(TagEnd::Heading(_)) => {
+                self.styles.pop();
+                self.blank_line();
+            }
+            Event::End(TagEnd::Paragraph) => {
+                if self.lists.is_empty() {
+                    self.blank_line();
+                } else {
+                    self.flush();
+                }
+            }
+            Event::Start(Tag::BlockQuote(_)) => {
+                self.flush();
+                self.quote_depth += 1;
+            }
+            Event::End(TagEnd::BlockQuote(_)) => {
+                self.flush();
+                self.quote_depth -= 1;
+                self.blank_line();
+            }
+            Event::Start(Tag::CodeBlock(_)) => {
+                self.blank_line();
+                self.in_code_block = true;
+            }
+            Event::End(TagEnd::CodeBlock) => {
+                self.in_code_block = false;
+                self.blank_line();
+            }
+            Event::Start(Tag::List(start)) => {
+                self.flush();
+                self.lists.push(start);
+            }
+            Event::End(TagEnd::List(_)) => {
+                self.flush();
+                self.lists.pop();
+                if self.lists.is_empty() {
+                    self.blank_line();
+                }
+            }
+            Event::Start(Tag::Item) => {
+                self.flush();
+                let marker = match self.lists.last_mut() {
+                    Some(Some(number)) => {
+                        *number += 1;
+                        format!("{}. ", *number - 1)
+                    }
+                    _ => "• ".to_string(),
+                };
+                self.current.push(Span::styled(marker, Style::new().yellow()));
+            }
+            Event::End(TagEnd::Item) => self.flush(),
+            Event::Start(Tag::Emphasis) => self.push_style(Style::new().italic()),
+            Event::Start(Tag::Strong) => self.push_style(Style::new().bold()),
+            Event::Start(Tag::Strikethrough) => self.push_style(Style::new().crossed_out()),
+            Event::Start(Tag::Link { dest_url, .. }) => {
+                self.push_style(Style::new().blue().underlined());
+                self.link = Some(dest_url.to_string());
+            }
+            Event::End(TagEnd::Emphasis | TagEnd::Strong | TagEnd::Strikethrough) => {
+                self.styles.pop();
+            }
+            Event::End(TagEnd::Link) => {
+                self.styles.pop();
+                if let Some(url) = self.link.take() {
+                    self.current.push(Span::styled(format!(" ({})", url), Style::new().dark_gray()));
+                }
+            }
+            Event::Text(text) if self.in_code_block => {
+                for line in text.lines() {
+                    self.current.push(Span::styled(format!("  {}", line), Style::new().yellow()));
+                    self.flush();
+                }
+            }
+            Event::Text(text) => self.current.push(Span::styled(text.to_string(), self.style())),
+            Event::Code(code) => self.current.push(Span::styled(code.to_string(), Style::new().yellow())),
+            Event::SoftBreak => self.current.push(Span::raw(" ")),
+            Event::HardBreak => self.flush(),
+            Event::Rule => {
+                self.blank_line();
+                self.lines.push(Line::from("─".repeat(40)).dark_gray());
+                self.lines.push(Line::default());
+            }
+            Event::TaskListMarker(checked) => self.current.push(Span::raw(if checked { "[x] " } else { "[ ] " })),
+            Event::Html(html) | Event::InlineHtml(html) => self.current.push(Span::raw(html.to_string())),
+            _ => {}
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ratatui::style::Modifier;
+    use serde_json::json;
+
+    fn plain(lines: &[Line]) -> Vec<String> {
+        lines
+            .iter()
+            .map(|line| line.spans.iter().map(|span| span.content.as_ref()).collect::<String>().trim_end().to_string())
+            .collect()
+    }
+
+    #[test]
+    fn test_render_markdown() {
+        let lines = render_markdown("# Done\n\nAdded **tests** and `cargo test`.\n\n1. first\n2. second\n   - nested\n\n> note\n\n```\nfn main() {}\n```");
+        assert_eq!(
+            plain(&lines),
+            vec!["Done", "", "Added tests and cargo test.", "", "1. first", "2. second", "  • nested", "", "│ note", "", "  fn main() {}"]
+        );
+        assert!(lines[2].spans.iter().any(|span| span.content == "tests" && span.style.add_modifier.contains(Modifier::BOLD)));
+    }
+
+    #[test]
+    fn test_build_response() {
+        let draft = |text: &str, options: &[&str]| Draft {
+            text: text.to_string(),
+            selected_options: options.iter().map(|o| o.to_string()).collect(),
+        };
+        let request = json!({ "aiResponse": "ok?" });
+        assert!(build_response(&request, None).is_err());
+        let response = build_response(&request, Some(&draft(" continue \n", &["yes"]))).unwrap();
+        assert_eq!(response.feedback, "continue");
+        assert_eq!(response.selected_options, Some(vec!["yes".to_string()]));
+
+        let request = json!({ "fields": { "properties": { "name": { "type": "string" } } } });
+        let response = build_response(&request, Some(&draft(r#"{"name": "x"}"#, &[]))).unwrap();
+        assert_eq!(response.form_values, Some(json!({ "name": "x" })));
+        assert!(build_response(&request, Some(&draft("not json", &[]))).is_err());
+    }
+}
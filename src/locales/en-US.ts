@@ -9,7 +9,8 @@ export default {
   nav: {
     dashboard: 'Dashboard',
     settings: 'Settings',
-    feedback: 'Feedback'
+    feedback: 'Feedback',
+    history: 'History'
   },
   
   // Status
@@ -110,6 +111,36 @@ export default {
       'en-US': 'English'
     },
     
+    // Quick replies
+    quickReplies: {
+      title: 'Quick Replies',
+      description: 'Reusable replies shown above the feedback input. Click one or press its shortcut to insert it.',
+      name: 'Name',
+      content: 'Reply text',
+      shortcut: 'Shortcut (press keys, Backspace to clear)',
+      variables: 'Variables: {variables}',
+      sourceDefaults: 'Default reply per AI source',
+      source: 'AI source (MCP_SOURCE)'
+    },
+
+    // Feedback timeout
+    feedbackTimeout: {
+      title: 'Feedback Timeout',
+      description: 'How long the feedback tool waits for a reply before giving up. Applies to stdio and HTTP clients; a timeout passed by the AI takes precedence.',
+      timeoutSeconds: 'Timeout in seconds (0 waits forever)',
+      onTimeout: 'When the timeout expires',
+      policies: {
+        default_reply: 'Send the default reply',
+        cancel: 'Cancel the request',
+        escalate: 'Tell the AI to stop and wait for a human'
+      },
+      defaultReply: 'Default reply',
+      defaultReplyPlaceholder: 'Leave empty to use the built-in reply',
+      saved: 'Saved',
+      invalidTimeout: 'The timeout must be a whole number of seconds',
+      overridden: 'Overridden by environment variables: {variables}'
+    },
+
     // Placeholder content
     comingSoon: 'More settings coming soon',
     comingSoonHint: 'Configuration options will be available here'
@@ -129,12 +160,44 @@ export default {
     history: 'Feedback History',
     customEmphasis: 'Custom Emphasis',
     customEmphasisPlaceholder: 'Enter content to emphasize...',
+    attach: 'Attach',
+    attachHint: 'Attach files, or paste / drop screenshots and logs here',
+    tooManyAttachments: 'At most {count} attachments',
+    attachmentTooLarge: '{name} is larger than {limit}',
+    timeoutIn: 'Reply within {time}',
+    onTimeout: {
+      default_reply: 'Default reply is sent on timeout',
+      cancel: 'Session is cancelled on timeout',
+      escalate: 'AI is told to stop and wait for a human on timeout'
+    },
     empty: {
       title: 'No feedback sessions',
       description: 'Interactive feedback interface will appear here when AI calls the feedback tool'
     }
   },
 
+  // Feedback history page
+  history: {
+    title: 'Feedback History',
+    subtitle: 'Search past feedback sessions and their answers',
+    searchPlaceholder: 'Search questions, context and answers...',
+    allStatuses: 'All statuses',
+    source: 'AI source',
+    context: 'Context',
+    since: 'From',
+    until: 'To',
+    search: 'Search',
+    export: 'Export',
+    exportFormat: 'Export format',
+    loadMore: 'Load more',
+    empty: 'No feedback history',
+    status: {
+      answered: 'Answered',
+      cancelled: 'Cancelled',
+      timed_out: 'Timed out'
+    }
+  },
+
   // Common buttons and actions
   common: {
     save: 'Save',
@@ -9,7 +9,8 @@ export default {
   nav: {
     dashboard: '仪表板',
     settings: '设置',
-    feedback: 'Feedback'
+    feedback: 'Feedback',
+    history: '历史'
   },
   
   // 状态
@@ -110,6 +111,36 @@ export default {
       'en-US': 'English'
     },
     
+    // 快捷回复
+    quickReplies: {
+      title: '快捷回复',
+      description: '显示在反馈输入框上方的常用回复，点击或按快捷键即可插入',
+      name: '名称',
+      content: '回复内容',
+      shortcut: '快捷键 (直接按下组合键，Backspace 清除)',
+      variables: '可用变量：{variables}',
+      sourceDefaults: '按 AI 来源设置默认回复',
+      source: 'AI 来源 (MCP_SOURCE)'
+    },
+
+    // 反馈超时
+    feedbackTimeout: {
+      title: '反馈超时',
+      description: '反馈工具等待回复的时长，对 stdio 和 HTTP 客户端都生效；AI 调用时指定的超时优先',
+      timeoutSeconds: '超时秒数 (0 表示一直等待)',
+      onTimeout: '超时后',
+      policies: {
+        default_reply: '发送默认回复',
+        cancel: '取消请求',
+        escalate: '要求 AI 停下等待人工处理'
+      },
+      defaultReply: '默认回复',
+      defaultReplyPlaceholder: '留空使用内置回复',
+      saved: '已保存',
+      invalidTimeout: '超时必须是整数秒',
+      overridden: '已被环境变量覆盖：{variables}'
+    },
+
     // 占位内容
     comingSoon: '更多设置即将推出',
     comingSoonHint: '配置选项将在此处提供'
@@ -129,12 +160,44 @@ export default {
     history: '反馈历史',
     customEmphasis: '自定义强调语',
     customEmphasisPlaceholder: '请输入要强调的内容...',
+    attach: '附件',
+    attachHint: '添加文件，也可以直接粘贴或拖入截图、日志',
+    tooManyAttachments: '最多 {count} 个附件',
+    attachmentTooLarge: '{name} 超过了 {limit} 的大小限制',
+    timeoutIn: '剩余 {time}',
+    onTimeout: {
+      default_reply: '超时后发送默认回复',
+      cancel: '超时后取消会话',
+      escalate: '超时后要求 AI 停止并等待人工处理'
+    },
     empty: {
       title: '暂无反馈会话',
       description: '当 AI 调用 feedback 工具时，会在这里显示交互式反馈界面'
     }
   },
 
+  // 反馈历史页面
+  history: {
+    title: '反馈历史',
+    subtitle: '搜索以往的反馈会话和回复',
+    searchPlaceholder: '搜索问题、上下文和回复...',
+    allStatuses: '全部状态',
+    source: 'AI 来源',
+    context: '上下文',
+    since: '开始日期',
+    until: '结束日期',
+    search: '搜索',
+    export: '导出',
+    exportFormat: '导出格式',
+    loadMore: '加载更多',
+    empty: '暂无反馈历史',
+    status: {
+      answered: '已回复',
+      cancelled: '已取消',
+      timed_out: '已超时'
+    }
+  },
+
   // 通用按钮和操作
   common: {
     save: '保存',
@@ -2,6 +2,7 @@ import { createRouter, createWebHistory } from 'vue-router'
 import Dashboard from '../views/Dashboard.vue'
 import Settings from '../views/Settings.vue'
 import Feedback from '../views/Feedback.vue'
+import History from '../views/History.vue'
 
 const routes = [
   {
@@ -22,6 +23,11 @@ const routes = [
     path: '/feedback',
     name: 'Feedback',
     component: Feedback
+  },
+  {
+    path: '/history',
+    name: 'History',
+    component: History
   }
 ]
 
@@ -9,6 +9,34 @@ export interface FeedbackData {
   timestamp: string
   aiSource?: string
   aiSourceDisplay?: string
+  timeoutSeconds?: number | null // 等待超时秒数
+  onTimeout?: 'default_reply' | 'cancel' | 'escalate' | null // 超时后的处理策略
+  expiresAt?: string | null // 超时时间
+  options?: FeedbackOption[] | null // 可选项
+  multiSelect?: boolean | null // 是否允许多选
+  fields?: FeedbackFormSchema | null // 表单字段
+  allowFreeText?: boolean // 是否显示自由文本输入框
+  silent?: boolean // 自动回复规则静默转交，不提醒用户
+}
+
+export interface FeedbackOption {
+  value: string
+  label: string
+  description?: string | null
+}
+
+export interface FeedbackFormField {
+  type: 'string' | 'number' | 'integer' | 'boolean'
+  title?: string
+  description?: string
+  enum?: string[]
+  default?: string | number | boolean
+}
+
+export interface FeedbackFormSchema {
+  type: 'object'
+  properties: Record<string, FeedbackFormField>
+  required: string[]
 }
 
 export interface FeedbackTab {
@@ -64,7 +92,13 @@ export const useFeedbackStore = defineStore('feedback', () => {
         context: data.context,
         timestamp: data.timestamp,
         aiSource: data.aiSource,
-        aiSourceDisplay: data.aiSourceDisplay
+        aiSourceDisplay: data.aiSourceDisplay,
+        onTimeout: data.onTimeout,
+        expiresAt: data.expiresAt,
+        options: data.options,
+        multiSelect: data.multiSelect,
+        fields: data.fields,
+        allowFreeText: data.allowFreeText
       }
       // 更新tab的AI来源信息
       feedbackTabs.value[existingIndex].aiSource = data.aiSource
@@ -92,7 +126,13 @@ export const useFeedbackStore = defineStore('feedback', () => {
         context: data.context,
         timestamp: data.timestamp,
         aiSource: data.aiSource,
-        aiSourceDisplay: data.aiSourceDisplay
+        aiSourceDisplay: data.aiSourceDisplay,
+        onTimeout: data.onTimeout,
+        expiresAt: data.expiresAt,
+        options: data.options,
+        multiSelect: data.multiSelect,
+        fields: data.fields,
+        allowFreeText: data.allowFreeText
       },
       events: {
         feedback: handleFeedbackSubmit
@@ -322,9 +362,10 @@ export const useFeedbackStore = defineStore('feedback', () => {
       
       if (!activeElement) return false
       
-      // 检查是否是 feedback textarea 或 emphasis input
+      // 检查是否是 feedback textarea、emphasis input 或表单字段
       const isFeedbackTextarea = activeElement.classList.contains('feedback-textarea')
       const isEmphasisInput = activeElement.classList.contains('emphasis-input')
+      const isFormField = activeElement.classList.contains('field-input')
       
       // 检查是否在 feedback session 容器内
       const feedbackContainer = activeElement.closest('.feedback-session')
@@ -334,10 +375,11 @@ export const useFeedbackStore = defineStore('feedback', () => {
         classList: Array.from(activeElement.classList),
         isFeedbackTextarea,
         isEmphasisInput,
+        isFormField,
         inFeedbackContainer: !!feedbackContainer
       })
       
-      return (isFeedbackTextarea || isEmphasisInput) && !!feedbackContainer
+      return (isFeedbackTextarea || isEmphasisInput || isFormField) && !!feedbackContainer
     } catch (error) {
       console.error('❌ Error checking input focus:', error)
       return false
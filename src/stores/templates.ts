@@ -0,0 +1,94 @@
+import { defineStore } from 'pinia'
+import { ref } from 'vue'
+import { invoke } from '@tauri-apps/api/core'
+
+export interface ReplyTemplate {
+  id: string
+  name: string
+  content: string // 可以使用 {context}、{source}、{source_display}、{session_id}、{date}、{time} 变量
+  shortcut?: string | null // 如 Alt+1
+}
+
+export interface TemplateLibrary {
+  templates: ReplyTemplate[]
+  sourceDefaults: Record<string, string> // AI 来源 -> 默认模板 id
+}
+
+export interface TemplateSession {
+  sessionId: string
+  context: string
+  aiSource?: string
+  aiSourceDisplay?: string
+}
+
+// 把键盘事件转换为与后端一致的快捷键格式，如 Ctrl+Shift+R
+export function shortcutFromEvent(event: KeyboardEvent) {
+  const modifiers = [
+    event.ctrlKey && 'Ctrl',
+    event.altKey && 'Alt',
+    event.shiftKey && 'Shift',
+    event.metaKey && 'Meta'
+  ].filter(Boolean) as string[]
+  // 使用 code 而不是 key，避免 macOS 上 Alt 组合键产生特殊字符
+  let key = event.code.startsWith('Key') ? event.code.slice(3)
+    : event.code.startsWith('Digit') ? event.code.slice(5)
+    : event.key
+  if (key.length === 1) key = key.toUpperCase()
+  return [...modifiers, key].join('+')
+}
+
+export const useTemplatesStore = defineStore('templates', () => {
+  const library = ref<TemplateLibrary>({ templates: [], sourceDefaults: {} })
+  const loaded = ref(false)
+
+  async function load() {
+    try {
+      library.value = await invoke<TemplateLibrary>('get_reply_templates')
+      loaded.value = true
+    } catch (error) {
+      console.error('Failed to load reply templates:', error)
+    }
+  }
+
+  async function ensureLoaded() {
+    if (!loaded.value) await load()
+  }
+
+  // 以下操作失败时抛出后端返回的错误信息，由调用方显示
+  async function saveTemplate(template: ReplyTemplate) {
+    library.value = await invoke<TemplateLibrary>('save_reply_template', { template })
+  }
+
+  async function deleteTemplate(templateId: string) {
+    library.value = await invoke<TemplateLibrary>('delete_reply_template', { templateId })
+  }
+
+  async function setSourceDefault(aiSource: string, templateId: string | null) {
+    library.value = await invoke<TemplateLibrary>('set_default_reply_template', { aiSource, templateId })
+  }
+
+  function render(templateId: string, session: TemplateSession) {
+    return invoke<string>('render_reply_template', { templateId, session })
+  }
+
+  function findByShortcut(shortcut: string) {
+    return library.value.templates.find(template => template.shortcut === shortcut) || null
+  }
+
+  function defaultFor(aiSource?: string) {
+    const templateId = aiSource ? library.value.sourceDefaults[aiSource] : undefined
+    return templateId ? library.value.templates.find(template => template.id === templateId) || null : null
+  }
+
+  return {
+    library,
+    load,
+    ensureLoaded,
+    saveTemplate,
+    deleteTemplate,
+    setSourceDefault,
+    render,
+    findByShortcut,
+    defaultFor
+  }
+})